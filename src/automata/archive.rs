@@ -0,0 +1,241 @@
+use automata::{Semiring,Label};
+use automata::vector::VectorFSA;
+use automata::binary::{BinaryCodec,BinaryError,ConstFSA,write_binary};
+use automata::binary::{encode_u32,encode_u64,decode_u32,decode_u64,pad,padded};
+
+use memmap::Mmap;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+const MAGIC: &[u8] = b"RUSTREFA";
+const VERSION: u32 = 1;
+
+struct Entry {
+    key: String,
+    start: usize,
+    end: usize,
+}
+
+/// Writer for archives of named machines
+///
+/// An archive is a header followed by machines in the native binary format,
+/// each aligned to 8 bytes, and an index of keys at the end. Since the index
+/// is written last, `finish` must be called after adding all the machines.
+pub struct ArchiveWriter<'a> {
+    dest: &'a mut Write,
+    pos: usize,
+    entries: Vec<Entry>,
+}
+
+impl<'a> ArchiveWriter<'a> {
+    pub fn new(dest: &'a mut Write) -> Result<Self, BinaryError> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        encode_u32(VERSION, &mut buf);
+        encode_u32(0, &mut buf); // reserved
+        dest.write_all(&buf)?;
+        Ok(ArchiveWriter {
+            dest: dest,
+            pos: buf.len(),
+            entries: Vec::new(),
+        })
+    }
+
+    /// Appends a machine with the key
+    ///
+    /// Keys must be unique in an archive.
+    pub fn add<W, L>(&mut self, key: &str, m: &VectorFSA<W, L>) -> Result<(), BinaryError>
+        where W: Semiring + BinaryCodec, L: Label + BinaryCodec {
+        if self.entries.iter().any(|e| e.key == key) {
+            return Err(BinaryError::InvalidData(format!("Duplicated key: {}", key)));
+        }
+        let mut buf = Vec::new();
+        write_binary(m, &mut buf)?;
+        let start = self.pos;
+        let end = start + buf.len();
+        pad(&mut buf);
+        self.dest.write_all(&buf)?;
+        self.pos += buf.len();
+        self.entries.push(Entry { key: key.to_string(), start: start, end: end });
+        Ok(())
+    }
+
+    /// Writes the index, and returns the number of machines in the archive
+    pub fn finish(self) -> Result<usize, BinaryError> {
+        let mut buf = Vec::new();
+        for e in self.entries.iter() {
+            encode_u64(e.start as u64, &mut buf);
+            encode_u64(e.end as u64, &mut buf);
+            encode_u32(e.key.len() as u32, &mut buf);
+            buf.extend_from_slice(e.key.as_bytes());
+            pad(&mut buf);
+        }
+        encode_u64(self.entries.len() as u64, &mut buf);
+        encode_u64(self.pos as u64, &mut buf);
+        self.dest.write_all(&buf)?;
+        Ok(self.entries.len())
+    }
+}
+
+/// Read-only archive of named machines
+///
+/// Machines are kept in the order of addition for sequential access, and can
+/// also be looked up by keys. Loaded machines share the underlying bytes.
+pub struct Archive {
+    data: Rc<AsRef<[u8]>>,
+    entries: Vec<Entry>,
+    index: BTreeMap<String, usize>,
+}
+
+impl Archive {
+    /// Maps the file into memory and reads the index
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BinaryError> {
+        let file = File::open(path)?;
+        // The mapping stays valid as long as nobody truncates the file.
+        let mmap = unsafe { Mmap::map(&file)? };
+        Archive::new(Rc::new(mmap))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, BinaryError> {
+        Archive::new(Rc::new(data))
+    }
+
+    fn new(data: Rc<AsRef<[u8]>>) -> Result<Self, BinaryError> {
+        let mut entries = Vec::new();
+        {
+            let bytes = (*data).as_ref();
+            if bytes.len() < 32 || &bytes[0..8] != MAGIC {
+                return Err(BinaryError::InvalidHeader("Bad magic number".to_string()));
+            }
+            let version = decode_u32(&bytes[8..]);
+            if version != VERSION {
+                return Err(BinaryError::UnsupportedVersion(version));
+            }
+            let nentries = decode_u64(&bytes[(bytes.len() - 16)..]) as usize;
+            let mut pos = decode_u64(&bytes[(bytes.len() - 8)..]) as usize;
+            let index_end = bytes.len() - 16;
+            for _ in 0..nentries {
+                if pos > index_end || index_end - pos < 20 {
+                    return Err(BinaryError::InvalidData("Truncated index".to_string()));
+                }
+                let start = decode_u64(&bytes[pos..]) as usize;
+                let end = decode_u64(&bytes[(pos + 8)..]) as usize;
+                let len = decode_u32(&bytes[(pos + 16)..]) as usize;
+                pos += 20;
+                if pos + len > index_end || start > end || end > index_end {
+                    return Err(BinaryError::InvalidData("Broken index".to_string()));
+                }
+                let key = String::from_utf8(bytes[pos..(pos + len)].to_vec())
+                    .map_err(|_| BinaryError::InvalidData("Key is not UTF-8".to_string()))?;
+                pos = padded(pos + len);
+                entries.push(Entry { key: key, start: start, end: end });
+            }
+        }
+        let mut index = BTreeMap::new();
+        for (i, e) in entries.iter().enumerate() {
+            if index.insert(e.key.clone(), i).is_some() {
+                return Err(BinaryError::InvalidData(format!("Duplicated key: {}", e.key)));
+            }
+        }
+        Ok(Archive {
+            data: data,
+            entries: entries,
+            index: index,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns keys in the order of addition
+    pub fn keys<'a>(&'a self) -> Box<'a + Iterator<Item=&'a str>> {
+        box self.entries.iter().map(|e| e.key.as_str())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Loads the machine with the key, or returns `None` if not found
+    pub fn get<W, L>(&self, key: &str) -> Option<Result<ConstFSA<W, L>, BinaryError>>
+        where W: Semiring + BinaryCodec, L: Label + BinaryCodec {
+        self.index.get(key).map(|i| self.get_at(*i))
+    }
+
+    /// Loads the `i`-th machine
+    ///
+    /// Panics if `i` is out of range.
+    pub fn get_at<W, L>(&self, i: usize) -> Result<ConstFSA<W, L>, BinaryError>
+        where W: Semiring + BinaryCodec, L: Label + BinaryCodec {
+        let e = &self.entries[i];
+        ConstFSA::from_shared(self.data.clone(), e.start..e.end)
+    }
+
+    /// Returns pairs of keys and machines in the order of addition
+    pub fn iter<'a, W, L>(&'a self)
+                          -> Box<'a + Iterator<Item=(&'a str, Result<ConstFSA<W, L>, BinaryError>)>>
+        where W: 'a + Semiring + BinaryCodec, L: 'a + Label + BinaryCodec {
+        box (0..self.len()).map(move |i| (self.entries[i].key.as_str(), self.get_at(i)))
+    }
+}
+
+#[test]
+pub fn archive_test() {
+    use automata::{LoadTSV,DumpTSV,FSA};
+    use automata::vector::{ByteVectorFSA,ByteLabel};
+    use std::env;
+    use std::fs;
+
+    let src_a = "
+0	1	97	true
+1	true
+".trim();
+    let src_b = "
+0	1	98	true
+1	2	99	true
+2	true
+".trim();
+    let mut buf = Vec::<u8>::new();
+    {
+        let mut writer = ArchiveWriter::new(&mut buf).expect("Write failed");
+        writer.add("rule_b", &ByteVectorFSA::load_tsv(src_b.as_bytes())).expect("Write failed");
+        writer.add("rule_a", &ByteVectorFSA::load_tsv(src_a.as_bytes())).expect("Write failed");
+        match writer.add("rule_a", &ByteVectorFSA::load_tsv(src_a.as_bytes())) {
+            Err(BinaryError::InvalidData(_)) => {}
+            other => panic!("Unexpected result: {:?}", other)
+        }
+        assert_eq!(writer.finish().expect("Write failed"), 2);
+    }
+
+    let archive = Archive::from_bytes(buf.clone()).expect("Load failed");
+    assert_eq!(archive.len(), 2);
+    assert!(archive.keys().eq(vec!["rule_b", "rule_a"].into_iter()));
+    assert!(archive.contains_key("rule_a"));
+    assert!(archive.get::<bool, ByteLabel>("rule_c").is_none());
+
+    let rule_a = archive.get::<bool, ByteLabel>("rule_a").unwrap().expect("Load failed");
+    let mut dump_buf = Vec::<u8>::new();
+    rule_a.to_vector().expect("Invalid data").dump_tsv(&mut dump_buf);
+    assert_eq!(String::from_utf8(dump_buf).expect("UTF-8 error").trim(), src_a);
+
+    let nstates: Vec<_> = archive.iter::<bool, ByteLabel>()
+        .map(|(k, m)| (k, m.expect("Load failed").nstates()))
+        .collect();
+    assert_eq!(nstates, vec![("rule_b", Some(3)), ("rule_a", Some(2))]);
+
+    let path = env::temp_dir().join(format!("rustre-archive-test-{}.far", ::std::process::id()));
+    fs::File::create(&path).and_then(|mut f| f.write_all(&buf)).expect("Write failed");
+    let mapped = Archive::open(&path).expect("Open failed");
+    let rule_b = mapped.get::<bool, ByteLabel>("rule_b").unwrap().expect("Load failed");
+    let mut dump_buf = Vec::<u8>::new();
+    rule_b.to_vector().expect("Invalid data").dump_tsv(&mut dump_buf);
+    assert_eq!(String::from_utf8(dump_buf).expect("UTF-8 error").trim(), src_b);
+    fs::remove_file(&path).expect("Remove failed");
+
+    assert!(Archive::from_bytes(buf[..(buf.len() - 8)].to_vec()).is_err());
+}
@@ -1,8 +1,10 @@
 use automata::{StateMachine,FSA};
 use automata::vector::VectorFSA;
 use automata::lazy::ArcCache;
+use automata::symbol::SymbolTable;
 
 use std::cmp::Ordering;
+use std::rc::Rc;
 
 #[allow(unused_imports)]
 use test::Bencher;
@@ -56,6 +58,14 @@ impl<M, F> StateMachine for ArcSortStateMachine<M, F>
             box v.into_iter()
         })
     }
+
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.source.input_symbols()
+    }
+
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.source.output_symbols()
+    }
 }
 
 pub fn arcsort<M: FSA, F: Fn(&M::Arc, &M::Arc) -> Ordering>(m: M, f: F) ->
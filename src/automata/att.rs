@@ -0,0 +1,279 @@
+use automata::{StateMachine,Semiring,Arc,SimpleArc,MutableStateMachine,Label,i64state};
+use automata::vector::VectorFSA;
+use automata::symbol::{SymbolTable,SymbolLabel};
+
+use std::collections::BTreeMap;
+use std::io::{self,Write,Read,BufReader,BufRead};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::rc::Rc;
+
+/// Errors in reading or writing the AT&T text format
+#[derive(Debug)]
+pub enum AttError {
+    Io(io::Error),
+    /// Malformed input with the line number starting from 1
+    Syntax(usize, String),
+    /// The label cannot be written as an integer id
+    Label(String),
+}
+
+impl From<io::Error> for AttError {
+    fn from(e: io::Error) -> AttError {
+        AttError::Io(e)
+    }
+}
+
+/// Returns the integer id for `label`, where epsilon is 0
+fn label_id<L: SymbolLabel + Display>(label: &L) -> Result<usize, AttError> {
+    match label.symbol_id() {
+        None => Ok(0),
+        Some(0) => Err(AttError::Label(format!("{} collides with epsilon", label))),
+        Some(id) => Ok(id)
+    }
+}
+
+fn format_label<L: SymbolLabel + Display>(syms: Option<&SymbolTable>, label: &L)
+                                          -> Result<String, AttError> {
+    let id = label_id(label)?;
+    Ok(syms.and_then(|t| t.find_symbol(id)).map(|s| s.to_string())
+       .unwrap_or(id.to_string()))
+}
+
+fn parse_label<L: SymbolLabel>(syms: Option<&SymbolTable>, s: &str, lineno: usize)
+                               -> Result<L, AttError> {
+    let id = match syms {
+        Some(t) => t.find_id(s)
+            .ok_or(AttError::Syntax(lineno, format!("Unknown symbol: {}", s)))?,
+        None => usize::from_str(s)
+            .map_err(|_| AttError::Syntax(lineno, format!("Invalid label: {}", s)))?
+    };
+    if id == 0 {
+        return Ok(L::epsilon());
+    }
+    L::from_symbol_id(id).ok_or(AttError::Syntax(lineno, format!("Label out of range: {}", s)))
+}
+
+fn parse_weight<W: Semiring + FromStr>(s: Option<&&str>, lineno: usize) -> Result<W, AttError> {
+    match s {
+        None => Ok(W::one()),
+        Some(s) => W::from_str(s)
+            .map_err(|_| AttError::Syntax(lineno, format!("Invalid weight: {}", s)))
+    }
+}
+
+/// Maps state ids in text to states in order of appearance
+///
+/// As `fstcompile` does, the source state of the first line becomes the
+/// initial state.
+fn get_state<W: Semiring, L: Label>(ret: &mut VectorFSA<W, L>,
+                                    statemap: &mut BTreeMap<usize, i64state>,
+                                    s: &str, lineno: usize) -> Result<i64state, AttError> {
+    let id = usize::from_str(s)
+        .map_err(|_| AttError::Syntax(lineno, format!("Invalid state: {}", s)))?;
+    if let Some(st) = statemap.get(&id) {
+        return Ok(*st);
+    }
+    let st = ret.add_new_state();
+    statemap.insert(id, st);
+    Ok(st)
+}
+
+fn read_lines<W, L, R, F>(src: R, arc_columns: usize, mut parse_arc_label: F)
+                          -> Result<VectorFSA<W, L>, AttError>
+    where W: Semiring + FromStr, L: Label, R: Read,
+          F: FnMut(&[&str], usize) -> Result<L, AttError> {
+    let mut ret = VectorFSA::new();
+    let mut statemap = BTreeMap::new();
+    for (idx, line_or_err) in BufReader::new(src).lines().enumerate() {
+        let line = line_or_err?;
+        let lineno = idx + 1;
+        let vals: Vec<&str> = line.split_whitespace().collect();
+        if vals.is_empty() {
+            continue;
+        }
+        if vals.len() <= 2 {
+            let st = get_state(&mut ret, &mut statemap, vals[0], lineno)?;
+            let w = parse_weight(vals.get(1), lineno)?;
+            ret.set_final_weight(&st, w);
+        } else if vals.len() == arc_columns || vals.len() == arc_columns + 1 {
+            let p = get_state(&mut ret, &mut statemap, vals[0], lineno)?;
+            let q = get_state(&mut ret, &mut statemap, vals[1], lineno)?;
+            let l = parse_arc_label(&vals[2..arc_columns], lineno)?;
+            let w = parse_weight(vals.get(arc_columns), lineno)?;
+            ret.add_arc(&p, SimpleArc::new(l, w, q));
+        } else {
+            return Err(AttError::Syntax(lineno, format!("Unexpected number of columns: {}",
+                                                        vals.len())));
+        }
+    }
+    Ok(ret)
+}
+
+fn write_lines<W, L, F>(m: &VectorFSA<W, L>, dest: &mut Write, format_arc_label: F)
+                        -> Result<(), AttError>
+    where W: Semiring + Display, L: Label,
+          F: Fn(&L) -> Result<String, AttError> {
+    for st in m.states() {
+        for arc in m.arcs(&st) {
+            let mut line = format!("{}\t{}\t{}", st, arc.nextstate(),
+                                   format_arc_label(&arc.label())?);
+            if arc.weight() != W::one() {
+                line.push_str(&format!("\t{}", arc.weight()));
+            }
+            line.push('\n');
+            dest.write_all(line.as_bytes())?;
+        }
+        let fw = m.final_weight(&st);
+        if fw.is_nonzero() {
+            let line = if fw == W::one() {
+                format!("{}\n", st)
+            } else {
+                format!("{}\t{}\n", st, fw)
+            };
+            dest.write_all(line.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads an acceptor in the AT&T text format
+///
+/// Each line is either an arc `src dst label [weight]` or a final state
+/// `state [weight]`, where omitted weights mean `one()`. Labels are symbols
+/// in `syms` if given, and otherwise integer ids where 0 is epsilon. The
+/// symbol table is attached to the result as both input and output symbols.
+pub fn read_fsa<W, L, R>(src: R, syms: Option<Rc<SymbolTable>>)
+                         -> Result<VectorFSA<W, L>, AttError>
+    where W: Semiring + FromStr, L: SymbolLabel, R: Read {
+    let mut ret = {
+        let table = syms.as_ref().map(|t| &**t);
+        read_lines(src, 3, |cols, lineno| parse_label(table, cols[0], lineno))?
+    };
+    ret.set_input_symbols(syms.clone());
+    ret.set_output_symbols(syms);
+    Ok(ret)
+}
+
+/// Writes an acceptor, using its input symbols if attached
+pub fn write_fsa<W, L>(m: &VectorFSA<W, L>, dest: &mut Write) -> Result<(), AttError>
+    where W: Semiring + Display, L: SymbolLabel + Display {
+    let syms = m.input_symbols();
+    let table = syms.as_ref().map(|t| &**t);
+    write_lines(m, dest, |l| format_label(table, l))
+}
+
+/// Reads a transducer in the AT&T text format
+///
+/// Arcs are written as `src dst ilabel olabel [weight]`; see `read_fsa` for
+/// the others.
+pub fn read_fst<W, I, O, R>(src: R, isyms: Option<Rc<SymbolTable>>,
+                            osyms: Option<Rc<SymbolTable>>)
+                            -> Result<VectorFSA<W, (I, O)>, AttError>
+    where W: Semiring + FromStr, I: SymbolLabel, O: SymbolLabel, R: Read {
+    let mut ret = {
+        let itable = isyms.as_ref().map(|t| &**t);
+        let otable = osyms.as_ref().map(|t| &**t);
+        read_lines(src, 4, |cols, lineno| {
+            Ok((parse_label(itable, cols[0], lineno)?, parse_label(otable, cols[1], lineno)?))
+        })?
+    };
+    ret.set_input_symbols(isyms);
+    ret.set_output_symbols(osyms);
+    Ok(ret)
+}
+
+/// Writes a transducer, using its symbol tables if attached
+pub fn write_fst<W, I, O>(m: &VectorFSA<W, (I, O)>, dest: &mut Write) -> Result<(), AttError>
+    where W: Semiring + Display, I: SymbolLabel + Display, O: SymbolLabel + Display {
+    let isyms = m.input_symbols();
+    let osyms = m.output_symbols();
+    let itable = isyms.as_ref().map(|t| &**t);
+    let otable = osyms.as_ref().map(|t| &**t);
+    write_lines(m, dest, |&(ref i, ref o)| {
+        Ok(format!("{}\t{}", format_label(itable, i)?, format_label(otable, o)?))
+    })
+}
+
+/// Reads a symbol table file, where each line is `symbol id`
+pub fn read_symbols<R: Read>(src: R) -> Result<SymbolTable, AttError> {
+    let mut ret = SymbolTable::new();
+    for (idx, line_or_err) in BufReader::new(src).lines().enumerate() {
+        let line = line_or_err?;
+        let lineno = idx + 1;
+        let vals: Vec<&str> = line.split_whitespace().collect();
+        if vals.is_empty() {
+            continue;
+        }
+        if vals.len() != 2 {
+            return Err(AttError::Syntax(lineno, "Symbol table must have 2 columns".to_string()));
+        }
+        let id = usize::from_str(vals[1])
+            .map_err(|_| AttError::Syntax(lineno, format!("Invalid id: {}", vals[1])))?;
+        ret.add_symbol_with_id(vals[0], id).map_err(|what| AttError::Syntax(lineno, what))?;
+    }
+    Ok(ret)
+}
+
+/// Writes a symbol table file
+pub fn write_symbols(syms: &SymbolTable, dest: &mut Write) -> Result<(), AttError> {
+    for (id, sym) in syms.iter() {
+        dest.write_all(format!("{}\t{}\n", sym, id).as_bytes())?;
+    }
+    Ok(())
+}
+
+#[test]
+pub fn att_test() {
+    use automata::{Tropical,FSA};
+    use automata::vector::{ByteLabel,CharLabel};
+
+    let syms = Rc::new(read_symbols("
+<eps>	0
+a	97
+b	98
+".trim().as_bytes()).expect("Read failed"));
+
+    // state 5 appears first, so it becomes the initial state
+    let src = "
+5	7	a	0.5
+5	7	b
+7	5	<eps>
+7	1.5
+".trim();
+    let fsa: VectorFSA<Tropical<f32>, ByteLabel> =
+        read_fsa(src.as_bytes(), Some(syms.clone())).expect("Read failed");
+    assert_eq!(fsa.nstates(), Some(2));
+    assert_eq!(fsa.final_weight(&1), Tropical::new(1.5));
+    assert!(fsa.arcs(&1).all(|a| a.label() == ByteLabel::epsilon()));
+
+    let mut dump_buf = Vec::<u8>::new();
+    write_fsa(&fsa, &mut dump_buf).expect("Write failed");
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    println!(" === Result[AT&T] ===\n{}", dumped);
+    assert_eq!(dumped, "0\t1\ta\t0.5\n0\t1\tb\n1\t0\t<eps>\n1\t1.5\n");
+
+    let fst: VectorFSA<bool, (ByteLabel, CharLabel)> =
+        read_fst("0 1 97 0\n1 0 0 955\n0".as_bytes(), None, None).expect("Read failed");
+    let mut dump_buf = Vec::<u8>::new();
+    write_fst(&fst, &mut dump_buf).expect("Write failed");
+    assert_eq!(String::from_utf8(dump_buf).expect("UTF-8 error"),
+               "0\t1\t97\t0\n0\n1\t0\t0\t955\n");
+
+    let mut nul: VectorFSA<bool, ByteLabel> =
+        read_fsa("0 1 1".as_bytes(), None).expect("Read failed");
+    nul.add_arc(&0, SimpleArc::new(ByteLabel::from(0), true, 1));
+    match write_fsa(&nul, &mut io::sink()) {
+        Err(AttError::Label(_)) => {}
+        other => panic!("Unexpected result: {:?}", other)
+    }
+
+    match read_fsa::<bool, ByteLabel, _>("0 1 a".as_bytes(), None) {
+        Err(AttError::Syntax(1, _)) => {}
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+    }
+    match read_fsa::<bool, ByteLabel, _>("0 1 2\n0 1 2 3 4 5".as_bytes(), None) {
+        Err(AttError::Syntax(2, _)) => {}
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+    }
+}
@@ -0,0 +1,494 @@
+use automata::{StateMachine,FSA,Semiring,Arc,SimpleArc,Label,MutableStateMachine};
+use automata::{Tropical,Log,i64state};
+use automata::vector::{VectorFSA,ByteLabel,CharLabel};
+
+use memmap::Mmap;
+
+use std::fs::File;
+use std::io::{self,Read,Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::ops::Range;
+use std::rc::Rc;
+
+const MAGIC: &[u8] = b"RUSTREFS";
+/// Version of the format, bumped on incompatible changes
+pub const VERSION: u32 = 1;
+const ALIGN: usize = 8;
+
+/// Errors in reading or writing the native binary format
+#[derive(Debug)]
+pub enum BinaryError {
+    Io(io::Error),
+    /// Not a rustre binary file, or the header is broken
+    InvalidHeader(String),
+    UnsupportedVersion(u32),
+    /// Label or weight type in the file, and the requested one
+    TypeMismatch(String, String),
+    /// The file is shorter than the header says, or states are out of range
+    InvalidData(String),
+}
+
+impl From<io::Error> for BinaryError {
+    fn from(e: io::Error) -> BinaryError {
+        BinaryError::Io(e)
+    }
+}
+
+/// Trait for labels and weights stored in fixed-size little-endian records
+pub trait BinaryCodec : Sized {
+    /// Returns the name recorded in headers for checking types on loading
+    fn type_name() -> String;
+    fn size() -> usize;
+    fn encode(&self, dest: &mut Vec<u8>);
+    fn decode(src: &[u8]) -> Self;
+}
+
+pub fn encode_u32(v: u32, dest: &mut Vec<u8>) {
+    dest.extend((0..4).map(|i| (v >> (8 * i)) as u8));
+}
+
+pub fn encode_u64(v: u64, dest: &mut Vec<u8>) {
+    dest.extend((0..8).map(|i| (v >> (8 * i)) as u8));
+}
+
+pub fn decode_u32(src: &[u8]) -> u32 {
+    src[0..4].iter().rev().fold(0, |acc, x| (acc << 8) | (*x as u32))
+}
+
+pub fn decode_u64(src: &[u8]) -> u64 {
+    src[0..8].iter().rev().fold(0, |acc, x| (acc << 8) | (*x as u64))
+}
+
+/// Appends zeros up to the alignment of sections
+pub fn pad(dest: &mut Vec<u8>) {
+    while dest.len() % ALIGN != 0 {
+        dest.push(0);
+    }
+}
+
+pub fn padded(pos: usize) -> usize {
+    (pos + ALIGN - 1) / ALIGN * ALIGN
+}
+
+impl BinaryCodec for bool {
+    fn type_name() -> String { "bool".to_string() }
+    fn size() -> usize { 1 }
+    fn encode(&self, dest: &mut Vec<u8>) { dest.push(*self as u8) }
+    fn decode(src: &[u8]) -> Self { src[0] != 0 }
+}
+
+impl BinaryCodec for Tropical<f32> {
+    fn type_name() -> String { "tropical32".to_string() }
+    fn size() -> usize { 4 }
+    fn encode(&self, dest: &mut Vec<u8>) { encode_u32(self.value().to_bits(), dest) }
+    fn decode(src: &[u8]) -> Self { Tropical::new(f32::from_bits(decode_u32(src))) }
+}
+
+impl BinaryCodec for Log<f32> {
+    fn type_name() -> String { "log32".to_string() }
+    fn size() -> usize { 4 }
+    fn encode(&self, dest: &mut Vec<u8>) { encode_u32(self.value().to_bits(), dest) }
+    fn decode(src: &[u8]) -> Self { Log::new(f32::from_bits(decode_u32(src))) }
+}
+
+/// Stores bytes as 16-bit values, where 0x100 is epsilon
+impl BinaryCodec for ByteLabel {
+    fn type_name() -> String { "byte".to_string() }
+    fn size() -> usize { 2 }
+    fn encode(&self, dest: &mut Vec<u8>) {
+        let v = self.to_byte().map(|b| b as u16).unwrap_or(0x100);
+        dest.push(v as u8);
+        dest.push((v >> 8) as u8);
+    }
+    fn decode(src: &[u8]) -> Self {
+        if src[1] == 0 { ByteLabel::from(src[0]) } else { ByteLabel::epsilon() }
+    }
+}
+
+/// Stores code points as 32-bit values, where 0x110000 is epsilon
+impl BinaryCodec for CharLabel {
+    fn type_name() -> String { "char".to_string() }
+    fn size() -> usize { 4 }
+    fn encode(&self, dest: &mut Vec<u8>) {
+        encode_u32(self.to_char().map(|ch| ch as u32).unwrap_or(0x110000), dest)
+    }
+    fn decode(src: &[u8]) -> Self {
+        ::std::char::from_u32(decode_u32(src)).map(CharLabel::from).unwrap_or(CharLabel::epsilon())
+    }
+}
+
+impl<A: BinaryCodec, B: BinaryCodec> BinaryCodec for (A, B) {
+    fn type_name() -> String { format!("{}:{}", A::type_name(), B::type_name()) }
+    fn size() -> usize { A::size() + B::size() }
+    fn encode(&self, dest: &mut Vec<u8>) {
+        self.0.encode(dest);
+        self.1.encode(dest);
+    }
+    fn decode(src: &[u8]) -> Self {
+        (A::decode(src), B::decode(&src[A::size()..]))
+    }
+}
+
+/// Writes the machine in the native binary format
+///
+/// The file consists of a header, final weights, a table of offsets to the
+/// first arc of each state, and a flat array of arcs, where each section is
+/// aligned to 8 bytes. Symbol tables are not stored.
+pub fn write_binary<W, L>(m: &VectorFSA<W, L>, dest: &mut Write) -> Result<(), BinaryError>
+    where W: Semiring + BinaryCodec, L: Label + BinaryCodec {
+    let nstates = m.nstates().expect("Vector FSA must have a number of states");
+    let narcs: usize = m.states().map(|s| m.arcs_vec(&s).len()).sum();
+    if nstates > u32::max_value() as usize {
+        return Err(BinaryError::InvalidData(format!("Too many states: {}", nstates)));
+    }
+
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    encode_u32(VERSION, &mut buf);
+    encode_u32(0, &mut buf); // reserved
+    encode_u64(nstates as u64, &mut buf);
+    encode_u64(narcs as u64, &mut buf);
+    for name in vec![L::type_name(), W::type_name()].into_iter() {
+        encode_u32(name.len() as u32, &mut buf);
+        buf.extend_from_slice(name.as_bytes());
+    }
+    pad(&mut buf);
+    dest.write_all(&buf)?;
+
+    buf.clear();
+    for s in m.states() {
+        m.final_weight(&s).encode(&mut buf);
+    }
+    pad(&mut buf);
+    let mut offset = 0;
+    for s in m.states() {
+        encode_u64(offset as u64, &mut buf);
+        offset += m.arcs_vec(&s).len();
+    }
+    encode_u64(offset as u64, &mut buf);
+    dest.write_all(&buf)?;
+
+    for s in m.states() {
+        buf.clear();
+        for arc in m.arcs_vec(&s).iter() {
+            arc.label().encode(&mut buf);
+            arc.weight().encode(&mut buf);
+            encode_u32(arc.nextstate() as u32, &mut buf);
+        }
+        dest.write_all(&buf)?;
+    }
+    Ok(())
+}
+
+/// Reads the whole machine in the native binary format into memory
+pub fn read_binary<W, L, R>(src: R) -> Result<VectorFSA<W, L>, BinaryError>
+    where W: Semiring + BinaryCodec, L: Label + BinaryCodec, R: Read {
+    let mut src = src;
+    let mut data = Vec::new();
+    src.read_to_end(&mut data)?;
+    ConstFSA::from_bytes(data)?.to_vector()
+}
+
+/// Read-only machine reading the native binary format in place
+///
+/// Arcs are decoded from the underlying bytes on each access, so that opening
+/// a memory-mapped file doesn't need to parse the whole file. Only the header
+/// and section sizes are checked on opening; use `validate` for files that
+/// may be broken.
+pub struct ConstFSA<W: Semiring + BinaryCodec, L: Label + BinaryCodec> {
+    data: Rc<AsRef<[u8]>>,
+    range: Range<usize>,
+    nstates: usize,
+    narcs: usize,
+    finals_at: usize,
+    offsets_at: usize,
+    arcs_at: usize,
+    phantom: PhantomData<(W, L)>,
+}
+
+impl<W: Semiring + BinaryCodec, L: Label + BinaryCodec> ConstFSA<W, L> {
+    /// Maps the file into memory and checks its header
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BinaryError> {
+        let file = File::open(path)?;
+        // The mapping stays valid as long as nobody truncates the file.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let len = mmap.len();
+        ConstFSA::from_shared(Rc::new(mmap), 0..len)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, BinaryError> {
+        let len = data.len();
+        ConstFSA::from_shared(Rc::new(data), 0..len)
+    }
+
+    /// Reads the machine stored in `range` of the shared bytes
+    ///
+    /// This is for containers holding several machines in a buffer; the
+    /// range must start at an 8-byte boundary of the buffer.
+    pub fn from_shared(data: Rc<AsRef<[u8]>>, range: Range<usize>) -> Result<Self, BinaryError> {
+        if range.end > (*data).as_ref().len() || range.start > range.end {
+            return Err(BinaryError::InvalidData("Range out of the buffer".to_string()));
+        }
+        let (nstates, narcs, header_len) = {
+            let bytes = &(*data).as_ref()[range.clone()];
+            if bytes.len() < 32 || &bytes[0..8] != MAGIC {
+                return Err(BinaryError::InvalidHeader("Bad magic number".to_string()));
+            }
+            let version = decode_u32(&bytes[8..]);
+            if version != VERSION {
+                return Err(BinaryError::UnsupportedVersion(version));
+            }
+            let nstates = decode_u64(&bytes[16..]) as usize;
+            let narcs = decode_u64(&bytes[24..]) as usize;
+            let mut pos = 32;
+            for expected in vec![L::type_name(), W::type_name()].into_iter() {
+                if bytes.len() < pos + 4 {
+                    return Err(BinaryError::InvalidHeader("Truncated header".to_string()));
+                }
+                let len = decode_u32(&bytes[pos..]) as usize;
+                pos += 4;
+                if bytes.len() < pos + len {
+                    return Err(BinaryError::InvalidHeader("Truncated header".to_string()));
+                }
+                let name = String::from_utf8_lossy(&bytes[pos..(pos + len)]).into_owned();
+                if name != expected {
+                    return Err(BinaryError::TypeMismatch(name, expected));
+                }
+                pos += len;
+            }
+            (nstates, narcs, padded(pos))
+        };
+
+        let overflow = || BinaryError::InvalidData("Section sizes overflow".to_string());
+        let arc_size = L::size() + W::size() + 4;
+        let finals_at = header_len;
+        let offsets_at = nstates.checked_mul(W::size())
+            .and_then(|n| n.checked_add(finals_at))
+            .and_then(|n| n.checked_add(ALIGN - 1))
+            .map(|n| n / ALIGN * ALIGN)
+            .ok_or_else(&overflow)?;
+        let arcs_at = nstates.checked_add(1)
+            .and_then(|n| n.checked_mul(8))
+            .and_then(|n| n.checked_add(offsets_at))
+            .ok_or_else(&overflow)?;
+        let end = narcs.checked_mul(arc_size)
+            .and_then(|n| n.checked_add(arcs_at))
+            .ok_or_else(&overflow)?;
+        let ret = ConstFSA {
+            data: data,
+            range: range,
+            nstates: nstates,
+            narcs: narcs,
+            finals_at: finals_at,
+            offsets_at: offsets_at,
+            arcs_at: arcs_at,
+            phantom: PhantomData,
+        };
+        if ret.bytes().len() < end {
+            return Err(BinaryError::InvalidData("Truncated file".to_string()));
+        }
+        Ok(ret)
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &(*self.data).as_ref()[self.range.clone()]
+    }
+
+    fn offset(&self, s: usize) -> usize {
+        decode_u64(&self.bytes()[(self.offsets_at + s * 8)..]) as usize
+    }
+
+    /// Returns the range of arcs of the state, or `None` if offsets are broken
+    fn arc_range(&self, s: usize) -> Option<Range<usize>> {
+        let (begin, end) = (self.offset(s), self.offset(s + 1));
+        if begin <= end && end <= self.narcs { Some(begin..end) } else { None }
+    }
+
+    fn arc_at(&self, idx: usize) -> SimpleArc<i64state, W, L> {
+        let arc_size = L::size() + W::size() + 4;
+        let src = &self.bytes()[(self.arcs_at + idx * arc_size)..];
+        let label = L::decode(src);
+        let weight = W::decode(&src[L::size()..]);
+        let nextstate = decode_u32(&src[(L::size() + W::size())..]);
+        SimpleArc::new(label, weight, nextstate as i64state)
+    }
+
+    /// Checks the offset table and the destinations of all arcs
+    ///
+    /// This reads the whole file, so it is not done on opening.
+    pub fn validate(&self) -> Result<(), BinaryError> {
+        let mut prev = 0;
+        for s in 0..self.nstates {
+            match self.arc_range(s) {
+                Some(ref r) if r.start == prev => { prev = r.end; }
+                _ => {
+                    return Err(BinaryError::InvalidData(
+                        format!("Broken offset table at state {}", s)));
+                }
+            }
+        }
+        if prev != self.narcs {
+            return Err(BinaryError::InvalidData("Broken offset table".to_string()));
+        }
+        for i in 0..self.narcs {
+            let nextstate = self.arc_at(i).nextstate();
+            if nextstate as usize >= self.nstates {
+                return Err(BinaryError::InvalidData(
+                    format!("Arc {} points to an invalid state {}", i, nextstate)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the machine into a `VectorFSA`, keeping state ids
+    ///
+    /// The machine is validated first, so broken files are reported as
+    /// `BinaryError::InvalidData`.
+    pub fn to_vector(&self) -> Result<VectorFSA<W, L>, BinaryError> {
+        self.validate()?;
+        let mut ret = VectorFSA::new();
+        for _ in 0..self.nstates {
+            ret.add_new_state();
+        }
+        for s in self.states() {
+            let fw = self.final_weight(&s);
+            if fw.is_nonzero() {
+                ret.set_final_weight(&s, fw);
+            }
+            for arc in self.arcs(&s) {
+                ret.add_arc(&s, arc);
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl<W: Semiring + BinaryCodec, L: Label + BinaryCodec> StateMachine for ConstFSA<W, L> {
+    type State = i64state;
+    type Arc = SimpleArc<i64state, W, L>;
+    type Weight = W;
+    type Label = L;
+
+    fn init_state(&self) -> i64state {
+        0
+    }
+
+    fn states<'a>(&'a self) -> Box<'a + Iterator<Item=i64state>> {
+        box (0..self.nstates).map(|s| s as i64state)
+    }
+
+    fn final_weight(&self, s: &i64state) -> W {
+        W::decode(&self.bytes()[(self.finals_at + (*s as usize) * W::size())..])
+    }
+
+    /// Returns arcs of the state
+    ///
+    /// Panics if the offsets of the state are out of the arc array. The
+    /// destinations of arcs are trusted as they are in the file; call
+    /// `validate` beforehand for files that may be broken.
+    fn arcs<'a>(&'a self, s: &i64state) -> Box<'a + Iterator<Item=Self::Arc>> {
+        let st = *s as usize;
+        let range = self.arc_range(st)
+            .unwrap_or_else(|| panic!("Broken offset table at state {}", st));
+        box range.map(move |i| self.arc_at(i))
+    }
+}
+
+impl<W: Semiring + BinaryCodec, L: Label + BinaryCodec> FSA for ConstFSA<W, L> {
+    fn nstates(&self) -> Option<usize> {
+        Some(self.nstates)
+    }
+}
+
+#[test]
+pub fn binary_test() {
+    use automata::{LoadTSV,DumpTSV};
+    use automata::vector::ByteVectorFSA;
+    use automata::rmeps::rmeps;
+    use std::env;
+    use std::fs;
+
+    let src = "
+0	1	97	true
+0	2	eps	true
+1	true
+1	1	98	true
+2	0	0	true
+".trim();
+    let fst_a = ByteVectorFSA::load_tsv(src.as_bytes());
+
+    let mut buf = Vec::<u8>::new();
+    write_binary(&fst_a, &mut buf).expect("Write failed");
+
+    let const_a = ConstFSA::<bool, ByteLabel>::from_bytes(buf.clone()).expect("Load failed");
+    assert_eq!(const_a.nstates(), Some(3));
+    let mut dump_buf = Vec::<u8>::new();
+    const_a.to_vector().expect("Invalid data").dump_tsv(&mut dump_buf);
+    assert_eq!(String::from_utf8(dump_buf).expect("UTF-8 error").trim(), src);
+
+    // generic algorithms work directly on the loaded machine
+    let mut dump_buf = Vec::<u8>::new();
+    rmeps(const_a).dump_tsv(&mut dump_buf);
+    let mut expected_buf = Vec::<u8>::new();
+    rmeps(fst_a.clone()).dump_tsv(&mut expected_buf);
+    assert_eq!(dump_buf, expected_buf);
+
+    match ConstFSA::<bool, CharLabel>::from_bytes(buf.clone()) {
+        Err(BinaryError::TypeMismatch(ref found, _)) if found == "byte" => {}
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+    }
+    let truncated = buf[..(buf.len() - 1)].to_vec();
+    match ConstFSA::<bool, ByteLabel>::from_bytes(truncated) {
+        Err(BinaryError::InvalidData(_)) => {}
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+    }
+
+    // offsets of states 0..3 ([0, 2, 3, 4]) are just before the arcs
+    let arcs_at = buf.len() - 4 * (ByteLabel::size() + bool::size() + 4);
+    let corrupt_offset = |s: usize, offset: u64| -> Vec<u8> {
+        let mut corrupted = buf.clone();
+        let at = arcs_at - (4 - s) * 8;
+        let mut encoded = Vec::new();
+        encode_u64(offset, &mut encoded);
+        corrupted[at..(at + 8)].copy_from_slice(&encoded);
+        corrupted
+    };
+    // broken offsets are found by validation rather than on opening
+    for &(s, offset) in [(1, 100), (1, 4), (2, 1), (3, 3)].iter() {
+        let corrupted = ConstFSA::<bool, ByteLabel>::from_bytes(corrupt_offset(s, offset))
+            .expect("Load failed");
+        match corrupted.validate() {
+            Err(BinaryError::InvalidData(_)) => {}
+            other => panic!("Unexpected result: {:?}", other)
+        }
+        assert!(read_binary::<bool, ByteLabel, _>(&corrupt_offset(s, offset)[..]).is_err());
+    }
+    // so are arcs pointing out of the states
+    let mut bad_target = buf.clone();
+    let at = bad_target.len() - 4;
+    bad_target[at] = 3;
+    let corrupted = ConstFSA::<bool, ByteLabel>::from_bytes(bad_target).expect("Load failed");
+    match corrupted.to_vector().map(|m| m.nstates()) {
+        Err(BinaryError::InvalidData(_)) => {}
+        other => panic!("Unexpected result: {:?}", other)
+    }
+    // huge counts must not overflow the size computation
+    let mut huge = buf.clone();
+    let mut encoded = Vec::new();
+    encode_u64(u64::max_value(), &mut encoded);
+    huge[16..24].copy_from_slice(&encoded);
+    match ConstFSA::<bool, ByteLabel>::from_bytes(huge) {
+        Err(BinaryError::InvalidData(_)) => {}
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+    }
+
+    let path = env::temp_dir().join(format!("rustre-binary-test-{}.fsa", ::std::process::id()));
+    fs::File::create(&path).and_then(|mut f| f.write_all(&buf)).expect("Write failed");
+    let mapped = ConstFSA::<bool, ByteLabel>::open(&path).expect("Open failed");
+    let mut dump_buf = Vec::<u8>::new();
+    read_binary::<bool, ByteLabel, _>(&buf[..]).expect("Read failed").dump_tsv(&mut dump_buf);
+    assert_eq!(String::from_utf8(dump_buf).expect("UTF-8 error").trim(), src);
+    assert!(mapped.arcs(&1).map(|a| a.label()).eq(vec![ByteLabel::from(98)].into_iter()));
+    fs::remove_file(&path).expect("Remove failed");
+}
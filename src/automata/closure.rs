@@ -1,8 +1,10 @@
 use automata::{StateMachine,FSA,Semiring,Arc,SimpleArc,Label,MutableStateMachine};
 use automata::vector::{VectorFSA};
 use automata::concat::ConcatStateMachine;
+use automata::symbol::SymbolTable;
 
 use std::iter::once;
+use std::rc::Rc;
 
 pub struct ClosurePlusMachine<M: StateMachine> {
     source: M,
@@ -41,6 +43,14 @@ impl<M: StateMachine> StateMachine for ClosurePlusMachine<M> {
             )
         }
     }
+
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.source.input_symbols()
+    }
+
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.source.output_symbols()
+    }
 }
 
 impl<M: StateMachine> ClosurePlusMachine<M> {
@@ -82,22 +92,22 @@ pub fn closure_plus_test() {
 
     let fst_a = ByteVectorFSA::load_tsv("
 0	true
-0	1	0	true
+0	1	eps	true
 1	1	1	true
 1	2	2	true
-2	1	0	true
+2	1	eps	true
 2	2	3	true
 2	true
 ".trim().as_bytes());
     let aplus_src = "
 0	true
-0	0	0	true
-0	1	0	true
+0	0	eps	true
+0	1	eps	true
 1	1	1	true
 1	2	2	true
 2	true
-2	0	0	true
-2	1	0	true
+2	0	eps	true
+2	1	eps	true
 2	2	3	true
 ".trim();
 
@@ -116,24 +126,24 @@ pub fn closure_star_test() {
 
     let fst_a = ByteVectorFSA::load_tsv("
 0	true
-0	1	0	true
+0	1	eps	true
 1	1	1	true
 1	2	2	true
-2	1	0	true
+2	1	eps	true
 2	2	3	true
 2	true
 ".trim().as_bytes());
     let astar_src = "
 0	true
-0	1	0	true
+0	1	eps	true
 1	true
-1	1	0	true
-1	2	0	true
+1	1	eps	true
+1	2	eps	true
 2	2	1	true
 2	3	2	true
 3	true
-3	1	0	true
-3	2	0	true
+3	1	eps	true
+3	2	eps	true
 3	3	3	true
 ".trim();
 
@@ -0,0 +1,63 @@
+use automata::{StateMachine,FSA,Arc,SimpleArc,Label,boolweight,MutableStateMachine};
+use automata::vector::{VectorFSA};
+
+use std::collections::BTreeSet;
+
+/// Complement a deterministic unweighted acceptor over the given alphabet
+///
+/// The input must be deterministic and epsilon-free, e.g. the output of
+/// `determinize`. Missing transitions are completed with a sink state, and
+/// then the final states are flipped. Labels that are not in `alphabet` are
+/// kept as is.
+pub fn complement<L>(m: VectorFSA<boolweight, L>, alphabet: &[L])
+                     -> VectorFSA<boolweight, L>
+    where L: Label + Ord {
+
+    let mut m = m;
+    let nstates = m.nstates().expect("Vector FSA must have a number of states");
+    let sink = m.add_new_state();
+
+    for stidx in 0..(nstates + 1) {
+        let st = stidx as i64;
+        let present: BTreeSet<L> = m.arcs(&st).map(|a| a.label()).collect();
+        for l in alphabet.iter() {
+            if ! present.contains(l) {
+                m.add_arc(&st, SimpleArc::new(l.clone(), true, sink));
+            }
+        }
+        let fw = m.final_weight(&st);
+        m.set_final_weight(&st, ! fw);
+    }
+
+    m
+}
+
+#[test]
+pub fn complement_test() {
+    use automata::{LoadTSV,DumpTSV};
+    use automata::vector::{ByteVectorFSA,ByteLabel};
+
+    let fst_a = ByteVectorFSA::load_tsv("
+0	1	1	true
+1	true
+1	1	2	true
+".trim().as_bytes());
+
+    let expected_src = "
+0	true
+0	1	1	true
+0	2	2	true
+1	1	2	true
+1	2	1	true
+2	true
+2	2	1	true
+2	2	2	true
+".trim();
+
+    let result = complement(fst_a, &[ByteLabel::from(1), ByteLabel::from(2)]);
+    let mut dump_buf = Vec::<u8>::new();
+    result.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    println!(" === Result[Complement] ===\n{}", dumped);
+    assert!(dumped.trim() == expected_src);
+}
@@ -1,6 +1,9 @@
 use automata::{StateMachine,FSA,Semiring,Arc,SimpleArc,Label,State};
 use automata::vector::VectorFSA;
 use automata::lazy::ArcCache;
+use automata::symbol::{SymbolTable,compatible_symbols};
+
+use std::rc::Rc;
 
 /// Trait for composition filter state machine
 pub trait CompositionFilter<LA: Arc, RA: Arc, W: Semiring> {
@@ -87,6 +90,14 @@ impl<L, R, M, F> StateMachine for CompositeStateMachine<L, R, M, F>
             panic!("not implemented")
         })
     }
+
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.left.input_symbols()
+    }
+
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.right.output_symbols()
+    }
 }
 
 impl<L, R, M, F> FSA for CompositeStateMachine<L, R, M, F>
@@ -103,6 +114,8 @@ impl<L, R, M, F> CompositeStateMachine<L, R, M, F>
           M: Matcher<L::Arc, R::Arc>,
           F: CompositionFilter<L::Arc, R::Arc, L::Weight> {
     fn new(left: L, right: R, matcher: M, filter: F) -> Self {
+        assert!(compatible_symbols(&left.output_symbols(), &right.input_symbols()),
+                "Output symbols of the left don't match input symbols of the right");
         CompositeStateMachine {
             left: left,
             right: right,
@@ -117,6 +130,8 @@ pub fn compose<L, R>(left: L, right: R)
                      -> VectorFSA<L::Weight, L::Label>
     where L: StateMachine,
           R: StateMachine {
+    assert!(compatible_symbols(&left.output_symbols(), &right.input_symbols()),
+            "Output symbols of the left don't match input symbols of the right");
     panic!("Not implemented")
 }
 
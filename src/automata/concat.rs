@@ -1,7 +1,9 @@
 use automata::{StateMachine,FSA,Semiring,Arc,SimpleArc,Label,State};
 use std::iter::once;
+use std::rc::Rc;
 use automata::vector::{VectorFSA};
 use automata::lazy::ArcCache;
+use automata::symbol::{SymbolTable,merge_symbols};
 
 use either::{Either,Left,Right};
 
@@ -14,6 +16,9 @@ impl<L: State, R: State> State for Either<L, R> {
 pub struct ConcatStateMachine<L: StateMachine, R: StateMachine<Weight=L::Weight, Label=L::Label>> {
     left: L,
     right: R,
+    /// Merged symbol tables, checked once at construction
+    isymbols: Option<Rc<SymbolTable>>,
+    osymbols: Option<Rc<SymbolTable>>,
     cache: ArcCache<Either<L::State, R::State>,
                     SimpleArc<Either<L::State, R::State>,
                               L::Weight, L::Label>>
@@ -82,14 +87,26 @@ impl<L: StateMachine,
         })
     }
 
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.isymbols.clone()
+    }
+
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.osymbols.clone()
+    }
 
 }
 
 impl<L: StateMachine, R: StateMachine<Weight=L::Weight, Label=L::Label>> ConcatStateMachine<L, R> {
     pub fn new(left: L, right: R) -> Self {
+        // panics here rather than in every call of `input_symbols`
+        let isymbols = merge_symbols(left.input_symbols(), right.input_symbols());
+        let osymbols = merge_symbols(left.output_symbols(), right.output_symbols());
         ConcatStateMachine {
             left: left,
             right: right,
+            isymbols: isymbols,
+            osymbols: osymbols,
             cache: ArcCache::new()
         }
     }
@@ -117,10 +134,10 @@ pub fn concat_test() {
 
     let fst_a = ByteVectorFSA::load_tsv("
 0	true
-0	1	0	true
+0	1	eps	true
 1	1	1	true
 1	2	2	true
-2	1	0	true
+2	1	eps	true
 2	2	3	true
 2	true
 ".trim().as_bytes());
@@ -131,16 +148,16 @@ pub fn concat_test() {
 1	true".trim().as_bytes());
 
     let fst_ab_src = "
-0	1	0	true
-0	2	0	true
+0	1	eps	true
+0	2	eps	true
 1	1	1	true
 1	3	2	true
 2	2	1	true
 2	4	2	true
 3	true
 3	3	3	true
-4	1	0	true
-4	2	0	true
+4	1	eps	true
+4	2	eps	true
 4	4	3	true
 ".trim();
 
@@ -158,10 +175,10 @@ pub fn concat_bench(b: &mut Bencher) {
 
     let fst_a = ByteVectorFSA::load_tsv("
 0	true
-0	1	0	true
+0	1	eps	true
 1	1	1	true
 1	2	2	true
-2	1	0	true
+2	1	eps	true
 2	2	3	true
 2	true
 ".trim().as_bytes());
@@ -175,3 +192,17 @@ pub fn concat_bench(b: &mut Bencher) {
         concat(fst_a.clone(), fst_b.clone())
     })
 }
+
+#[test]
+#[should_panic(expected = "Symbol tables are not compatible")]
+pub fn concat_incompatible_symbols_test() {
+    use automata::symbol::SymbolTable;
+    use automata::vector::ByteVectorFSA;
+
+    let load = |syms: &str| {
+        let syms = Rc::new(SymbolTable::load_tsv(syms.as_bytes()));
+        ByteVectorFSA::load_tsv_with_symbols("0\t1\ta\ttrue\n1\ttrue".as_bytes(), Some(syms))
+    };
+    // fails on construction, before any arc is expanded
+    ConcatStateMachine::new(load("a\t1"), load("a\t2"));
+}
@@ -204,7 +204,7 @@ pub fn connect<M>(m: &mut M) where M: MutableStateMachine {
 #[test]
 pub fn dfs_visit_test() {
     use automata::{LoadTSV,boolweight,i64state,SimpleArc};
-    use automata::vector::ByteVectorFSA;
+    use automata::vector::{ByteVectorFSA,ByteLabel};
     use self::VisitorEvent::*;
     let fst_a = ByteVectorFSA::load_tsv("
 0	1	1	true
@@ -219,7 +219,7 @@ pub fn dfs_visit_test() {
 
     let mut log = String::new();
 
-    dfs_visit(&fst_a, |ev: VisitorEvent<i64state, SimpleArc<i64state,boolweight,u8>>| {
+    dfs_visit(&fst_a, |ev: VisitorEvent<i64state, SimpleArc<i64state,boolweight,ByteLabel>>| {
         match ev {
             EnterState(ref st) => {
                 log.push_str(format!("EN{}\n", st).as_str());
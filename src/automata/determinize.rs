@@ -1,6 +1,7 @@
 use automata::{StateMachine,FSA,Semiring,SimpleArc,State,Arc,WeakLeftDiv};
 use automata::vector::VectorFSA;
 use automata::lazy::ArcCache;
+use automata::symbol::SymbolTable;
 
 use std::rc::Rc;
 use std::collections::{BTreeMap,BTreeSet};
@@ -102,6 +103,14 @@ impl<M: StateMachine> StateMachine for DeterminizedMachine<M>
         })
     }
 
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.source.input_symbols()
+    }
+
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.source.output_symbols()
+    }
+
 }
 
 impl<M: StateMachine> DeterminizedMachine<M>
@@ -0,0 +1,164 @@
+use automata::{StateMachine,FSA,Semiring,Arc,Label,DumpDot};
+use automata::vector::{ByteLabel,CharLabel};
+use automata::symbol::{SymbolTable,SymbolLabel};
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::Write;
+
+/// Trait for labels printed on edges in DOT
+pub trait DotLabel : Label {
+    /// Returns the text for the label, looking up symbols in the tables if given
+    ///
+    /// Acceptor labels use `isyms` only.
+    fn dot_label(&self, isyms: Option<&SymbolTable>, osyms: Option<&SymbolTable>) -> String;
+}
+
+fn find_symbol<L: SymbolLabel>(syms: Option<&SymbolTable>, label: &L) -> Option<String> {
+    syms.and_then(|t| label.symbol_id().and_then(|id| t.find_symbol(id)))
+        .map(|s| s.to_string())
+}
+
+/// Prints visible ASCII characters as they are, and other bytes in hex
+impl DotLabel for ByteLabel {
+    fn dot_label(&self, isyms: Option<&SymbolTable>, _: Option<&SymbolTable>) -> String {
+        if let Some(sym) = find_symbol(isyms, self) {
+            return sym;
+        }
+        match self.to_byte() {
+            None => "eps".to_string(),
+            Some(b) if (b as char).is_ascii_graphic() => (b as char).to_string(),
+            Some(b) => format!("0x{:02x}", b)
+        }
+    }
+}
+
+/// Prints visible characters as they are, and others as code points
+impl DotLabel for CharLabel {
+    fn dot_label(&self, isyms: Option<&SymbolTable>, _: Option<&SymbolTable>) -> String {
+        if let Some(sym) = find_symbol(isyms, self) {
+            return sym;
+        }
+        match self.to_char() {
+            Some(ch) if ! ch.is_control() && ! ch.is_whitespace() => ch.to_string(),
+            _ => self.to_string()
+        }
+    }
+}
+
+impl<I: DotLabel, O: DotLabel> DotLabel for (I, O) {
+    fn dot_label(&self, isyms: Option<&SymbolTable>, osyms: Option<&SymbolTable>) -> String {
+        format!("{}:{}", self.0.dot_label(isyms, None), self.1.dot_label(osyms, None))
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Prints states in the order of `states()` with sequential ids
+///
+/// The initial state is drawn in bold, and final states with double circles.
+/// Weights equal to `one()` are omitted.
+impl<M: FSA> DumpDot for M where M::Label: DotLabel, M::Weight: Display {
+    fn dump_dot(&self, dest: &mut Write) {
+        let isyms = self.input_symbols();
+        let osyms = self.output_symbols();
+        let itable = isyms.as_ref().map(|t| &**t);
+        let otable = osyms.as_ref().map(|t| &**t);
+
+        let states: Vec<M::State> = self.states().collect();
+        let ids: BTreeMap<M::State, usize> =
+            states.iter().cloned().enumerate().map(|(i, s)| (s, i)).collect();
+        let init = self.init_state();
+
+        let mut out = String::new();
+        out.push_str("digraph FSA {\n");
+        out.push_str("  rankdir = LR;\n");
+        out.push_str("  node [shape = circle];\n");
+        for (id, s) in states.iter().enumerate() {
+            let fw = self.final_weight(s);
+            let mut attrs = Vec::new();
+            if fw.is_nonzero() {
+                if fw != M::Weight::one() {
+                    attrs.push(format!("label = \"{}/{}\"", id, escape(&fw.to_string())));
+                }
+                attrs.push("shape = doublecircle".to_string());
+            }
+            if *s == init {
+                attrs.push("style = bold".to_string());
+            }
+            if attrs.is_empty() {
+                out.push_str(&format!("  {};\n", id));
+            } else {
+                out.push_str(&format!("  {} [{}];\n", id, attrs.join(", ")));
+            }
+        }
+        for (id, s) in states.iter().enumerate() {
+            for arc in self.arcs(s) {
+                let mut label = arc.label().dot_label(itable, otable);
+                if arc.weight() != M::Weight::one() {
+                    label = format!("{}/{}", label, arc.weight());
+                }
+                out.push_str(&format!("  {} -> {} [label = \"{}\"];\n",
+                                      id, ids[&arc.nextstate()], escape(&label)));
+            }
+        }
+        out.push_str("}\n");
+        dest.write_all(out.as_bytes()).expect("Dump failed [dot]");
+    }
+}
+
+#[test]
+pub fn dot_test() {
+    use automata::{LoadTSV,Tropical,SimpleArc,MutableStateMachine};
+    use automata::vector::{ByteVectorFSA,VectorFSA};
+    use automata::union::union;
+    use std::rc::Rc;
+
+    let fst_a = ByteVectorFSA::load_tsv("
+0	1	97	true
+0	2	10	true
+1	2	eps	true
+2	2	34	true
+2	true
+".trim().as_bytes());
+
+    let mut dump_buf = Vec::<u8>::new();
+    fst_a.dump_dot(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    println!(" === Result[DOT] ===\n{}", dumped);
+    assert_eq!(dumped, "
+digraph FSA {
+  rankdir = LR;
+  node [shape = circle];
+  0 [style = bold];
+  1;
+  2 [shape = doublecircle];
+  0 -> 1 [label = \"a\"];
+  0 -> 2 [label = \"0x0a\"];
+  1 -> 2 [label = \"eps\"];
+  2 -> 2 [label = \"\\\"\"];
+}
+".trim_left());
+
+    // lazy machines are renumbered in the order of `states()`
+    let mut dump_buf = Vec::<u8>::new();
+    union(fst_a.clone(), fst_a.clone()).dump_dot(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    assert_eq!(dumped.lines().filter(|l| l.contains("->")).count(), 10);
+
+    let mut syms = SymbolTable::new();
+    syms.add_symbol_with_id("<a>", 97).expect("Add failed");
+    let mut fst_w: VectorFSA<Tropical<f32>, (ByteLabel, ByteLabel)> = VectorFSA::new();
+    let (s0, s1) = (fst_w.add_new_state(), fst_w.add_new_state());
+    fst_w.add_arc(&s0, SimpleArc::new((ByteLabel::from(97), ByteLabel::from(98)),
+                                      Tropical::new(0.5), s1));
+    fst_w.set_final_weight(&s1, Tropical::new(1.5));
+    fst_w.set_input_symbols(Some(Rc::new(syms)));
+    let mut dump_buf = Vec::<u8>::new();
+    fst_w.dump_dot(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    assert!(dumped.contains("  1 [label = \"1/1.5\", shape = doublecircle];\n"));
+    assert!(dumped.contains("  0 -> 1 [label = \"<a>:b/0.5\"];\n"));
+}
@@ -0,0 +1,149 @@
+use automata::{StateMachine,FSA,Semiring,Arc,SimpleArc,State};
+use automata::vector::{VectorFSA};
+use automata::lazy::ArcCache;
+use automata::symbol::{SymbolTable,merge_symbols};
+
+use std::rc::Rc;
+
+/// Product state, i.e. pair of left state and right state
+#[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Debug)]
+pub struct IntersectState<L, R> {
+    left: L,
+    right: R
+}
+
+impl<L: State, R: State> State for IntersectState<L, R> {
+}
+
+/// Lazy product construction of two acceptors
+///
+/// Both operands are expected to be epsilon-free; epsilon arcs are treated
+/// as ordinary labels here.
+pub struct IntersectStateMachine<L: StateMachine,
+                                 R: StateMachine<Weight=L::Weight, Label=L::Label>> {
+    left: L,
+    right: R,
+    isymbols: Option<Rc<SymbolTable>>,
+    osymbols: Option<Rc<SymbolTable>>,
+    cache: ArcCache<IntersectState<L::State, R::State>,
+                    SimpleArc<IntersectState<L::State, R::State>,
+                              L::Weight, L::Label>>
+}
+
+impl<L: StateMachine,
+     R: StateMachine<Weight=L::Weight,Label=L::Label>>
+    StateMachine for IntersectStateMachine<L, R>
+{
+
+    type State = IntersectState<L::State, R::State>;
+    type Weight = L::Weight;
+    type Label = L::Label;
+    type Arc = SimpleArc<Self::State, Self::Weight, Self::Label>;
+
+    fn init_state(&self) -> Self::State {
+        IntersectState {
+            left: self.left.init_state(),
+            right: self.right.init_state()
+        }
+    }
+
+    fn final_weight(&self, s: &Self::State) -> Self::Weight {
+        let lfw = self.left.final_weight(&s.left);
+        let rfw = self.right.final_weight(&s.right);
+        lfw.times(&rfw)
+    }
+
+    fn arcs<'a>(&'a self, s: &Self::State) -> Box<'a + Iterator<Item=Self::Arc>> {
+        self.cache.query(s, &|s| {
+            let rarcs: Vec<R::Arc> = self.right.arcs(&s.right).collect();
+            let mut ret = Vec::new();
+            for la in self.left.arcs(&s.left) {
+                for ra in rarcs.iter() {
+                    if la.label() == ra.label() {
+                        ret.push(SimpleArc::new(la.label(), la.weight().times(&ra.weight()),
+                                                IntersectState {
+                                                    left: la.nextstate(),
+                                                    right: ra.nextstate()
+                                                }));
+                    }
+                }
+            }
+            box ret.into_iter()
+        })
+    }
+
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.isymbols.clone()
+    }
+
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.osymbols.clone()
+    }
+}
+
+impl<L: StateMachine, R: StateMachine<Weight=L::Weight, Label=L::Label>>
+    IntersectStateMachine<L, R> {
+    pub fn new(left: L, right: R) -> Self {
+        let isymbols = merge_symbols(left.input_symbols(), right.input_symbols());
+        let osymbols = merge_symbols(left.output_symbols(), right.output_symbols());
+        IntersectStateMachine {
+            left: left,
+            right: right,
+            isymbols: isymbols,
+            osymbols: osymbols,
+            cache: ArcCache::new()
+        }
+    }
+}
+
+impl<L: FSA, R: FSA<Weight=L::Weight,Label=L::Label>> FSA for IntersectStateMachine<L, R> {
+    fn nstates(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Intersect two epsilon-free acceptors
+pub fn intersect<L: FSA, R: FSA<Weight=L::Weight, Label=L::Label>>(left: L, right: R) ->
+    VectorFSA<L::Weight, L::Label> {
+    let dyn = IntersectStateMachine::new(left, right);
+    VectorFSA::<L::Weight, L::Label>::new_from_automaton(&dyn)
+}
+
+#[test]
+pub fn intersect_test() {
+    use automata::{LoadTSV,DumpTSV};
+    use automata::vector::ByteVectorFSA;
+
+    let fst_a = ByteVectorFSA::load_tsv("
+0	0	1	true
+0	1	2	true
+1	true
+1	1	3	true
+".trim().as_bytes());
+    let fst_b = ByteVectorFSA::load_tsv("
+0	1	1	true
+0	2	2	true
+1	1	1	true
+1	2	2	true
+2	true
+2	3	3	true
+3	true
+".trim().as_bytes());
+
+    let expected_src = "
+0	1	1	true
+0	2	2	true
+1	1	1	true
+1	2	2	true
+2	true
+2	3	3	true
+3	true
+".trim();
+
+    let result = intersect(fst_a, fst_b);
+    let mut dump_buf = Vec::<u8>::new();
+    result.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    println!(" === Result[Intersect] ===\n{}", dumped);
+    assert!(dumped.trim() == expected_src);
+}
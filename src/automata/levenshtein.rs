@@ -0,0 +1,94 @@
+use automata::{StateMachine,FSA,Arc,SimpleArc,Label,boolweight,MutableStateMachine,i64state};
+use automata::vector::{VectorFSA};
+
+/// Expands an acceptor to accept strings within the given edit distance
+///
+/// The result has `max_edits + 1` copies of the input, where the copy `e`
+/// corresponds to strings with `e` edits so far. Substitutions and
+/// insertions consume any label in `alphabet` and move to the next copy, and
+/// deletions are epsilon arcs to the next copy. The input must be
+/// epsilon-free, e.g. the output of `rmeps`.
+pub fn levenshtein<L>(m: &VectorFSA<boolweight, L>, max_edits: usize, alphabet: &[L])
+                      -> VectorFSA<boolweight, L>
+    where L: Label + Ord {
+
+    let nstates = m.nstates().expect("Vector FSA must have a number of states");
+    let state_at = |st: i64state, edits: usize| -> i64state {
+        (edits * nstates) as i64state + st
+    };
+
+    let mut ret = VectorFSA::new();
+    for _ in 0..((max_edits + 1) * nstates) {
+        ret.add_new_state();
+    }
+
+    for edits in 0..(max_edits + 1) {
+        for st in m.states() {
+            let cur = state_at(st, edits);
+            if m.final_weight(&st) {
+                ret.set_final_weight(&cur, true);
+            }
+            for arc in m.arcs(&st) {
+                ret.add_arc(&cur, SimpleArc::new(arc.label(), true,
+                                                 state_at(arc.nextstate(), edits)));
+            }
+            if edits == max_edits {
+                continue;
+            }
+            for arc in m.arcs(&st) {
+                let next = state_at(arc.nextstate(), edits + 1);
+                for l in alphabet.iter() {
+                    if *l != arc.label() {
+                        ret.add_arc(&cur, SimpleArc::new(l.clone(), true, next));
+                    }
+                }
+                ret.add_arc(&cur, SimpleArc::new(L::epsilon(), true, next));
+            }
+            for l in alphabet.iter() {
+                ret.add_arc(&cur, SimpleArc::new(l.clone(), true, state_at(st, edits + 1)));
+            }
+        }
+    }
+    ret.copy_symbols(m);
+
+    ret
+}
+
+#[test]
+pub fn levenshtein_test() {
+    use automata::LoadTSV;
+    use automata::vector::{ByteVectorFSA,ByteLabel};
+    use automata::rmeps::rmeps;
+    use automata::determinize::determinize;
+
+    // accepts "1 2 3"
+    let fst_a = ByteVectorFSA::load_tsv("
+0	1	1	true
+1	2	2	true
+2	3	3	true
+3	true
+".trim().as_bytes());
+
+    let dfa = determinize(rmeps(levenshtein(&fst_a, 1, &[1, 2, 3, 4].iter()
+                                                .map(|b| ByteLabel::from(*b))
+                                                .collect::<Vec<_>>())));
+    let accepts = |input: &[u8]| -> bool {
+        let mut st = dfa.init_state();
+        for b in input.iter() {
+            match dfa.arcs(&st).find(|a| a.label() == ByteLabel::from(*b)) {
+                Some(a) => { st = a.nextstate(); }
+                None => { return false; }
+            }
+        }
+        dfa.final_weight(&st)
+    };
+
+    assert!(accepts(&[1, 2, 3]));
+    assert!(accepts(&[1, 4, 3])); // substitution
+    assert!(accepts(&[1, 2, 4, 3])); // insertion
+    assert!(accepts(&[1, 3])); // deletion
+    assert!(accepts(&[1, 2, 3, 3]));
+    assert!(! accepts(&[4, 2, 4]));
+    assert!(! accepts(&[3]));
+    assert!(! accepts(&[1, 4, 4, 3]));
+}
@@ -1,4 +1,5 @@
-use automata::{StateMachine,boolweight,Arc,i64state,Label,FSA,SimpleArc,MutableStateMachine};
+use automata::{StateMachine,boolweight,Arc,i64state,Label,FSA,SimpleArc,MutableStateMachine,
+                Semiring};
 use automata::vector::{VectorFSA};
 use automata::connect::connect;
 use automata::reverse::reverse;
@@ -12,6 +13,17 @@ use test::Bencher;
 pub fn minimize_unweighted<L>(m: VectorFSA<boolweight, L>)
                               -> VectorFSA<boolweight, L>
     where L: Label + Ord {
+    minimize(m)
+}
+
+/// Minimize automaton by treating weights as a part of labels
+///
+/// States are initially partitioned by their final weights, and arcs are
+/// distinguished by pairs of labels and weights. Thus the result is minimal
+/// only when weights are already pushed, e.g. as in outputs of determinization
+/// over idempotent semirings with trivial arc weights.
+pub fn minimize<W, L>(m: VectorFSA<W, L>) -> VectorFSA<W, L>
+    where W: Semiring + Ord, L: Label + Ord {
 
     let mut m = m;
     type State = i64state;
@@ -23,22 +35,30 @@ pub fn minimize_unweighted<L>(m: VectorFSA<boolweight, L>)
     // Initialize
     let tr = reverse(&m);
 
-    let allstates: BTreeSet<State> = m.states().collect();
-    let finals: BTreeSet<State> = m.final_states().map(|t| t.0).collect();
-    let mut stack: Vec<BTreeSet<State>> = vec![finals.clone()];
+    let mut by_final_weight: BTreeMap<W, BTreeSet<State>> = BTreeMap::new();
+    let mut nonfinals = BTreeSet::new();
+    for s in m.states() {
+        let fw = m.final_weight(&s);
+        if fw.is_nonzero() {
+            by_final_weight.entry(fw).or_insert(BTreeSet::new()).insert(s);
+        } else {
+            nonfinals.insert(s);
+        }
+    }
+    let mut stack: Vec<BTreeSet<State>> = by_final_weight.values().cloned().collect();
 
-    let mut partitions: Vec<BTreeSet<State>> = vec![
-        finals.clone(), allstates.difference(&finals).cloned().collect()];
+    let mut partitions: Vec<BTreeSet<State>> = stack.clone();
+    partitions.push(nonfinals);
 
     while ! stack.is_empty() {
         let set: BTreeSet<State> = stack.pop().expect("Stack is empty");
 
-        // map from prefix label and preceeding state
-        let mut prevs: BTreeMap<L, BTreeSet<State>> = BTreeMap::new();
+        // map from prefix label/ weight and preceeding state
+        let mut prevs: BTreeMap<(L, W), BTreeSet<State>> = BTreeMap::new();
 
         for s in set {
             for rarc in tr.arcs(&(s + 1)) {
-                prevs.entry(rarc.label())
+                prevs.entry((rarc.label(), rarc.weight()))
                     .or_insert(BTreeSet::new())
                     .insert(rarc.nextstate() - 1);
             }
@@ -101,17 +121,16 @@ pub fn minimize_unweighted<L>(m: VectorFSA<boolweight, L>)
     let mut ret = VectorFSA::new();
     for (partid, part) in partitions.iter().enumerate() {
         let st = ret.add_new_state();
-        let mut is_final = false;
+        let mut final_weight = W::zero();
 
         let mut arcset = BTreeSet::new(); // for removing duplicates
         for os in part.iter() {
-            if m.final_weight(&os) {
-                is_final = true;
-            }
+            // all states in the partition share the same final weight
+            final_weight = m.final_weight(&os);
             for arc in m.arcs(&os) {
                 arcset.insert(
                     SimpleArc::new(
-                        arc.label(), true,
+                        arc.label(), arc.weight(),
                         state2part[arc.nextstate() as usize] as i64)
                 );
             }
@@ -121,10 +140,11 @@ pub fn minimize_unweighted<L>(m: VectorFSA<boolweight, L>)
             ret.add_arc(&(partid as i64), arc);
         }
 
-        if is_final {
-            ret.set_final_weight(&st, true);
+        if final_weight.is_nonzero() {
+            ret.set_final_weight(&st, final_weight);
         }
     }
+    ret.copy_symbols(&m);
 
     ret
 }
@@ -167,6 +187,41 @@ pub fn minimize_test() {
     assert!(dumped.trim() == expected_src);
 }
 
+#[test]
+pub fn minimize_final_weight_test() {
+    use automata::{LoadTSV,DumpTSV,PatternSet};
+    use automata::vector::ByteLabel;
+
+    // states 1 and 2 accept different patterns, but 3 and 4 are equivalent
+    let fst_a = VectorFSA::<PatternSet, ByteLabel>::load_tsv("
+0	1	1	*
+0	2	2	*
+1	3	3	*
+2	4	3	*
+1	{0}
+2	{1}
+3	{0,1}
+4	{0,1}
+".trim().as_bytes());
+
+    let expected_src = "
+0	3	1	*
+0	2	2	*
+1	{0,1}
+2	{1}
+2	1	3	*
+3	{0}
+3	1	3	*
+".trim();
+
+    let result = minimize(fst_a);
+    let mut dump_buf = Vec::<u8>::new();
+    result.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    println!(" === Result[Minimize] ===\n{}", dumped);
+    assert!(dumped.trim() == expected_src);
+}
+
 #[bench]
 pub fn minimize_bench(b: &mut Bencher) {
     use automata::{LoadTSV};
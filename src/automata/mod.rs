@@ -11,12 +11,28 @@ pub mod connect;
 pub mod reverse;
 pub mod minimize;
 pub mod compose;
+pub mod intersect;
+pub mod complement;
+pub mod trie;
+pub mod levenshtein;
+pub mod utf8;
+pub mod symbol;
+pub mod att;
+pub mod openfst;
+pub mod binary;
+pub mod dot;
+pub mod archive;
 
 use std::io::{Write,Read};
 use std::collections::{LinkedList,BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+use std::rc::Rc;
 
 use num_traits::{Float};
 
+use automata::symbol::SymbolTable;
+
 /// Alias for `i64` when it is used as a state id
 #[allow(non_camel_case_types)]
 pub type i64state = i64;
@@ -25,9 +41,19 @@ pub type i64state = i64;
 #[allow(non_camel_case_types)]
 pub type boolweight = bool;
 
-#[derive(Clone,PartialOrd,PartialEq)]
+#[derive(Clone,PartialOrd,PartialEq,Debug)]
 pub struct Tropical<T>(T);
 
+impl<T: Float> Tropical<T> {
+    pub fn new(v: T) -> Self {
+        Tropical(v)
+    }
+
+    pub fn value(&self) -> T {
+        self.0
+    }
+}
+
 pub trait DumpTSV {
     fn dump_tsv(&self, dest: &mut Write);
 }
@@ -36,6 +62,14 @@ pub trait LoadTSV<M> {
     fn load_tsv<R: Read>(source: R) -> M;
 }
 
+/// Trait for printing machines in the Graphviz DOT language
+///
+/// It is implemented for every `FSA` in `automata::dot`. Since all the states
+/// reachable with `states()` are printed, the machine must be finite.
+pub trait DumpDot {
+    fn dump_dot(&self, dest: &mut Write);
+}
+
 /// Trait for state descriptor in `StateMachine`
 ///
 /// State is expected to be cheap to clone, and it is typically immutable.
@@ -124,6 +158,87 @@ impl<T: Float> Semiring for Tropical<T> {
     }
 }
 
+/// Prints the value, or `Infinity` for zero as in OpenFst
+impl<T: Float + fmt::Display> fmt::Display for Tropical<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == T::infinity() {
+            write!(f, "Infinity")
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl<T: Float + FromStr> FromStr for Tropical<T> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "Infinity" || s == "inf" {
+            return Ok(Tropical(T::infinity()));
+        }
+        T::from_str(s).map(Tropical).map_err(|_| format!("Invalid weight: {}", s))
+    }
+}
+
+/// Log semiring over negative log probabilities
+#[derive(Clone,PartialOrd,PartialEq,Debug)]
+pub struct Log<T>(T);
+
+impl<T: Float> Log<T> {
+    pub fn new(v: T) -> Self {
+        Log(v)
+    }
+
+    pub fn value(&self) -> T {
+        self.0
+    }
+}
+
+impl<T: Float> Eq for Log<T> {
+}
+
+impl<T: Float> Semiring for Log<T> {
+    fn plus(&self, rhs: &Self) -> Self {
+        if self.0 == T::infinity() {
+            return rhs.clone();
+        }
+        if rhs.0 == T::infinity() {
+            return self.clone();
+        }
+        let (lo, hi) = if self.0 < rhs.0 { (self.0, rhs.0) } else { (rhs.0, self.0) };
+        Log(lo - (lo - hi).exp().ln_1p())
+    }
+    fn times(&self, rhs: &Self) -> Self {
+        Log(self.0 + rhs.0)
+    }
+    fn zero() -> Self {
+        Log(T::infinity())
+    }
+    fn one() -> Self {
+        Log(T::zero())
+    }
+}
+
+impl<T: Float + fmt::Display> fmt::Display for Log<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == T::infinity() {
+            write!(f, "Infinity")
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl<T: Float + FromStr> FromStr for Log<T> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "Infinity" || s == "inf" {
+            return Ok(Log(T::infinity()));
+        }
+        T::from_str(s).map(Log).map_err(|_| format!("Invalid weight: {}", s))
+    }
+}
 
 #[derive(Clone,PartialOrd,PartialEq,Ord,Eq,Debug)]
 pub struct DivByZeroError;
@@ -146,6 +261,94 @@ impl WeakLeftDiv for boolweight {
     }
 }
 
+/// Set semiring over pattern ids
+///
+/// This is used as a final weight for recording which patterns are accepted.
+/// `plus` is the union and `times` is the intersection, so `one()` is the
+/// universal set `All`.
+#[derive(Clone,PartialOrd,PartialEq,Ord,Eq,Debug)]
+pub enum PatternSet {
+    All,
+    Ids(BTreeSet<usize>),
+}
+
+impl PatternSet {
+    pub fn singleton(id: usize) -> PatternSet {
+        let mut ids = BTreeSet::new();
+        ids.insert(id);
+        PatternSet::Ids(ids)
+    }
+}
+
+impl Semiring for PatternSet {
+    fn plus(&self, rhs: &Self) -> Self {
+        match (self, rhs) {
+            (&PatternSet::Ids(ref a), &PatternSet::Ids(ref b)) => {
+                PatternSet::Ids(a.union(b).cloned().collect())
+            }
+            _ => PatternSet::All
+        }
+    }
+    fn times(&self, rhs: &Self) -> Self {
+        match (self, rhs) {
+            (&PatternSet::Ids(ref a), &PatternSet::Ids(ref b)) => {
+                PatternSet::Ids(a.intersection(b).cloned().collect())
+            }
+            (&PatternSet::All, other) | (other, &PatternSet::All) => other.clone()
+        }
+    }
+    fn zero() -> Self {
+        PatternSet::Ids(BTreeSet::new())
+    }
+    fn one() -> Self {
+        PatternSet::All
+    }
+}
+
+impl WeakLeftDiv for PatternSet {
+    /// Returns the numerator itself, which is valid since the semiring is
+    /// idempotent and the numerator is always a subset of the denominator in
+    /// determinization.
+    fn leftdiv(&self, denom: &Self) -> Result<Self, DivByZeroError> {
+        if ! denom.is_nonzero() {
+            Err(DivByZeroError)
+        } else {
+            Ok(self.clone())
+        }
+    }
+}
+
+impl fmt::Display for PatternSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &PatternSet::All => write!(f, "*"),
+            &PatternSet::Ids(ref ids) => {
+                let ids: Vec<String> = ids.iter().map(|i| i.to_string()).collect();
+                write!(f, "{{{}}}", ids.join(","))
+            }
+        }
+    }
+}
+
+impl FromStr for PatternSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "*" {
+            return Ok(PatternSet::All);
+        }
+        if ! s.starts_with('{') || ! s.ends_with('}') {
+            return Err(format!("Invalid pattern set: {}", s));
+        }
+        let inner = &s[1..(s.len() - 1)];
+        let mut ids = BTreeSet::new();
+        for id in inner.split(',').filter(|t| ! t.is_empty()) {
+            ids.insert(id.parse::<usize>().map_err(|e| e.to_string())?);
+        }
+        Ok(PatternSet::Ids(ids))
+    }
+}
+
 /// Generic base trait for graph-based state machines
 ///
 /// The trait is designed to be generic enough for represent both
@@ -185,6 +388,18 @@ pub trait StateMachine {
 
     fn arcs<'a>(&'a self, s: &Self::State) -> Box<'a + Iterator<Item=Self::Arc>>;
 
+    /// Returns the symbol table for input labels if attached
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        None
+    }
+
+    /// Returns the symbol table for output labels if attached
+    ///
+    /// For acceptors, this is typically the same as `input_symbols`.
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        None
+    }
+
 }
 
 /// Traits for mutable state machines
@@ -0,0 +1,750 @@
+use automata::{StateMachine,Semiring,Arc,SimpleArc,MutableStateMachine,Label,Tropical,Log,i64state};
+use automata::vector::{VectorFSA,ByteLabel,CharLabel,IntLabel};
+use automata::symbol::{SymbolTable,SymbolLabel};
+
+use std::io::{self,Read,Write,ErrorKind};
+use std::rc::Rc;
+
+const FST_MAGIC: i32 = 2125659606;
+const SYMBOL_TABLE_MAGIC: i32 = 2125658996;
+
+const HAS_ISYMBOLS: i32 = 0x1;
+const HAS_OSYMBOLS: i32 = 0x2;
+const IS_ALIGNED: i32 = 0x4;
+
+const VECTOR_VERSION: i32 = 2;
+const CONST_VERSION: i32 = 2;
+const CONST_ALIGNED_VERSION: i32 = 1;
+const FILE_ALIGN: u64 = 16;
+
+// Property bits in headers, as defined in `fst/properties.h`
+const PROP_EXPANDED: u64 = 0x1;
+const PROP_MUTABLE: u64 = 0x2;
+const PROP_ERROR: u64 = 0x4;
+const PROP_ACCEPTOR: u64 = 0x10000;
+const PROP_NOT_ACCEPTOR: u64 = 0x20000;
+const PROP_EPSILONS: u64 = 0x400000;
+const PROP_NO_EPSILONS: u64 = 0x800000;
+const PROP_I_EPSILONS: u64 = 0x1000000;
+const PROP_NO_I_EPSILONS: u64 = 0x2000000;
+const PROP_O_EPSILONS: u64 = 0x4000000;
+const PROP_NO_O_EPSILONS: u64 = 0x8000000;
+const PROP_WEIGHTED: u64 = 0x100000000;
+const PROP_UNWEIGHTED: u64 = 0x200000000;
+
+/// Properties computed from the contents, and verified on reading
+const CHECKED_PROPS: u64 = PROP_ACCEPTOR | PROP_NOT_ACCEPTOR | PROP_EPSILONS | PROP_NO_EPSILONS |
+    PROP_I_EPSILONS | PROP_NO_I_EPSILONS | PROP_O_EPSILONS | PROP_NO_O_EPSILONS |
+    PROP_WEIGHTED | PROP_UNWEIGHTED;
+
+/// Errors in reading or writing OpenFst binary files
+#[derive(Debug)]
+pub enum FstError {
+    Io(io::Error),
+    /// Not an OpenFst file, or the header is broken
+    InvalidHeader(String),
+    /// FST types other than `vector` and `const`
+    UnsupportedFstType(String),
+    /// Arc type in the file, and the one for the requested weight type
+    UnsupportedArcType(String, &'static str),
+    /// File version for the FST type
+    UnsupportedVersion(String, i32),
+    /// Properties in the header contradict the contents or the label type
+    Properties(String),
+    InvalidSymbolTable(String),
+    /// States or labels out of range
+    InvalidData(String),
+}
+
+impl From<io::Error> for FstError {
+    fn from(e: io::Error) -> FstError {
+        FstError::Io(e)
+    }
+}
+
+/// Binary representations of OpenFst FSTs
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum FstFormat {
+    /// `VectorFst`, which is the default of `fstcompile`
+    Vector,
+    /// `ConstFst` with the default 32-bit offsets
+    Const,
+}
+
+/// Trait for weights stored as 32-bit floats in OpenFst arcs
+pub trait OpenFstWeight : Semiring {
+    /// Returns the arc type name in headers
+    fn arc_type() -> &'static str;
+    fn to_f32(&self) -> f32;
+    fn from_f32(v: f32) -> Self;
+}
+
+impl OpenFstWeight for Tropical<f32> {
+    fn arc_type() -> &'static str { "standard" }
+    fn to_f32(&self) -> f32 { self.value() }
+    fn from_f32(v: f32) -> Self { Tropical::new(v) }
+}
+
+impl OpenFstWeight for Log<f32> {
+    fn arc_type() -> &'static str { "log" }
+    fn to_f32(&self) -> f32 { self.value() }
+    fn from_f32(v: f32) -> Self { Log::new(v) }
+}
+
+/// Trait for labels stored as pairs of integer ids in OpenFst arcs
+///
+/// Epsilon is stored as 0, and the other labels are stored as their symbol
+/// ids. Single labels are stored as acceptor arcs, and pairs of labels are
+/// stored as transducer arcs.
+pub trait OpenFstLabel : Label {
+    fn to_ids(&self) -> Result<(i32, i32), FstError>;
+    fn from_ids(ilabel: i32, olabel: i32) -> Result<Self, FstError>;
+}
+
+fn label_to_id<L: SymbolLabel>(l: &L) -> Result<i32, FstError> {
+    match l.symbol_id() {
+        None => Ok(0),
+        Some(0) => Err(FstError::InvalidData("Label collides with epsilon".to_string())),
+        Some(id) if id <= i32::max_value() as usize => Ok(id as i32),
+        Some(id) => Err(FstError::InvalidData(format!("Label out of range: {}", id)))
+    }
+}
+
+fn id_to_label<L: SymbolLabel>(id: i32) -> Result<L, FstError> {
+    if id == 0 {
+        return Ok(L::epsilon());
+    }
+    if id < 0 {
+        return Err(FstError::InvalidData(format!("Label out of range: {}", id)));
+    }
+    L::from_symbol_id(id as usize)
+        .ok_or(FstError::InvalidData(format!("Label out of range: {}", id)))
+}
+
+fn acceptor_ids<L: SymbolLabel>(l: &L) -> Result<(i32, i32), FstError> {
+    let id = label_to_id(l)?;
+    Ok((id, id))
+}
+
+fn acceptor_from_ids<L: SymbolLabel>(ilabel: i32, olabel: i32) -> Result<L, FstError> {
+    if ilabel != olabel {
+        return Err(FstError::Properties("Transducer arcs cannot be read as an acceptor"
+                                        .to_string()));
+    }
+    id_to_label(ilabel)
+}
+
+impl OpenFstLabel for ByteLabel {
+    fn to_ids(&self) -> Result<(i32, i32), FstError> { acceptor_ids(self) }
+    fn from_ids(i: i32, o: i32) -> Result<Self, FstError> { acceptor_from_ids(i, o) }
+}
+
+impl OpenFstLabel for CharLabel {
+    fn to_ids(&self) -> Result<(i32, i32), FstError> { acceptor_ids(self) }
+    fn from_ids(i: i32, o: i32) -> Result<Self, FstError> { acceptor_from_ids(i, o) }
+}
+
+/// Accepts any positive id, unlike bytes and chars
+impl OpenFstLabel for IntLabel {
+    fn to_ids(&self) -> Result<(i32, i32), FstError> { acceptor_ids(self) }
+    fn from_ids(i: i32, o: i32) -> Result<Self, FstError> { acceptor_from_ids(i, o) }
+}
+
+impl<I: SymbolLabel, O: SymbolLabel> OpenFstLabel for (I, O) {
+    fn to_ids(&self) -> Result<(i32, i32), FstError> {
+        Ok((label_to_id(&self.0)?, label_to_id(&self.1)?))
+    }
+    fn from_ids(i: i32, o: i32) -> Result<Self, FstError> {
+        Ok((id_to_label(i)?, id_to_label(o)?))
+    }
+}
+
+struct RawArc {
+    ilabel: i32,
+    olabel: i32,
+    weight: f32,
+    nextstate: i32,
+}
+
+struct RawState {
+    final_weight: f32,
+    arcs: Vec<RawArc>,
+}
+
+struct Header {
+    fst_type: String,
+    arc_type: String,
+    version: i32,
+    flags: i32,
+    properties: u64,
+    start: i64,
+    nstates: i64,
+    narcs: i64,
+}
+
+/// Little-endian reader keeping the position for alignment
+struct Input<R: Read> {
+    inner: R,
+    pos: u64,
+}
+
+impl<R: Read> Input<R> {
+    fn read_bytes(&mut self, n: usize) -> Result<Vec<u8>, FstError> {
+        let mut buf = vec![0; n];
+        self.inner.read_exact(&mut buf)?;
+        self.pos += n as u64;
+        Ok(buf)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, FstError> {
+        let b = self.read_bytes(4)?;
+        Ok(b.iter().rev().fold(0, |acc, x| (acc << 8) | (*x as u32)))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, FstError> {
+        let b = self.read_bytes(8)?;
+        Ok(b.iter().rev().fold(0, |acc, x| (acc << 8) | (*x as u64)))
+    }
+
+    fn read_i32(&mut self) -> Result<i32, FstError> {
+        self.read_u32().map(|v| v as i32)
+    }
+
+    fn read_i64(&mut self) -> Result<i64, FstError> {
+        self.read_u64().map(|v| v as i64)
+    }
+
+    fn read_f32(&mut self) -> Result<f32, FstError> {
+        self.read_u32().map(f32::from_bits)
+    }
+
+    /// Reads a float, or returns `None` at the end of the input
+    fn read_f32_or_eof(&mut self) -> Result<Option<f32>, FstError> {
+        let mut first = [0; 1];
+        loop {
+            match self.inner.read(&mut first) {
+                Ok(0) => { return Ok(None); }
+                Ok(_) => { break; }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => { return Err(FstError::Io(e)); }
+            }
+        }
+        self.pos += 1;
+        let rest = self.read_bytes(3)?;
+        let bits = rest.iter().rev().fold(0, |acc, x| (acc << 8) | (*x as u32));
+        Ok(Some(f32::from_bits((bits << 8) | (first[0] as u32))))
+    }
+
+    fn read_string(&mut self) -> Result<String, FstError> {
+        let len = self.read_i32()?;
+        if len < 0 {
+            return Err(FstError::InvalidHeader(format!("Negative string length: {}", len)));
+        }
+        String::from_utf8(self.read_bytes(len as usize)?)
+            .map_err(|_| FstError::InvalidHeader("String is not valid UTF-8".to_string()))
+    }
+
+    fn align(&mut self) -> Result<(), FstError> {
+        let pad = (FILE_ALIGN - self.pos % FILE_ALIGN) % FILE_ALIGN;
+        self.read_bytes(pad as usize).map(|_| ())
+    }
+}
+
+/// Little-endian writer keeping the position for alignment
+struct Output<'a> {
+    inner: &'a mut Write,
+    pos: u64,
+}
+
+impl<'a> Output<'a> {
+    fn write_bytes(&mut self, b: &[u8]) -> Result<(), FstError> {
+        self.inner.write_all(b)?;
+        self.pos += b.len() as u64;
+        Ok(())
+    }
+
+    fn write_u32(&mut self, v: u32) -> Result<(), FstError> {
+        let b: Vec<u8> = (0..4).map(|i| (v >> (8 * i)) as u8).collect();
+        self.write_bytes(&b)
+    }
+
+    fn write_u64(&mut self, v: u64) -> Result<(), FstError> {
+        let b: Vec<u8> = (0..8).map(|i| (v >> (8 * i)) as u8).collect();
+        self.write_bytes(&b)
+    }
+
+    fn write_i32(&mut self, v: i32) -> Result<(), FstError> {
+        self.write_u32(v as u32)
+    }
+
+    fn write_i64(&mut self, v: i64) -> Result<(), FstError> {
+        self.write_u64(v as u64)
+    }
+
+    fn write_f32(&mut self, v: f32) -> Result<(), FstError> {
+        self.write_u32(v.to_bits())
+    }
+
+    fn write_string(&mut self, s: &str) -> Result<(), FstError> {
+        self.write_i32(s.len() as i32)?;
+        self.write_bytes(s.as_bytes())
+    }
+
+    fn align(&mut self) -> Result<(), FstError> {
+        let pad = (FILE_ALIGN - self.pos % FILE_ALIGN) % FILE_ALIGN;
+        self.write_bytes(&vec![0; pad as usize])
+    }
+}
+
+fn read_header<R: Read>(input: &mut Input<R>) -> Result<Header, FstError> {
+    if input.read_i32()? != FST_MAGIC {
+        return Err(FstError::InvalidHeader("Bad magic number".to_string()));
+    }
+    Ok(Header {
+        fst_type: input.read_string()?,
+        arc_type: input.read_string()?,
+        version: input.read_i32()?,
+        flags: input.read_i32()?,
+        properties: input.read_u64()?,
+        start: input.read_i64()?,
+        nstates: input.read_i64()?,
+        narcs: input.read_i64()?,
+    })
+}
+
+fn write_header(out: &mut Output, hdr: &Header) -> Result<(), FstError> {
+    out.write_i32(FST_MAGIC)?;
+    out.write_string(&hdr.fst_type)?;
+    out.write_string(&hdr.arc_type)?;
+    out.write_i32(hdr.version)?;
+    out.write_i32(hdr.flags)?;
+    out.write_u64(hdr.properties)?;
+    out.write_i64(hdr.start)?;
+    out.write_i64(hdr.nstates)?;
+    out.write_i64(hdr.narcs)
+}
+
+fn read_symbols<R: Read>(input: &mut Input<R>) -> Result<SymbolTable, FstError> {
+    if input.read_i32()? != SYMBOL_TABLE_MAGIC {
+        return Err(FstError::InvalidSymbolTable("Bad magic number".to_string()));
+    }
+    let _name = input.read_string()?;
+    let _available_key = input.read_i64()?;
+    let size = input.read_i64()?;
+    if size < 0 {
+        return Err(FstError::InvalidSymbolTable(format!("Negative size: {}", size)));
+    }
+    let mut ret = SymbolTable::new();
+    for _ in 0..size {
+        let sym = input.read_string()?;
+        let key = input.read_i64()?;
+        if key < 0 {
+            return Err(FstError::InvalidSymbolTable(format!("Negative key: {}", key)));
+        }
+        ret.add_symbol_with_id(&sym, key as usize).map_err(FstError::InvalidSymbolTable)?;
+    }
+    Ok(ret)
+}
+
+fn write_symbols(out: &mut Output, syms: &SymbolTable) -> Result<(), FstError> {
+    out.write_i32(SYMBOL_TABLE_MAGIC)?;
+    out.write_string("")?;
+    let available_key = syms.iter().map(|(id, _)| id + 1).max().unwrap_or(0);
+    out.write_i64(available_key as i64)?;
+    out.write_i64(syms.len() as i64)?;
+    for (id, sym) in syms.iter() {
+        out.write_string(sym)?;
+        out.write_i64(id as i64)?;
+    }
+    Ok(())
+}
+
+fn read_raw_arc<R: Read>(input: &mut Input<R>) -> Result<RawArc, FstError> {
+    Ok(RawArc {
+        ilabel: input.read_i32()?,
+        olabel: input.read_i32()?,
+        weight: input.read_f32()?,
+        nextstate: input.read_i32()?,
+    })
+}
+
+fn write_raw_arc(out: &mut Output, arc: &RawArc) -> Result<(), FstError> {
+    out.write_i32(arc.ilabel)?;
+    out.write_i32(arc.olabel)?;
+    out.write_f32(arc.weight)?;
+    out.write_i32(arc.nextstate)
+}
+
+fn read_vector_states<R: Read>(input: &mut Input<R>, hdr: &Header)
+                               -> Result<Vec<RawState>, FstError> {
+    let mut states = Vec::new();
+    // The number of states may be unknown (-1), and then states continue
+    // until the end of the input.
+    while hdr.nstates < 0 || (states.len() as i64) < hdr.nstates {
+        let final_weight = if hdr.nstates < 0 {
+            match input.read_f32_or_eof()? {
+                Some(w) => w,
+                None => { break; }
+            }
+        } else {
+            input.read_f32()?
+        };
+        let narcs = input.read_i64()?;
+        if narcs < 0 {
+            return Err(FstError::InvalidData(format!("Negative number of arcs: {}", narcs)));
+        }
+        let mut arcs = Vec::new();
+        for _ in 0..narcs {
+            arcs.push(read_raw_arc(input)?);
+        }
+        states.push(RawState { final_weight: final_weight, arcs: arcs });
+    }
+    Ok(states)
+}
+
+fn read_const_states<R: Read>(input: &mut Input<R>, hdr: &Header, aligned: bool)
+                              -> Result<Vec<RawState>, FstError> {
+    if hdr.nstates < 0 || hdr.narcs < 0 {
+        return Err(FstError::InvalidHeader("Const FST must have the numbers of states and arcs"
+                                           .to_string()));
+    }
+    if aligned {
+        input.align()?;
+    }
+    let mut ranges = Vec::new();
+    let mut finals = Vec::new();
+    for _ in 0..hdr.nstates {
+        finals.push(input.read_f32()?);
+        let pos = input.read_u32()? as usize;
+        let narcs = input.read_u32()? as usize;
+        let _niepsilons = input.read_u32()?;
+        let _noepsilons = input.read_u32()?;
+        ranges.push((pos, pos + narcs));
+    }
+    if aligned {
+        input.align()?;
+    }
+    let mut arcs = Vec::new();
+    for _ in 0..hdr.narcs {
+        arcs.push(Some(read_raw_arc(input)?));
+    }
+
+    let mut states = Vec::new();
+    for (fw, (begin, end)) in finals.into_iter().zip(ranges.into_iter()) {
+        if end > arcs.len() {
+            return Err(FstError::InvalidData(format!("Arcs out of range: {}..{}", begin, end)));
+        }
+        let mut state_arcs = Vec::new();
+        for arc in arcs[begin..end].iter_mut() {
+            let arc = arc.take().ok_or(FstError::InvalidData("Arcs are shared".to_string()))?;
+            state_arcs.push(arc);
+        }
+        states.push(RawState { final_weight: fw, arcs: state_arcs });
+    }
+    Ok(states)
+}
+
+fn compute_properties<W: OpenFstWeight>(states: &[RawState]) -> u64 {
+    let mut acceptor = true;
+    let mut epsilons = false;
+    let mut iepsilons = false;
+    let mut oepsilons = false;
+    let mut weighted = false;
+    for st in states.iter() {
+        let fw = W::from_f32(st.final_weight);
+        if fw != W::zero() && fw != W::one() {
+            weighted = true;
+        }
+        for arc in st.arcs.iter() {
+            acceptor &= arc.ilabel == arc.olabel;
+            epsilons |= arc.ilabel == 0 && arc.olabel == 0;
+            iepsilons |= arc.ilabel == 0;
+            oepsilons |= arc.olabel == 0;
+            weighted |= W::from_f32(arc.weight) != W::one();
+        }
+    }
+    let pick = |cond: bool, yes: u64, no: u64| if cond { yes } else { no };
+    pick(acceptor, PROP_ACCEPTOR, PROP_NOT_ACCEPTOR) |
+    pick(epsilons, PROP_EPSILONS, PROP_NO_EPSILONS) |
+    pick(iepsilons, PROP_I_EPSILONS, PROP_NO_I_EPSILONS) |
+    pick(oepsilons, PROP_O_EPSILONS, PROP_NO_O_EPSILONS) |
+    pick(weighted, PROP_WEIGHTED, PROP_UNWEIGHTED)
+}
+
+/// Reads an FST in the OpenFst binary `vector` or `const` format
+///
+/// The weight type must match the arc type in the header, and declared
+/// properties are verified against the contents. The initial state is
+/// renumbered to 0 by swapping it with the state 0.
+pub fn read_openfst<W, L, R>(src: R) -> Result<VectorFSA<W, L>, FstError>
+    where W: OpenFstWeight, L: OpenFstLabel, R: Read {
+    let mut input = Input { inner: src, pos: 0 };
+    let hdr = read_header(&mut input)?;
+    if hdr.arc_type != W::arc_type() {
+        return Err(FstError::UnsupportedArcType(hdr.arc_type, W::arc_type()));
+    }
+    let aligned = match (hdr.fst_type.as_str(), hdr.version) {
+        ("vector", VECTOR_VERSION) => false,
+        ("const", CONST_ALIGNED_VERSION) => true,
+        ("const", CONST_VERSION) => hdr.flags & IS_ALIGNED != 0,
+        ("vector", v) | ("const", v) => {
+            return Err(FstError::UnsupportedVersion(hdr.fst_type.clone(), v));
+        }
+        _ => {
+            return Err(FstError::UnsupportedFstType(hdr.fst_type.clone()));
+        }
+    };
+    if hdr.properties & PROP_ERROR != 0 {
+        return Err(FstError::Properties("The FST is marked as an error".to_string()));
+    }
+
+    let isyms = if hdr.flags & HAS_ISYMBOLS != 0 {
+        Some(Rc::new(read_symbols(&mut input)?))
+    } else {
+        None
+    };
+    let osyms = if hdr.flags & HAS_OSYMBOLS != 0 {
+        Some(Rc::new(read_symbols(&mut input)?))
+    } else {
+        None
+    };
+
+    let states = if hdr.fst_type == "vector" {
+        read_vector_states(&mut input, &hdr)?
+    } else {
+        read_const_states(&mut input, &hdr, aligned)?
+    };
+
+    let mismatch = hdr.properties & CHECKED_PROPS & ! compute_properties::<W>(&states);
+    if mismatch != 0 {
+        return Err(FstError::Properties(format!("Properties {:#x} don't match the contents",
+                                                mismatch)));
+    }
+
+    let mut ret = VectorFSA::new();
+    ret.set_input_symbols(isyms);
+    ret.set_output_symbols(osyms);
+    if states.is_empty() {
+        return Ok(ret);
+    }
+    let nstates = states.len() as i64;
+    if hdr.start < 0 || hdr.start >= nstates {
+        return Err(FstError::InvalidData(format!("Initial state out of range: {}", hdr.start)));
+    }
+    let renumber = |s: i64| -> i64state {
+        if s == hdr.start { 0 } else if s == 0 { hdr.start } else { s }
+    };
+
+    for _ in 0..nstates {
+        ret.add_new_state();
+    }
+    for (s, st) in states.into_iter().enumerate() {
+        let newst = renumber(s as i64);
+        ret.set_final_weight(&newst, W::from_f32(st.final_weight));
+        for arc in st.arcs.into_iter() {
+            let next = arc.nextstate as i64;
+            if next < 0 || next >= nstates {
+                return Err(FstError::InvalidData(format!("State out of range: {}", next)));
+            }
+            let label = L::from_ids(arc.ilabel, arc.olabel)?;
+            ret.add_arc(&newst, SimpleArc::new(label, W::from_f32(arc.weight), renumber(next)));
+        }
+    }
+    Ok(ret)
+}
+
+/// Writes the machine in the OpenFst binary format
+///
+/// Attached symbol tables are written as well.
+pub fn write_openfst<W, L>(m: &VectorFSA<W, L>, format: FstFormat, dest: &mut Write)
+                           -> Result<(), FstError>
+    where W: OpenFstWeight, L: OpenFstLabel {
+    let mut states = Vec::new();
+    for st in m.states() {
+        let mut arcs = Vec::new();
+        for arc in m.arcs(&st) {
+            let (ilabel, olabel) = arc.label().to_ids()?;
+            arcs.push(RawArc {
+                ilabel: ilabel,
+                olabel: olabel,
+                weight: arc.weight().to_f32(),
+                nextstate: arc.nextstate() as i32,
+            });
+        }
+        states.push(RawState { final_weight: m.final_weight(&st).to_f32(), arcs: arcs });
+    }
+    let narcs: usize = states.iter().map(|st| st.arcs.len()).sum();
+
+    let isyms = m.input_symbols();
+    let osyms = m.output_symbols();
+    let mut flags = 0;
+    if isyms.is_some() {
+        flags |= HAS_ISYMBOLS;
+    }
+    if osyms.is_some() {
+        flags |= HAS_OSYMBOLS;
+    }
+    let (fst_type, version, props) = match format {
+        FstFormat::Vector => ("vector", VECTOR_VERSION, PROP_EXPANDED | PROP_MUTABLE),
+        FstFormat::Const => {
+            flags |= IS_ALIGNED;
+            ("const", CONST_VERSION, PROP_EXPANDED)
+        }
+    };
+    let hdr = Header {
+        fst_type: fst_type.to_string(),
+        arc_type: W::arc_type().to_string(),
+        version: version,
+        flags: flags,
+        properties: props | compute_properties::<W>(&states),
+        start: if states.is_empty() { -1 } else { m.init_state() },
+        nstates: states.len() as i64,
+        narcs: narcs as i64,
+    };
+
+    let mut out = Output { inner: dest, pos: 0 };
+    write_header(&mut out, &hdr)?;
+    if let Some(ref t) = isyms {
+        write_symbols(&mut out, t)?;
+    }
+    if let Some(ref t) = osyms {
+        write_symbols(&mut out, t)?;
+    }
+
+    match format {
+        FstFormat::Vector => {
+            for st in states.iter() {
+                out.write_f32(st.final_weight)?;
+                out.write_i64(st.arcs.len() as i64)?;
+                for arc in st.arcs.iter() {
+                    write_raw_arc(&mut out, arc)?;
+                }
+            }
+        }
+        FstFormat::Const => {
+            out.align()?;
+            let mut pos = 0;
+            for st in states.iter() {
+                out.write_f32(st.final_weight)?;
+                out.write_u32(pos as u32)?;
+                out.write_u32(st.arcs.len() as u32)?;
+                out.write_u32(st.arcs.iter().filter(|a| a.ilabel == 0).count() as u32)?;
+                out.write_u32(st.arcs.iter().filter(|a| a.olabel == 0).count() as u32)?;
+                pos += st.arcs.len();
+            }
+            out.align()?;
+            for st in states.iter() {
+                for arc in st.arcs.iter() {
+                    write_raw_arc(&mut out, arc)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+pub fn openfst_test() {
+    use automata::{DumpTSV,FSA};
+
+    let syms = Rc::new(::automata::att::read_symbols("
+<eps>	0
+a	97
+b	98
+".trim().as_bytes()).expect("Read failed"));
+    let mut fst: VectorFSA<Tropical<f32>, (ByteLabel, ByteLabel)> =
+        ::automata::att::read_fst("
+0	1	a	b	0.5
+1	1	b	<eps>
+1	2.5
+".trim().as_bytes(), Some(syms.clone()), Some(syms.clone())).expect("Read failed");
+
+    for format in vec![FstFormat::Vector, FstFormat::Const].into_iter() {
+        let mut buf = Vec::<u8>::new();
+        write_openfst(&fst, format, &mut buf).expect("Write failed");
+        assert_eq!(&buf[0..4], &[0xD6, 0xFD, 0xB2, 0x7E]);
+        let loaded: VectorFSA<Tropical<f32>, (ByteLabel, ByteLabel)> =
+            read_openfst(&buf[..]).expect("Read failed");
+        assert!(loaded.input_symbols() == Some(syms.clone()));
+        let mut dump_buf = Vec::<u8>::new();
+        ::automata::att::write_fst(&loaded, &mut dump_buf).expect("Write failed");
+        assert_eq!(String::from_utf8(dump_buf).expect("UTF-8 error"),
+                   "0\t1\ta\tb\t0.5\n1\t1\tb\t<eps>\n1\t2.5\n");
+
+        // wrong weight type
+        match read_openfst::<Log<f32>, (ByteLabel, ByteLabel), _>(&buf[..]) {
+            Err(FstError::UnsupportedArcType(ref found, "log")) if found == "standard" => {}
+            other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+        }
+        // transducer read as an acceptor
+        match read_openfst::<Tropical<f32>, ByteLabel, _>(&buf[..]) {
+            Err(FstError::Properties(_)) => {}
+            other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+        }
+    }
+
+    // the header claims an acceptor, but the arcs are not
+    fst.set_input_symbols(None);
+    fst.set_output_symbols(None);
+    let mut buf = Vec::<u8>::new();
+    write_openfst(&fst, FstFormat::Vector, &mut buf).expect("Write failed");
+    // magic, "vector", "standard", version and flags precede properties
+    let props_at = 4 + (4 + 6) + (4 + 8) + 4 + 4;
+    buf[props_at + 2] = (buf[props_at + 2] | 0x01) & ! 0x02;
+    match read_openfst::<Tropical<f32>, (ByteLabel, ByteLabel), _>(&buf[..]) {
+        Err(FstError::Properties(_)) => {}
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+    }
+
+    buf[0] = 0;
+    match read_openfst::<Tropical<f32>, (ByteLabel, ByteLabel), _>(&buf[..]) {
+        Err(FstError::InvalidHeader(_)) => {}
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+    }
+
+    // acceptors whose initial state is not 0
+    let mut buf = Vec::<u8>::new();
+    {
+        let mut out = Output { inner: &mut buf, pos: 0 };
+        write_header(&mut out, &Header {
+            fst_type: "vector".to_string(),
+            arc_type: "standard".to_string(),
+            version: VECTOR_VERSION,
+            flags: 0,
+            properties: PROP_EXPANDED,
+            start: 1,
+            nstates: -1,
+            narcs: -1,
+        }).expect("Write failed");
+        out.write_f32(0.0).expect("Write failed");
+        out.write_i64(0).expect("Write failed");
+        out.write_f32(::std::f32::INFINITY).expect("Write failed");
+        out.write_i64(1).expect("Write failed");
+        write_raw_arc(&mut out, &RawArc { ilabel: 97, olabel: 97, weight: 0.0, nextstate: 0 })
+            .expect("Write failed");
+    }
+    let fsa: VectorFSA<Tropical<f32>, ByteLabel> = read_openfst(&buf[..]).expect("Read failed");
+    let mut dump_buf = Vec::<u8>::new();
+    fsa.dump_tsv(&mut dump_buf);
+    assert_eq!(String::from_utf8(dump_buf).expect("UTF-8 error"), "0\t1\t97\t0\n1\t0\n");
+
+    // ids that are neither bytes nor chars, e.g. surrogates and beyond Unicode
+    let mut fsa: VectorFSA<Tropical<f32>, IntLabel> = VectorFSA::new();
+    for _ in 0..3 {
+        fsa.add_new_state();
+    }
+    fsa.add_arc(&0, SimpleArc::new(IntLabel::from(0xD800), Tropical::new(0.0), 1));
+    fsa.add_arc(&1, SimpleArc::new(IntLabel::from(0x110000), Tropical::new(0.0), 2));
+    fsa.set_final_weight(&2, Tropical::new(0.0));
+    let mut buf = Vec::<u8>::new();
+    write_openfst(&fsa, FstFormat::Vector, &mut buf).expect("Write failed");
+    let loaded: VectorFSA<Tropical<f32>, IntLabel> = read_openfst(&buf[..]).expect("Read failed");
+    let mut dump_buf = Vec::<u8>::new();
+    loaded.dump_tsv(&mut dump_buf);
+    assert_eq!(String::from_utf8(dump_buf).expect("UTF-8 error"),
+               "0\t1\t55296\t0\n1\t2\t1114112\t0\n2\t0\n");
+    match read_openfst::<Tropical<f32>, CharLabel, _>(&buf[..]) {
+        Err(FstError::InvalidData(_)) => {}
+        other => panic!("Unexpected result: {:?}", other.map(|m| m.nstates()))
+    }
+}
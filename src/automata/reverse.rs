@@ -24,7 +24,9 @@ pub fn reverse<W: Semiring, L: Label>(m: &VectorFSA<W, L>) -> VectorFSA<W, L> {
         }
     }
 
-    VectorFSA::with_data_unchecked(revarcs, revfinalws)
+    let mut ret = VectorFSA::with_data_unchecked(revarcs, revfinalws);
+    ret.copy_symbols(m);
+    ret
 }
 
 #[test]
@@ -44,8 +46,8 @@ pub fn reverse_test() {
 3	3	6	true
 ".trim().as_bytes());
     let expected_src = "
-0	2	0	true
-0	4	0	true
+0	2	eps	true
+0	4	eps	true
 1	true
 1	1	1	true
 2	1	1	true
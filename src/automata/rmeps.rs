@@ -2,6 +2,7 @@ use automata::{StateMachine,FSA,Semiring,SimpleArc,Label,State,Arc};
 use automata::vector::VectorFSA;
 use automata::lazy::ArcCache;
 use automata::shortestdistance::{shortest_distance};
+use automata::symbol::SymbolTable;
 
 use std::iter;
 use std::rc::Rc;
@@ -76,6 +77,14 @@ impl<M: StateMachine> StateMachine for RmEpsStateMachine<M>  where M::State : Or
         })
     }
 
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.source.input_symbols()
+    }
+
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.source.output_symbols()
+    }
+
 }
 
 impl<M: StateMachine> RmEpsStateMachine<M>  where M::State : Ord, M::Weight : Ord {
@@ -106,12 +115,12 @@ pub fn rmeps_test() {
     use automata::vector::ByteVectorFSA;
 
     let fst_a = ByteVectorFSA::load_tsv("
-0	1	0	true
+0	1	eps	true
 1	2	1	true
 1	2	2	true
-1	2	0	true
-2	2	0	true
-2	3	0	true
+1	2	eps	true
+2	2	eps	true
+2	3	eps	true
 3	true
 ".trim().as_bytes());
 
@@ -136,12 +145,12 @@ pub fn rmeps_bench(b: &mut Bencher) {
     use automata::vector::ByteVectorFSA;
 
     let fst_a = ByteVectorFSA::load_tsv("
-0	1	0	true
+0	1	eps	true
 1	2	1	true
 1	2	2	true
-1	2	0	true
-2	2	0	true
-2	3	0	true
+1	2	eps	true
+2	2	eps	true
+2	3	eps	true
 3	true
 ".trim().as_bytes());
 
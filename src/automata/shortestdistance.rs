@@ -57,11 +57,11 @@ pub fn shortest_distance_test() {
 
     let fst = ByteVectorFSA::load_tsv("
 0	true
-0	1	0	true
-1	1	0	true
-2	3	0	true
-3	2	0	true
-3	4	0	true
+0	1	eps	true
+1	1	eps	true
+2	3	eps	true
+3	2	eps	true
+3	4	eps	true
 ".trim().as_bytes());
     let dists_0 = shortest_distance(&fst, |_| { true }, 0, |ref a, ref b| { a == b });
     let dists_2 = shortest_distance(&fst, |_| { true }, 2, |ref a, ref b| { a == b });
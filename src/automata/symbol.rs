@@ -0,0 +1,202 @@
+use automata::{Label,DumpTSV,LoadTSV};
+
+use std::collections::BTreeMap;
+use std::io::{Write,Read,BufReader,BufRead};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::rc::Rc;
+
+/// Bidirectional mapping between symbols and label ids
+///
+/// Ids don't need to be contiguous. In TSV, each line holds a symbol and its
+/// id, as in symbol table files of OpenFst.
+#[derive(Clone,Debug,PartialEq,Eq,Default)]
+pub struct SymbolTable {
+    symbols: BTreeMap<usize, String>,
+    ids: BTreeMap<String, usize>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable {
+            symbols: BTreeMap::new(),
+            ids: BTreeMap::new()
+        }
+    }
+
+    /// Adds a symbol with the smallest unused id, and returns the id
+    ///
+    /// If the symbol is already in the table, its id is returned as is.
+    pub fn add_symbol(&mut self, sym: &str) -> usize {
+        if let Some(id) = self.find_id(sym) {
+            return id;
+        }
+        let id = self.symbols.keys().next_back().map(|i| i + 1).unwrap_or(0);
+        self.symbols.insert(id, sym.to_string());
+        self.ids.insert(sym.to_string(), id);
+        id
+    }
+
+    /// Adds a symbol with the given id
+    ///
+    /// Fails if either of the symbol or the id is already bound to another one.
+    pub fn add_symbol_with_id(&mut self, sym: &str, id: usize) -> Result<(), String> {
+        match (self.find_id(sym), self.find_symbol(id)) {
+            (None, None) => {}
+            (Some(i), Some(_)) if i == id => { return Ok(()); }
+            _ => { return Err(format!("Conflicting symbol: {} {}", sym, id)); }
+        }
+        self.symbols.insert(id, sym.to_string());
+        self.ids.insert(sym.to_string(), id);
+        Ok(())
+    }
+
+    pub fn find_id(&self, sym: &str) -> Option<usize> {
+        self.ids.get(sym).cloned()
+    }
+
+    pub fn find_symbol(&self, id: usize) -> Option<&str> {
+        self.symbols.get(&id).map(|s| s.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns pairs of ids and symbols in the order of ids
+    pub fn iter<'a>(&'a self) -> Box<'a + Iterator<Item=(usize, &'a str)>> {
+        box self.symbols.iter().map(|(id, sym)| (*id, sym.as_str()))
+    }
+
+    /// Returns the symbol for `label`, or its default notation if not found
+    pub fn label_to_string<L: SymbolLabel + Display>(syms: Option<&SymbolTable>, label: &L)
+                                                     -> String {
+        syms.and_then(|t| label.symbol_id().and_then(|id| t.find_symbol(id)))
+            .map(|s| s.to_string())
+            .unwrap_or(label.to_string())
+    }
+
+    /// Parses `s` as a symbol in the table, or in the default notation of labels
+    pub fn parse_label<L: SymbolLabel + FromStr>(syms: Option<&SymbolTable>, s: &str)
+                                                 -> Option<L> {
+        match syms.and_then(|t| t.find_id(s)) {
+            Some(id) => L::from_symbol_id(id),
+            None => L::from_str(s).ok()
+        }
+    }
+}
+
+impl DumpTSV for SymbolTable {
+    fn dump_tsv(&self, dest: &mut Write) {
+        for (id, sym) in self.iter() {
+            let line = format!("{}\t{}\n", sym, id);
+            dest.write_all(line.as_bytes()).expect("Dump failed [symbol]");
+        }
+    }
+}
+
+impl LoadTSV<SymbolTable> for SymbolTable {
+    fn load_tsv<R: Read>(src: R) -> SymbolTable {
+        let mut ret = SymbolTable::new();
+        let bufread = BufReader::new(src);
+        for line_or_err in bufread.lines() {
+            let line = line_or_err.expect("Read error");
+            let vals: Vec<&str> = line.split_whitespace().collect();
+            if vals.is_empty() {
+                continue;
+            }
+            assert!(vals.len() == 2, "Symbol table must have 2 columns");
+            let id = usize::from_str(vals[1]).expect("Parse error (symbol id)");
+            ret.add_symbol_with_id(vals[0], id).expect("Parse error (symbol)");
+        }
+        ret
+    }
+}
+
+/// Trait for labels that can be looked up in symbol tables
+pub trait SymbolLabel : Label {
+    /// Returns the id of the label, or `None` for epsilon
+    fn symbol_id(&self) -> Option<usize>;
+
+    /// Returns the label with the id, or `None` if it is out of range
+    fn from_symbol_id(id: usize) -> Option<Self>;
+}
+
+/// Returns true if labels of machines with these tables can be mixed
+///
+/// Machines without symbol tables are compatible with any table.
+pub fn compatible_symbols(a: &Option<Rc<SymbolTable>>, b: &Option<Rc<SymbolTable>>) -> bool {
+    match (a, b) {
+        (&Some(ref a), &Some(ref b)) => Rc::ptr_eq(a, b) || a == b,
+        _ => true
+    }
+}
+
+/// Returns the symbol table for the result of a binary operation
+///
+/// Panics if the tables are not compatible.
+pub fn merge_symbols(a: Option<Rc<SymbolTable>>, b: Option<Rc<SymbolTable>>)
+                     -> Option<Rc<SymbolTable>> {
+    assert!(compatible_symbols(&a, &b), "Symbol tables are not compatible");
+    a.or(b)
+}
+
+#[test]
+pub fn symbol_table_test() {
+    let mut syms = SymbolTable::new();
+    assert_eq!(syms.add_symbol("a"), 0);
+    assert_eq!(syms.add_symbol("b"), 1);
+    assert_eq!(syms.add_symbol("a"), 0);
+    syms.add_symbol_with_id("z", 10).expect("Add failed");
+    assert_eq!(syms.add_symbol("c"), 11);
+    assert!(syms.add_symbol_with_id("b", 2).is_err());
+    assert!(syms.add_symbol_with_id("y", 1).is_err());
+    assert_eq!(syms.find_id("z"), Some(10));
+    assert_eq!(syms.find_symbol(1), Some("b"));
+    assert_eq!(syms.find_symbol(2), None);
+
+    let mut dump_buf = Vec::<u8>::new();
+    syms.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    assert_eq!(dumped, "a\t0\nb\t1\nz\t10\nc\t11\n");
+    assert_eq!(SymbolTable::load_tsv(dumped.as_bytes()), syms);
+
+    let other = Some(Rc::new(SymbolTable::load_tsv("a 0\nb 1".as_bytes())));
+    assert!(compatible_symbols(&Some(Rc::new(syms)), &None));
+    assert!(! compatible_symbols(&Some(Rc::new(SymbolTable::new())), &other));
+    assert!(compatible_symbols(&other.clone(), &other));
+}
+
+#[test]
+pub fn symbol_tsv_test() {
+    use automata::StateMachine;
+    use automata::vector::ByteVectorFSA;
+    use automata::concat::concat;
+
+    let syms = Rc::new(SymbolTable::load_tsv("
+a	1
+b	2
+".trim().as_bytes()));
+    let fst_a = ByteVectorFSA::load_tsv_with_symbols("
+0	1	a	true
+1	2	3	true
+2	true
+".trim().as_bytes(), Some(syms.clone()));
+
+    let expected_src = "
+0	1	a	true
+1	2	3	true
+2	3	eps	true
+3	4	a	true
+4	5	3	true
+5	true
+".trim();
+
+    let result = concat(fst_a.clone(), fst_a);
+    assert!(compatible_symbols(&result.input_symbols(), &Some(syms)));
+    let mut dump_buf = Vec::<u8>::new();
+    result.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    println!(" === Result[Concat with symbols] ===\n{}", dumped);
+    assert!(dumped.trim() == expected_src);
+}
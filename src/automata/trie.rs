@@ -0,0 +1,64 @@
+use automata::{Semiring,Label,SimpleArc,MutableStateMachine,StateMachine,i64state};
+use automata::vector::VectorFSA;
+
+use std::collections::BTreeMap;
+
+/// Builds a deterministic automaton accepting the given words
+///
+/// Each word is paired with its final weight; weights of duplicated words are
+/// summed up. Common prefixes are shared, so this is much cheaper than
+/// determinizing a union of the words.
+pub fn trie<W, L, I>(words: I) -> VectorFSA<W, L>
+    where W: Semiring, L: Label + Ord, I: IntoIterator<Item=(Vec<L>, W)> {
+
+    let mut ret = VectorFSA::new();
+    let mut children: Vec<BTreeMap<L, i64state>> = Vec::new();
+    let root = ret.add_new_state();
+    children.push(BTreeMap::new());
+
+    for (word, weight) in words.into_iter() {
+        let mut st = root;
+        for l in word.into_iter() {
+            let next = children[st as usize].get(&l).cloned();
+            st = match next {
+                Some(next) => next,
+                None => {
+                    let next = ret.add_new_state();
+                    children.push(BTreeMap::new());
+                    children[st as usize].insert(l.clone(), next);
+                    ret.add_arc(&st, SimpleArc::new(l, W::one(), next));
+                    next
+                }
+            };
+        }
+        let fw = ret.final_weight(&st).plus(&weight);
+        ret.set_final_weight(&st, fw);
+    }
+    ret
+}
+
+#[test]
+pub fn trie_test() {
+    use automata::DumpTSV;
+
+    use automata::vector::ByteLabel;
+
+    let words = vec!["ab", "ac", "b", "ab"].into_iter()
+        .map(|w| (w.bytes().map(ByteLabel::from).collect::<Vec<_>>(), true));
+    let expected = "
+0	1	97	true
+0	4	98	true
+1	2	98	true
+1	3	99	true
+2	true
+3	true
+4	true
+".trim();
+
+    let result: VectorFSA<bool, ByteLabel> = trie(words);
+    let mut dump_buf = Vec::<u8>::new();
+    result.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    println!("Trie Result\n====\n{}", dumped);
+    assert!(dumped.trim() == expected);
+}
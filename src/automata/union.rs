@@ -1,6 +1,9 @@
-use automata::{StateMachine,FSA,Semiring,Arc,SimpleArc,Label,State};
+use automata::{StateMachine,FSA,Semiring,Arc,SimpleArc,Label,State,MutableStateMachine,i64state};
 use automata::vector::{VectorFSA};
 use automata::lazy::ArcCache;
+use automata::symbol::{SymbolTable,merge_symbols};
+
+use std::rc::Rc;
 
 #[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Debug)]
 pub enum UnionState<L, R> {
@@ -15,6 +18,8 @@ impl<L: State, R: State> State for UnionState<L, R> {
 pub struct UnionStateMachine<L: StateMachine, R: StateMachine<Weight=L::Weight, Label=L::Label>> {
     left: L,
     right: R,
+    isymbols: Option<Rc<SymbolTable>>,
+    osymbols: Option<Rc<SymbolTable>>,
     cache: ArcCache<UnionState<L::State, R::State>,
                     SimpleArc<UnionState<L::State, R::State>,
                               L::Weight, L::Label>>
@@ -75,13 +80,25 @@ impl<L: StateMachine,
             }
         })
     }
+
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.isymbols.clone()
+    }
+
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.osymbols.clone()
+    }
 }
 
 impl<L: StateMachine, R: StateMachine<Weight=L::Weight, Label=L::Label>> UnionStateMachine<L, R> {
     fn new(left: L, right: R) -> Self {
+        let isymbols = merge_symbols(left.input_symbols(), right.input_symbols());
+        let osymbols = merge_symbols(left.output_symbols(), right.output_symbols());
         UnionStateMachine {
             left: left,
             right: right,
+            isymbols: isymbols,
+            osymbols: osymbols,
             cache: ArcCache::new()
         }
     }
@@ -102,6 +119,40 @@ pub fn union<L: FSA, R: FSA<Weight=L::Weight, Label=L::Label>>(left: L, right: R
     VectorFSA::<L::Weight, L::Label>::new_from_automaton(&dyn)
     }
 
+/// Union of many automata
+///
+/// Unlike folding `union`, this makes a single super-initial state with an
+/// epsilon arc to each operand, so the depth of epsilon chains doesn't grow
+/// with the number of operands.
+pub fn union_all<W: Semiring, L: Label>(machines: Vec<VectorFSA<W, L>>) -> VectorFSA<W, L> {
+    let mut ret = VectorFSA::new();
+    let init = ret.add_new_state();
+    let mut isymbols = None;
+    let mut osymbols = None;
+    for m in machines.into_iter() {
+        isymbols = merge_symbols(isymbols, m.input_symbols());
+        osymbols = merge_symbols(osymbols, m.output_symbols());
+        let offset = ret.nstates().expect("Vector FSA must have a number of states") as i64state;
+        for _ in m.states() {
+            ret.add_new_state();
+        }
+        for st in m.states() {
+            let fw = m.final_weight(&st);
+            if fw.is_nonzero() {
+                ret.set_final_weight(&(st + offset), fw);
+            }
+            for arc in m.arcs(&st) {
+                ret.add_arc(&(st + offset),
+                            SimpleArc::new(arc.label(), arc.weight(), arc.nextstate() + offset));
+            }
+        }
+        ret.add_arc(&init, SimpleArc::new(L::epsilon(), W::one(), m.init_state() + offset));
+    }
+    ret.set_input_symbols(isymbols);
+    ret.set_output_symbols(osymbols);
+    ret
+}
+
 #[test]
 pub fn concat_test() {
     use automata::{LoadTSV,DumpTSV};
@@ -109,10 +160,10 @@ pub fn concat_test() {
 
     let fst_a = ByteVectorFSA::load_tsv("
 0	true
-0	1	0	true
+0	1	eps	true
 1	1	1	true
 1	2	2	true
-2	1	0	true
+2	1	eps	true
 2	2	3	true
 2	true
 ".trim().as_bytes());
@@ -123,10 +174,10 @@ pub fn concat_test() {
 1	true".trim().as_bytes());
 
     let fst_ab_src = "
-0	1	0	true
-0	2	0	true
+0	1	eps	true
+0	2	eps	true
 1	true
-1	3	0	true
+1	3	eps	true
 2	2	1	true
 2	4	2	true
 3	3	1	true
@@ -134,7 +185,7 @@ pub fn concat_test() {
 4	true
 4	4	3	true
 5	true
-5	3	0	true
+5	3	eps	true
 5	5	3	true
 ".trim();
 
@@ -146,3 +197,31 @@ pub fn concat_test() {
     assert!(dumped.trim() == fst_ab_src);
 }
 
+#[test]
+pub fn union_all_test() {
+    use automata::{LoadTSV,DumpTSV};
+    use automata::vector::ByteVectorFSA;
+
+    let fsts: Vec<ByteVectorFSA> = vec!["0	1	1	true\n1	true", "0	1	2	true\n1	true",
+                                         "0	true"].into_iter().map(|src| {
+        ByteVectorFSA::load_tsv(src.as_bytes())
+    }).collect();
+
+    let expected = "
+0	1	eps	true
+0	3	eps	true
+0	5	eps	true
+1	2	1	true
+2	true
+3	4	2	true
+4	true
+5	true
+".trim();
+
+    let result = union_all(fsts);
+    let mut dump_buf = Vec::<u8>::new();
+    result.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    println!("Union Result\n====\n{}", dumped);
+    assert!(dumped.trim() == expected);
+}
@@ -0,0 +1,74 @@
+use automata::{StateMachine,Arc,SimpleArc,Label,Semiring,MutableStateMachine,i64state};
+use automata::vector::{VectorFSA,CharLabel,ByteLabel};
+
+/// Converts a char-labelled automaton to a byte-labelled one
+///
+/// Each arc is replaced with a path reading the UTF-8 encoding of its label,
+/// where the weight is put on the first arc of the path. Epsilon arcs are
+/// kept as epsilon arcs.
+pub fn expand_utf8<W: Semiring>(m: &VectorFSA<W, CharLabel>) -> VectorFSA<W, ByteLabel> {
+    let mut ret = VectorFSA::new();
+    for _ in m.states() {
+        ret.add_new_state();
+    }
+
+    for st in m.states() {
+        let fw = m.final_weight(&st);
+        if fw.is_nonzero() {
+            ret.set_final_weight(&st, fw);
+        }
+        for arc in m.arcs(&st) {
+            let ch = match arc.label().to_char() {
+                Some(ch) => ch,
+                None => {
+                    ret.add_arc(&st, SimpleArc::new(ByteLabel::epsilon(), arc.weight(),
+                                                    arc.nextstate()));
+                    continue;
+                }
+            };
+            let mut buf = [0; 4];
+            let bytes = ch.encode_utf8(&mut buf).as_bytes();
+            let mut prev: i64state = st;
+            let mut weight = arc.weight();
+            for (off, b) in bytes.iter().enumerate() {
+                let next = if off + 1 == bytes.len() {
+                    arc.nextstate()
+                } else {
+                    ret.add_new_state()
+                };
+                ret.add_arc(&prev, SimpleArc::new(ByteLabel::from(*b), weight, next));
+                weight = W::one();
+                prev = next;
+            }
+        }
+    }
+    ret
+}
+
+#[test]
+pub fn expand_utf8_test() {
+    use automata::{LoadTSV,DumpTSV};
+    use automata::vector::CharVectorFSA;
+
+    let fst_a = CharVectorFSA::load_tsv("
+0	1	U+0061	true
+0	1	U+00E9	true
+1	0	eps	true
+1	true
+".trim().as_bytes());
+
+    let expected_src = "
+0	1	97	true
+0	2	195	true
+1	true
+1	0	eps	true
+2	1	169	true
+".trim();
+
+    let result = expand_utf8(&fst_a);
+    let mut dump_buf = Vec::<u8>::new();
+    result.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+    println!(" === Result[ExpandUTF8] ===\n{}", dumped);
+    assert!(dumped.trim() == expected_src);
+}
@@ -1,15 +1,20 @@
-use automata::{StateMachine,FSA,Semiring,Arc,SimpleArc,Label,i64state,boolweight,MutableStateMachine,DumpTSV,LoadTSV,State};
+use automata::{StateMachine,FSA,Semiring,Arc,SimpleArc,Label,i64state,boolweight,
+                MutableStateMachine,DumpTSV,LoadTSV,State};
+use automata::symbol::{SymbolTable,SymbolLabel};
 
 use std::collections::{BTreeMap,BTreeSet};
 use std::io::{Write,Read,BufReader,BufRead};
 use std::str::FromStr;
-use std::fmt::{Display,Debug};
+use std::fmt::{self,Display,Debug};
 use std::cmp;
+use std::rc::Rc;
 
 #[derive(Clone)]
 pub struct VectorFSA<W: Semiring, L: Label> {
     arcs: Vec<Vec<SimpleArc<i64state, W, L>>>,
     finals: Vec<W>,
+    isymbols: Option<Rc<SymbolTable>>,
+    osymbols: Option<Rc<SymbolTable>>,
 }
 
 impl<L: Label, W: Semiring> VectorFSA<W, L> {
@@ -17,7 +22,9 @@ impl<L: Label, W: Semiring> VectorFSA<W, L> {
     pub fn new() -> Self {
         VectorFSA {
             arcs: Vec::new(),
-            finals: Vec::new()
+            finals: Vec::new(),
+            isymbols: None,
+            osymbols: None
         }
     }
 
@@ -26,12 +33,15 @@ impl<L: Label, W: Semiring> VectorFSA<W, L> {
                                finals: Vec<W>) -> Self {
         VectorFSA {
             arcs: arcs,
-            finals: finals
+            finals: finals,
+            isymbols: None,
+            osymbols: None
         }
     }
 
     /// Constructs a new VectorFSA from the given state machine
-    pub fn new_from_automaton<S: State, A: Arc<State=S, Weight=W, Label=L>>(src: &StateMachine<State=S, Label=L, Weight=W, Arc=A>) -> Self {
+    pub fn new_from_automaton<S: State, A: Arc<State=S, Weight=W, Label=L>>(
+        src: &StateMachine<State=S, Label=L, Weight=W, Arc=A>) -> Self {
         let mut statemap = BTreeMap::new();
         let mut get_state_num = move |st: S| {
             let newst = statemap.len();
@@ -65,17 +75,35 @@ impl<L: Label, W: Semiring> VectorFSA<W, L> {
 
         VectorFSA {
             arcs: arcs,
-            finals: finals
+            finals: finals,
+            isymbols: src.input_symbols(),
+            osymbols: src.output_symbols()
         }
     }
 
     pub fn arcs_vec<'a>(&'a self, s: &i64state) -> &'a Vec<SimpleArc<i64state, W, L>> {
         &self.arcs[*s as usize]
     }
+
+    pub fn set_input_symbols(&mut self, syms: Option<Rc<SymbolTable>>) {
+        self.isymbols = syms;
+    }
+
+    pub fn set_output_symbols(&mut self, syms: Option<Rc<SymbolTable>>) {
+        self.osymbols = syms;
+    }
+
+    /// Attaches the symbol tables of `src` to this machine
+    pub fn copy_symbols<M: StateMachine + ?Sized>(&mut self, src: &M) {
+        self.isymbols = src.input_symbols();
+        self.osymbols = src.output_symbols();
+    }
 }
 
-impl<L: Display + Label, W: Display + Semiring> DumpTSV for VectorFSA<W, L> {
+/// Labels are printed as symbols when the input symbol table is attached
+impl<L: Display + SymbolLabel, W: Display + Semiring> DumpTSV for VectorFSA<W, L> {
     fn dump_tsv(&self, dest: &mut Write) {
+        let syms = self.isymbols.as_ref().map(|t| &**t);
         for prev in self.states() {
             let fw = self.final_weight(&prev);
             if fw != W::zero() {
@@ -84,15 +112,28 @@ impl<L: Display + Label, W: Display + Semiring> DumpTSV for VectorFSA<W, L> {
             }
             for arc in self.arcs(&prev) {
                 let line = format!("{}\t{}\t{}\t{}\n", prev,
-                                   arc.nextstate(), arc.label(), arc.weight());
+                                   arc.nextstate(),
+                                   SymbolTable::label_to_string(syms, &arc.label()),
+                                   arc.weight());
                 dest.write_all(line.as_bytes()).expect("Dump failed [arc]");
             }
         }
     }
 }
 
-impl<L: FromStr + Label + Debug, W: FromStr + Semiring + Debug> LoadTSV<VectorFSA<W, L>> for VectorFSA<W, L> {
+impl<L: FromStr + SymbolLabel + Debug, W: FromStr + Semiring + Debug> LoadTSV<VectorFSA<W, L>>
+    for VectorFSA<W, L> {
     fn load_tsv<R: Read>(src: R) -> VectorFSA<W, L> {
+        VectorFSA::load_tsv_with_symbols(src, None)
+    }
+}
+
+impl<L: FromStr + SymbolLabel + Debug, W: FromStr + Semiring + Debug> VectorFSA<W, L> {
+    /// Loads TSV where labels may be written as symbols in `syms`
+    ///
+    /// The table is attached to the result as both input and output symbols.
+    pub fn load_tsv_with_symbols<R: Read>(src: R, syms: Option<Rc<SymbolTable>>)
+                                          -> VectorFSA<W, L> {
         let mut ret = VectorFSA::new();
         let bufread = BufReader::new(src);
         for line_or_err in bufread.lines() {
@@ -115,8 +156,9 @@ impl<L: FromStr + Label + Debug, W: FromStr + Semiring + Debug> LoadTSV<VectorFS
             } else {
                 assert!(vals.len() == 4, "Dumped TSV must have 2 or 4 columns");
                 match (i64::from_str(&vals[0]), i64::from_str(&vals[1]),
-                       L::from_str(&vals[2]), W::from_str(&vals[3])) {
-                    (Ok(p), Ok(q), Ok(l), Ok(w)) => {
+                       SymbolTable::parse_label(syms.as_ref().map(|t| &**t), &vals[2]),
+                       W::from_str(&vals[3])) {
+                    (Ok(p), Ok(q), Some(l), Ok(w)) => {
                         let max_pq = cmp::max(p, q);
                         while ret.nstates().unwrap() <= (max_pq as usize) {
                             ret.add_new_state();
@@ -129,6 +171,8 @@ impl<L: FromStr + Label + Debug, W: FromStr + Semiring + Debug> LoadTSV<VectorFS
                 }
             }
         }
+        ret.set_input_symbols(syms.clone());
+        ret.set_output_symbols(syms);
         ret
     }
 }
@@ -156,6 +200,14 @@ impl<L: Label, W: Semiring> StateMachine for VectorFSA<W, L> {
         let st = *s as usize;
         box self.arcs[st].iter().cloned()
     }
+
+    fn input_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.isymbols.clone()
+    }
+
+    fn output_symbols(&self) -> Option<Rc<SymbolTable>> {
+        self.osymbols.clone()
+    }
 }
 
 
@@ -234,12 +286,211 @@ impl<L: Label, W: Semiring> MutableStateMachine for VectorFSA<W, L> {
 }
 
 
-#[allow(dead_code)]
-pub type ByteVectorFST = VectorFSA<boolweight, (u8, u8)>;
-#[allow(dead_code)]
-pub type ByteVectorFSA = VectorFSA<boolweight, u8>;
+/// Label for bytes
+///
+/// Epsilon is represented by a value out of the byte range, so that every
+/// byte including NUL can be used as a label.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct ByteLabel(u16);
+
+const BYTE_EPSILON: u16 = 0x100;
+
+impl ByteLabel {
+    pub fn to_byte(&self) -> Option<u8> {
+        if self.0 == BYTE_EPSILON {
+            None
+        } else {
+            Some(self.0 as u8)
+        }
+    }
+
+    /// Returns the byte as an index for transition tables
+    ///
+    /// Panics on epsilon; the caller is expected to remove epsilons first.
+    pub fn index(&self) -> usize {
+        self.to_byte().expect("Epsilon cannot be used as a table index") as usize
+    }
+}
+
+impl From<u8> for ByteLabel {
+    fn from(b: u8) -> ByteLabel {
+        ByteLabel(b as u16)
+    }
+}
 
-impl Label for u8 {
-    fn epsilon() -> u8 { 0 }
+impl Label for ByteLabel {
+    fn epsilon() -> ByteLabel { ByteLabel(BYTE_EPSILON) }
 }
 
+/// Prints bytes in decimal, or `eps` for epsilon
+impl Display for ByteLabel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == BYTE_EPSILON {
+            write!(f, "eps")
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl SymbolLabel for ByteLabel {
+    fn symbol_id(&self) -> Option<usize> {
+        self.to_byte().map(|b| b as usize)
+    }
+
+    fn from_symbol_id(id: usize) -> Option<ByteLabel> {
+        if id < 0x100 { Some(ByteLabel(id as u16)) } else { None }
+    }
+}
+
+impl FromStr for ByteLabel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "eps" {
+            return Ok(ByteLabel::epsilon());
+        }
+        u8::from_str(s).map(ByteLabel::from)
+            .map_err(|_| format!("Invalid byte label: {}", s))
+    }
+}
+
+pub type ByteVectorFST = VectorFSA<boolweight, (ByteLabel, ByteLabel)>;
+pub type ByteVectorFSA = VectorFSA<boolweight, ByteLabel>;
+
+
+/// Label for Unicode scalar values
+///
+/// Epsilon is represented by a value out of the Unicode range, so that every
+/// char including NUL can be used as a label.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct CharLabel(u32);
+
+const CHAR_EPSILON: u32 = 0x110000;
+
+impl CharLabel {
+    pub fn to_char(&self) -> Option<char> {
+        if self.0 == CHAR_EPSILON {
+            None
+        } else {
+            char::from_u32(self.0)
+        }
+    }
+}
+
+impl From<char> for CharLabel {
+    fn from(ch: char) -> CharLabel {
+        CharLabel(ch as u32)
+    }
+}
+
+impl Label for CharLabel {
+    fn epsilon() -> CharLabel { CharLabel(CHAR_EPSILON) }
+}
+
+/// Prints code points in hexadecimal, or `eps` for epsilon
+impl Display for CharLabel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == CHAR_EPSILON {
+            write!(f, "eps")
+        } else {
+            write!(f, "U+{:04X}", self.0)
+        }
+    }
+}
+
+impl SymbolLabel for CharLabel {
+    fn symbol_id(&self) -> Option<usize> {
+        self.to_char().map(|ch| ch as usize)
+    }
+
+    fn from_symbol_id(id: usize) -> Option<CharLabel> {
+        if id > 0x10FFFF {
+            return None;
+        }
+        char::from_u32(id as u32).map(CharLabel::from)
+    }
+}
+
+impl FromStr for CharLabel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "eps" {
+            return Ok(CharLabel::epsilon());
+        }
+        if ! s.starts_with("U+") {
+            return Err(format!("Invalid char label: {}", s));
+        }
+        u32::from_str_radix(&s[2..], 16).ok()
+            .and_then(char::from_u32)
+            .map(CharLabel::from)
+            .ok_or(format!("Invalid char label: {}", s))
+    }
+}
+
+pub type CharVectorFSA = VectorFSA<boolweight, CharLabel>;
+
+
+/// Label for plain integer ids, e.g. those of OpenFst symbol tables
+///
+/// Any id can be used except `u32::MAX`, which represents epsilon.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct IntLabel(u32);
+
+const INT_EPSILON: u32 = ::std::u32::MAX;
+
+impl IntLabel {
+    pub fn to_u32(&self) -> Option<u32> {
+        if self.0 == INT_EPSILON {
+            None
+        } else {
+            Some(self.0)
+        }
+    }
+}
+
+impl From<u32> for IntLabel {
+    fn from(id: u32) -> IntLabel {
+        IntLabel(id)
+    }
+}
+
+impl Label for IntLabel {
+    fn epsilon() -> IntLabel { IntLabel(INT_EPSILON) }
+}
+
+/// Prints ids in decimal, or `eps` for epsilon
+impl Display for IntLabel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == INT_EPSILON {
+            write!(f, "eps")
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl SymbolLabel for IntLabel {
+    fn symbol_id(&self) -> Option<usize> {
+        self.to_u32().map(|id| id as usize)
+    }
+
+    fn from_symbol_id(id: usize) -> Option<IntLabel> {
+        if id < INT_EPSILON as usize { Some(IntLabel(id as u32)) } else { None }
+    }
+}
+
+impl FromStr for IntLabel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "eps" {
+            return Ok(IntLabel::epsilon());
+        }
+        match u32::from_str(s) {
+            Ok(id) if id != INT_EPSILON => Ok(IntLabel(id)),
+            _ => Err(format!("Invalid int label: {}", s))
+        }
+    }
+}
@@ -0,0 +1,193 @@
+#![feature(box_syntax, box_patterns,unboxed_closures,try_from,alloc,allocator_api,
+           conservative_impl_trait,universal_impl_trait,test)]
+
+extern crate either;
+#[macro_use]
+extern crate combine;
+extern crate num_traits;
+#[cfg(feature = "jit")]
+extern crate llvm_sys;
+extern crate memmap;
+extern crate alloc;
+extern crate test;
+
+#[cfg(feature = "jit")]
+macro_rules! c_str {
+    ($s:expr) => { {
+        concat!($s, "\0").as_ptr() as *const i8
+    } }
+}
+
+pub mod automata;
+pub mod rexp;
+pub mod runner;
+pub mod utils;
+
+pub use rexp::{Flags,RexpError};
+pub use runner::{MatchMode,Runner,Matcher,LineMatch};
+
+use automata::vector::ByteVectorFSA;
+use runner::cache::{Cache,CacheEntry,cache_key};
+
+use std::io::Read;
+
+/// Options for compiling expressions into a matcher
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct MatcherOptions {
+    pub flags: Flags,
+    pub mode: MatchMode,
+    /// Also select lines within this many byte edits from the expressions
+    pub max_edits: usize,
+    /// Use the JIT runner when possible
+    pub use_jit: bool,
+    /// Use the on-disk cache of compiled expressions; see `Cache::open_default`
+    pub use_cache: bool,
+}
+
+impl Default for MatcherOptions {
+    fn default() -> MatcherOptions {
+        MatcherOptions {
+            flags: Flags::default(),
+            mode: MatchMode::Substring,
+            max_edits: 0,
+            use_jit: false,
+            use_cache: false,
+        }
+    }
+}
+
+/// Returns the cache entry for the expressions, or `None` if it isn't used
+fn open_cache_entry<S: AsRef<str>>(exprs: &[S], options: &MatcherOptions) -> Option<CacheEntry> {
+    if ! options.use_cache {
+        return None;
+    }
+    let runner_type = if options.use_jit && options.mode == MatchMode::Substring {
+        "jit"
+    } else {
+        "table"
+    };
+    let exprs: Vec<String> = exprs.iter().map(|e| e.as_ref().to_string()).collect();
+    let key = cache_key(&exprs, options.flags, options.max_edits, options.mode, runner_type);
+    Cache::open_default().map(|c| c.entry(&key))
+}
+
+fn compile_optimized_fsa_with_cache<S: AsRef<str>>(exprs: &[S], options: &MatcherOptions,
+                                                    cache: Option<&CacheEntry>,
+                                                    warnings: &mut Vec<String>)
+                                                    -> Result<ByteVectorFSA, (usize, RexpError)> {
+    if let Some(optfsa) = cache.and_then(|c| c.load_fsa()) {
+        return Ok(optfsa);
+    }
+    let nfa = rexp::compile_rexp_list_nfa(exprs, options.flags)?;
+    let nfa = rexp::with_max_edits(nfa, options.max_edits);
+    let optfsa = runner::optimize_fsa_with_mode(nfa, options.mode);
+    if let Some(Err(what)) = cache.map(|c| c.store_fsa(&optfsa)) {
+        warnings.push(format!("Cannot write the cache: {}", what));
+    }
+    Ok(optfsa)
+}
+
+/// Compiles expressions into the minimal DFA that matchers run
+///
+/// The DFA is loaded from or stored in the cache if `options.use_cache` is
+/// set. Errors of the cache are returned as warnings.
+pub fn compile_optimized_fsa<S: AsRef<str>>(exprs: &[S], options: &MatcherOptions)
+                                            -> Result<(ByteVectorFSA, Vec<String>),
+                                                      (usize, RexpError)> {
+    let cache = open_cache_entry(exprs, options);
+    let mut warnings = Vec::new();
+    let optfsa = compile_optimized_fsa_with_cache(exprs, options, cache.as_ref(), &mut warnings)?;
+    Ok((optfsa, warnings))
+}
+
+/// Compiles expressions and returns the best runner for them
+///
+/// Lines accepted by any of the expressions are selected. On errors, the
+/// index of the invalid expression is returned with the error.
+pub fn compile_runner<S: AsRef<str>, R: Read>(exprs: &[S], flags: Flags, mode: MatchMode,
+                                              use_jit: bool)
+                                              -> Result<Box<Runner<R>>, (usize, RexpError)> {
+    let nfa = rexp::compile_rexp_list_nfa(exprs, flags)?;
+    Ok(runner::find_best_runner(nfa, use_jit, mode))
+}
+
+/// Compiles expressions and returns the best matcher for them
+///
+/// Same as `compile_runner`, except that selected lines are returned to the
+/// caller instead of being printed. Warnings such as a fallback from the JIT
+/// are discarded; use `compile_matcher_with_options` for getting them.
+pub fn compile_matcher<S: AsRef<str>>(exprs: &[S], flags: Flags, mode: MatchMode, use_jit: bool)
+                                      -> Result<Box<Matcher>, (usize, RexpError)> {
+    let options = MatcherOptions {
+        flags: flags,
+        mode: mode,
+        use_jit: use_jit,
+        .. MatcherOptions::default()
+    };
+    compile_matcher_with_options(exprs, &options).map(|(matcher, _)| matcher)
+}
+
+/// Compiles expressions and returns the best matcher for the options
+///
+/// Problems that don't prevent matching, e.g. fallbacks from the JIT or
+/// errors of the cache, are returned as warnings for the caller to report.
+pub fn compile_matcher_with_options<S: AsRef<str>>(exprs: &[S], options: &MatcherOptions)
+                                                   -> Result<(Box<Matcher>, Vec<String>),
+                                                             (usize, RexpError)> {
+    let cache = open_cache_entry(exprs, options);
+    let mut warnings = Vec::new();
+    let optfsa = compile_optimized_fsa_with_cache(exprs, options, cache.as_ref(), &mut warnings)?;
+    let (matcher, selection_warnings) =
+        runner::find_best_matcher_with_optimized_fsa(optfsa, options.use_jit, options.mode,
+                                                     cache.as_ref());
+    warnings.extend(selection_warnings);
+    Ok((matcher, warnings))
+}
+
+#[test]
+pub fn compile_matcher_with_options_test() {
+    let options = MatcherOptions {
+        mode: MatchMode::WholeLine,
+        max_edits: 1,
+        .. MatcherOptions::default()
+    };
+    let (m, warnings) = compile_matcher_with_options(&["abc"], &options).expect("Compile error");
+    assert!(warnings.is_empty());
+    assert!(m.is_match(b"abd"));
+    assert!(! m.is_match(b"xabc1"));
+    assert_eq!(compile_matcher_with_options(&["a", "b("], &options).err().map(|e| e.0), Some(1));
+}
+
+#[cfg(feature = "jit")]
+#[test]
+pub fn max_edits_jit_test() {
+    // edits must not make matches span lines with either runner
+    let input = "ab\nc\nabd\nxyz\nabc";
+    for &use_jit in [false, true].iter() {
+        let options = MatcherOptions {
+            max_edits: 1,
+            use_jit: use_jit,
+            .. MatcherOptions::default()
+        };
+        let (m, _) = compile_matcher_with_options(&["abc"], &options).expect("Compile error");
+        let lines: Vec<usize> = m.find_lines(input.as_bytes()).map(|l| l.line_number).collect();
+        assert_eq!(lines, vec![1, 3, 5]);
+    }
+}
+
+#[test]
+pub fn compile_matcher_test() {
+    let input = "abc\nxyz\nb\n";
+    let m = compile_matcher(&["b"], Flags::default(), MatchMode::Substring, false)
+        .expect("Compile error");
+    let lines: Vec<usize> = m.find_lines(input.as_bytes()).map(|l| l.line_number).collect();
+    assert_eq!(lines, vec![1, 3]);
+
+    // references to matchers are matchers as well
+    fn collect_lines<M: Matcher>(m: M, input: &str) -> Vec<LineMatch> {
+        m.find_lines(input.as_bytes()).collect()
+    }
+    let lines = collect_lines(&*m, input);
+    assert_eq!(lines[1], LineMatch { line_number: 3, offset: 8, line: b"b".to_vec() });
+    assert_eq!(collect_lines(&m, input).len(), 2);
+}
@@ -1,62 +1,372 @@
-#![feature(box_syntax, box_patterns,unboxed_closures,try_from,alloc,allocator_api,conservative_impl_trait,universal_impl_trait,test)]
-
-extern crate either;
-#[macro_use]
-extern crate combine;
-extern crate num_traits;
+extern crate rustre;
 extern crate clap;
-extern crate llvm_sys;
-extern crate alloc;
-extern crate test;
-
-macro_rules! c_str {
-    ($s:expr) => { {
-        concat!($s, "\0").as_ptr() as *const i8
-    } }
-}
 
-mod automata;
-mod rexp;
-mod runner;
-mod utils;
+use rustre::{automata, rexp, runner};
 
-#[allow(unused_imports)]
 use std::io::stdout;
 
-use clap::{Arg, App};
+use clap::{Arg, App, AppSettings, ArgMatches};
 use std::fs::File;
+use std::io::Read;
+use std::process;
+use runner::Runner;
+use automata::DumpDot;
+
+#[cfg(feature = "jit")]
+use clap::SubCommand;
+#[cfg(feature = "jit")]
+use std::ffi::OsStr;
+#[cfg(feature = "jit")]
+use std::io::Write;
+#[cfg(feature = "jit")]
+use std::path::{Path,PathBuf};
+
+/// Returns expressions given by `-e` and in the file given by `-f`
+fn read_exprs(matches: &ArgMatches) -> Vec<String> {
+    let mut exprs: Vec<String> = matches.values_of("expr")
+        .map(|vs| vs.map(|v| v.to_string()).collect())
+        .unwrap_or(Vec::new());
+    if let Some(pattern_file) = matches.value_of("file") {
+        let mut src = String::new();
+        match File::open(pattern_file).and_then(|mut f| f.read_to_string(&mut src)) {
+            Ok(_) => {}
+            Err(what) => {
+                eprintln!("Cannot read the pattern file: {}", what);
+                process::exit(2);
+            }
+        }
+        exprs.extend(src.lines().map(|l| l.to_string()));
+    }
+    exprs
+}
+
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings.iter() {
+        eprintln!("[WARN] {}", warning);
+    }
+}
+
+/// Reports the error of the expression with the index, and exits
+fn exit_invalid_expression((id, what): (usize, rexp::RexpError)) -> ! {
+    eprintln!("Invalid expression #{}: {:?}", id, what);
+    process::exit(2);
+}
+
+#[cfg(feature = "jit")]
+fn aot_command<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("aot")
+        .about("Compiles expressions ahead of time into a linkable matcher")
+        .arg(Arg::with_name("expr")
+             .short("e")
+             .long("expr")
+             .value_name("EXPR")
+             .required_unless("file")
+             .multiple(true)
+             .number_of_values(1)
+             .help("Regular expression; may be given several times")
+             .takes_value(true))
+        .arg(Arg::with_name("file")
+             .short("f")
+             .long("file")
+             .value_name("FILE")
+             .help("Read expressions from FILE, one per line")
+             .takes_value(true))
+        .arg(Arg::with_name("ignore-case")
+             .short("i")
+             .long("ignore-case")
+             .help("Ignore case distinctions"))
+        .arg(Arg::with_name("output")
+             .short("o")
+             .long("output")
+             .value_name("OUT")
+             .required(true)
+             .help("Output object file; a static library if it ends with .a")
+             .takes_value(true))
+        .arg(Arg::with_name("header")
+             .long("header")
+             .value_name("HEADER")
+             .help("Output C header [default: OUT with extension .h]")
+             .takes_value(true))
+        .arg(Arg::with_name("symbol")
+             .long("symbol")
+             .value_name("NAME")
+             .help("Name of the matcher function [default: rustre_run]")
+             .takes_value(true))
+        .arg(Arg::with_name("target")
+             .long("target")
+             .value_name("TRIPLE")
+             .help("Target triple [default: host]")
+             .takes_value(true))
+}
+
+/// Compiles expressions into an object file or a static library with a C header
+#[cfg(feature = "jit")]
+fn run_aot(matches: &ArgMatches) {
+    let flags = rexp::Flags {
+        case_insensitive: matches.occurrences_of("ignore-case") > 0,
+        .. rexp::Flags::default()
+    };
+    let exprs = read_exprs(matches);
+    let symbol = matches.value_of("symbol").unwrap_or("rustre_run");
+    if ! runner::aot::is_c_identifier(symbol) {
+        eprintln!("--symbol must be a C identifier");
+        process::exit(2);
+    }
+
+    let options = rustre::MatcherOptions { flags: flags, .. rustre::MatcherOptions::default() };
+    let (optfsa, _) = rustre::compile_optimized_fsa(&exprs, &options)
+        .unwrap_or_else(|e| exit_invalid_expression(e));
+    let target = matches.value_of("target").map(|t| t.to_string());
+    let obj = match runner::aot::compile_object(optfsa, symbol, target) {
+        Ok(obj) => obj,
+        Err(what) => {
+            eprintln!("Compilation failed: {}", what);
+            process::exit(2);
+        }
+    };
+
+    let output = Path::new(matches.value_of("output").unwrap());
+    let written = File::create(output).and_then(|mut f| {
+        if output.extension() == Some(OsStr::new("a")) {
+            let member = format!("{}.o", symbol);
+            let member = if member.len() < 16 { member } else { "rustre.o".to_string() };
+            runner::aot::write_static_library(&obj, &member, &[symbol], &mut f)
+        } else {
+            f.write_all(&obj)
+        }
+    });
+    let header = matches.value_of("header").map(PathBuf::from)
+        .unwrap_or(output.with_extension("h"));
+    let written = written.and_then(|_| {
+        File::create(&header).and_then(|mut f| {
+            f.write_all(runner::aot::c_header(symbol, &exprs).as_bytes())
+        })
+    });
+    if let Err(what) = written {
+        eprintln!("Cannot write the output: {}", what);
+        process::exit(2);
+    }
+}
 
 fn main() {
-    let matches = App::new("My GREPPER")
+    let app = App::new("My GREPPER")
         .version("1.0")
         .author("Yotaro Kubo <yotaro@ieee.org>")
         .about("Does GREP")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(Arg::with_name("expr")
              .short("e")
              .long("expr")
              .value_name("EXPR")
-             .required(true)
-             .help("Regular expression")
+             .required_unless("file")
+             .multiple(true)
+             .number_of_values(1)
+             .help("Regular expression; may be given several times")
              .takes_value(true))
-        .arg(Arg::with_name("jit")
-            .short("J")
-            .help("Use JIT when possible"))
+        .arg(Arg::with_name("file")
+             .short("f")
+             .long("file")
+             .value_name("FILE")
+             .help("Read expressions from FILE, one per line")
+             .takes_value(true))
+        .arg(Arg::with_name("ignore-case")
+            .short("i")
+            .long("ignore-case")
+            .help("Ignore case distinctions"))
+        .arg(Arg::with_name("line-regexp")
+            .short("x")
+            .long("line-regexp")
+            .help("Select only lines where the whole line matches"))
+        .arg(Arg::with_name("only-matching")
+            .short("o")
+            .long("only-matching")
+            .help("Print only the matched parts of lines (leftmost-longest)"))
+        .arg(Arg::with_name("replace")
+            .long("replace")
+            .value_name("REPL")
+            .help("Print lines with matches replaced; $1, ${name} refer to groups")
+            .takes_value(true))
+        .arg(Arg::with_name("pattern-ids")
+            .long("pattern-ids")
+            .help("Prefix each line with the ids of the matched expressions"))
+        .arg(Arg::with_name("max-edits")
+            .long("max-edits")
+            .value_name("K")
+            .help("Also match strings within K byte insertions, deletions or substitutions")
+            .takes_value(true))
+        .arg(Arg::with_name("transduce")
+            .long("transduce")
+            .help("Treat the expression as a transducer, and print the shortest rewrite of lines"))
+        .arg(Arg::with_name("no-cache")
+            .long("no-cache")
+            .help("Don't use the cache of compiled expressions in $XDG_CACHE_HOME/rustre, \
+                   which is bounded to 64 MiB and used by default"))
+        .arg(Arg::with_name("explain")
+            .long("explain")
+            .help("Print how the expressions are parsed, and exit"))
+        .arg(Arg::with_name("dump-dot")
+            .long("dump-dot")
+            .help("Print the optimized automaton in Graphviz DOT, and exit"))
         .arg(Arg::with_name("INPUT")
              .help("Sets the input file to use")
-             .required(true)
-             .index(1))
-        .get_matches();
-    let fsa = rexp::compile_rexp_nfa(matches.value_of("expr").unwrap());
+             .required_unless_one(&["explain", "dump-dot"])
+             .index(1));
+    #[cfg(feature = "jit")]
+    let app = app
+        .subcommand(aot_command())
+        .arg(Arg::with_name("jit")
+            .short("J")
+            .help("Use JIT when possible"));
+    let matches = app.get_matches();
+    #[cfg(feature = "jit")]
+    {
+        if let Some(aot_matches) = matches.subcommand_matches("aot") {
+            run_aot(aot_matches);
+            return;
+        }
+    }
+    let flags = rexp::Flags {
+        case_insensitive: matches.occurrences_of("ignore-case") > 0,
+        .. rexp::Flags::default()
+    };
+    let exprs = read_exprs(&matches);
+    if matches.occurrences_of("explain") > 0 {
+        let mut failed = false;
+        for (id, expr) in exprs.iter().enumerate() {
+            match rexp::parse_rexp_with_flags(expr, flags) {
+                Ok(ast) => {
+                    println!("#{}: {}", id, ast);
+                    print!("{}", ast.explain());
+                }
+                Err(what) => {
+                    eprintln!("Invalid expression #{}: {:?}", id, what);
+                    failed = true;
+                }
+            }
+        }
+        process::exit(if failed { 2 } else { 0 });
+    }
+
+    let only_matching = matches.occurrences_of("only-matching") > 0;
+    let replacement = matches.value_of("replace").map(|r| r.to_string());
+    if (only_matching || replacement.is_some()) && matches.occurrences_of("line-regexp") > 0 {
+        eprintln!("-x cannot be used with -o or --replace");
+        process::exit(2);
+    }
+    if replacement.is_some() && exprs.len() != 1 {
+        eprintln!("--replace requires exactly one expression");
+        process::exit(2);
+    }
+    let pattern_ids = matches.occurrences_of("pattern-ids") > 0;
+    if pattern_ids && (only_matching || replacement.is_some()) {
+        eprintln!("--pattern-ids cannot be used with -o or --replace");
+        process::exit(2);
+    }
+    let transduce = matches.occurrences_of("transduce") > 0;
+    if transduce && exprs.len() != 1 {
+        eprintln!("--transduce requires exactly one expression");
+        process::exit(2);
+    }
+    if transduce && (only_matching || replacement.is_some() || pattern_ids ||
+                     matches.occurrences_of("line-regexp") > 0) {
+        eprintln!("--transduce cannot be used with -x, -o, --replace or --pattern-ids");
+        process::exit(2);
+    }
+    let max_edits = match matches.value_of("max-edits").map(|k| k.parse::<usize>()) {
+        None => 0,
+        Some(Ok(k)) => k,
+        Some(Err(_)) => {
+            eprintln!("--max-edits must be a non-negative integer");
+            process::exit(2);
+        }
+    };
+    if max_edits > 0 && (pattern_ids || replacement.is_some() || transduce) {
+        eprintln!("--max-edits cannot be used with --pattern-ids, --replace or --transduce");
+        process::exit(2);
+    }
+    let dump_dot = matches.occurrences_of("dump-dot") > 0;
+    if dump_dot && (pattern_ids || replacement.is_some() || transduce) {
+        eprintln!("--dump-dot cannot be used with --pattern-ids, --replace or --transduce");
+        process::exit(2);
+    }
+    let mode = if matches.occurrences_of("line-regexp") > 0 {
+        runner::MatchMode::WholeLine
+    } else {
+        runner::MatchMode::Substring
+    };
+
+    let open_input = || {
+        let filename = matches.value_of("INPUT").unwrap();
+        match File::open(filename) {
+            Ok(f) => f,
+            Err(what) => {
+                eprintln!("Cannot open {}: {}", filename, what);
+                process::exit(2);
+            }
+        }
+    };
+
+    if replacement.is_some() {
+        let matcher = match rexp::capture::CaptureMatcher::new_with_flags(&exprs[0], flags) {
+            Ok(m) => m,
+            Err(what) => {
+                eprintln!("Invalid expression: {:?}", what);
+                process::exit(2);
+            }
+        };
+        let mut runner = runner::capture::CaptureRunner::new(matcher, only_matching, replacement);
+        runner.run(open_input());
+        return;
+    }
+
+    if transduce {
+        let fst = match rexp::transducer::compile_rexp_fst_with_flags(&exprs[0], flags) {
+            Ok(fst) => fst,
+            Err(what) => {
+                eprintln!("Invalid expression: {:?}", what);
+                process::exit(2);
+            }
+        };
+        let mut runner = runner::rewrite::RewriteRunner::new(fst);
+        runner.run(open_input());
+        return;
+    }
+
+    if pattern_ids {
+        let nfa = rexp::set::compile_rexp_set_nfa(&exprs, flags)
+            .unwrap_or_else(|e| exit_invalid_expression(e));
+        let mut runner = runner::set::PatternSetRunner::new(nfa, mode);
+        runner.run(open_input());
+        return;
+    }
 
-    let filename = matches.value_of("INPUT").unwrap();
-    let use_jit = matches.occurrences_of("jit") > 0;
+    if only_matching {
+        let nfa = rexp::compile_rexp_list_nfa(&exprs, flags)
+            .unwrap_or_else(|e| exit_invalid_expression(e));
+        let nfa = rexp::with_max_edits(nfa, max_edits);
+        let mut runner = runner::find::OnlyMatchingRunner::new(nfa);
+        runner.run(open_input());
+        return;
+    }
 
-    let file = match File::open(filename) {
-        Ok(f) => f,
-        Err(_) => panic!("Cannot open the file")
+    let options = rustre::MatcherOptions {
+        flags: flags,
+        mode: mode,
+        max_edits: max_edits,
+        use_jit: matches.occurrences_of("jit") > 0,
+        use_cache: matches.occurrences_of("no-cache") == 0,
     };
 
-    let mut runner = runner::find_best_runner(fsa, use_jit);
+    if dump_dot {
+        let (optfsa, warnings) = rustre::compile_optimized_fsa(&exprs, &options)
+            .unwrap_or_else(|e| exit_invalid_expression(e));
+        print_warnings(&warnings);
+        optfsa.dump_dot(&mut stdout());
+        return;
+    }
 
-    runner.run(file);
+    let (mut matcher, warnings) = rustre::compile_matcher_with_options(&exprs, &options)
+        .unwrap_or_else(|e| exit_invalid_expression(e));
+    print_warnings(&warnings);
+    matcher.run(open_input());
 }
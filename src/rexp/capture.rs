@@ -0,0 +1,755 @@
+use automata::{StateMachine,MutableStateMachine,FSA,Arc,SimpleArc,Label,boolweight,i64state};
+use automata::vector::VectorFSA;
+use automata::rmeps::rmeps;
+use automata::minimize::minimize;
+use rexp::{Rexp,Flags,RexpError,parse_rexp_with_flags,ast_to_fsa};
+
+use std::collections::BTreeMap;
+use std::mem;
+use std::ops::Range;
+
+/// Labels for tagged NFA
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum TagLabel {
+    Epsilon,
+    Byte(u8),
+    /// Records the current position to the slot, without consuming input
+    Save(usize),
+}
+
+impl Label for TagLabel {
+    fn epsilon() -> Self { TagLabel::Epsilon }
+}
+
+/// NFA with tagged transitions
+///
+/// Arcs leaving a state are ordered by priority, i.e. the first arc is
+/// preferred by the leftmost-first disambiguation.
+pub type TaggedNFA = VectorFSA<boolweight, TagLabel>;
+
+/// Appends names of the capturing groups in `ast` in the order of `(`
+fn group_names(ast: &Rexp, names: &mut Vec<Option<String>>) {
+    match ast {
+        &Rexp::Group(ref child) => {
+            names.push(None);
+            group_names(&child, names);
+        }
+        &Rexp::NamedGroup(ref name, ref child) => {
+            names.push(Some(name.clone()));
+            group_names(&child, names);
+        }
+        &Rexp::Many1(ref child) | &Rexp::Many0(ref child) | &Rexp::Option(ref child) |
+        &Rexp::Lazy(ref child) | &Rexp::Not(ref child) | &Rexp::Repeat(_, _, ref child) |
+        &Rexp::FlagGroup(_, ref child) => {
+            group_names(&child, names);
+        }
+        &Rexp::Or(ref children) | &Rexp::And(ref children) | &Rexp::Seq(ref children) => {
+            for child in children.iter() {
+                group_names(&child, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn count_groups(ast: &Rexp) -> usize {
+    let mut names = Vec::new();
+    group_names(ast, &mut names);
+    names.len()
+}
+
+/// Thompson-style construction of `TaggedNFA` from AST
+///
+/// Each `build*` function returns entry and exit states of the fragment.
+struct TaggedNFABuilder {
+    nfa: TaggedNFA,
+    ngroups: usize,
+}
+
+impl TaggedNFABuilder {
+    fn new_state(&mut self) -> i64state {
+        self.nfa.add_new_state()
+    }
+
+    fn link(&mut self, from: i64state, label: TagLabel, to: i64state) {
+        self.nfa.add_arc(&from, SimpleArc::new(label, true, to));
+    }
+
+    /// Adds two epsilon arcs from `from`, the first one has priority
+    fn link_prior(&mut self, from: i64state, first: i64state, second: i64state) {
+        self.link(from, TagLabel::Epsilon, first);
+        self.link(from, TagLabel::Epsilon, second);
+    }
+
+    fn build(&mut self, ast: &Rexp, flags: &mut Flags) -> (i64state, i64state) {
+        match ast {
+            &Rexp::Group(ref child) | &Rexp::NamedGroup(_, ref child) => {
+                self.build_group(&child, flags)
+            }
+            &Rexp::SetFlags(ref spec) => {
+                flags.apply(spec);
+                let st = self.new_state();
+                (st, st)
+            }
+            &Rexp::FlagGroup(ref spec, ref child) => {
+                let mut inner = flags.clone();
+                inner.apply(spec);
+                self.build(&child, &mut inner)
+            }
+            &Rexp::Seq(ref children) => {
+                let entry = self.new_state();
+                let mut exit = entry;
+                for child in children.iter() {
+                    let (ce, cx) = self.build(&child, flags);
+                    self.link(exit, TagLabel::Epsilon, ce);
+                    exit = cx;
+                }
+                (entry, exit)
+            }
+            &Rexp::Or(ref children) => {
+                let entry = self.new_state();
+                let exit = self.new_state();
+                for child in children.iter() {
+                    let (ce, cx) = self.build(&child, flags);
+                    self.link(entry, TagLabel::Epsilon, ce);
+                    self.link(cx, TagLabel::Epsilon, exit);
+                }
+                (entry, exit)
+            }
+            &Rexp::Many0(ref child) => self.build_star(&child, flags, true),
+            &Rexp::Many1(ref child) => self.build_plus(&child, flags, true),
+            &Rexp::Option(ref child) => self.build_option(&child, flags, true),
+            &Rexp::Repeat(b, e, ref child) => self.build_repeat(b, e, &child, flags, true),
+            &Rexp::Lazy(ref quantified) => {
+                match **quantified {
+                    Rexp::Many0(ref child) => self.build_star(&child, flags, false),
+                    Rexp::Many1(ref child) => self.build_plus(&child, flags, false),
+                    Rexp::Option(ref child) => self.build_option(&child, flags, false),
+                    Rexp::Repeat(b, e, ref child) => self.build_repeat(b, e, &child, flags, false),
+                    _ => self.build(&quantified, flags)
+                }
+            }
+            _ => self.build_leaf(ast, flags)
+        }
+    }
+
+    fn build_group(&mut self, child: &Rexp, flags: &Flags) -> (i64state, i64state) {
+        let idx = self.ngroups;
+        self.ngroups += 1;
+
+        let entry = self.new_state();
+        let exit = self.new_state();
+        let (ce, cx) = self.build(child, &mut flags.clone());
+        self.link(entry, TagLabel::Save(idx * 2), ce);
+        self.link(cx, TagLabel::Save(idx * 2 + 1), exit);
+        (entry, exit)
+    }
+
+    fn build_star(&mut self, child: &Rexp, flags: &Flags, greedy: bool) -> (i64state, i64state) {
+        let entry = self.new_state();
+        let exit = self.new_state();
+        let (ce, cx) = self.build(child, &mut flags.clone());
+        if greedy {
+            self.link_prior(entry, ce, exit);
+        } else {
+            self.link_prior(entry, exit, ce);
+        }
+        self.link(cx, TagLabel::Epsilon, entry);
+        (entry, exit)
+    }
+
+    fn build_plus(&mut self, child: &Rexp, flags: &Flags, greedy: bool) -> (i64state, i64state) {
+        let entry = self.new_state();
+        let exit = self.new_state();
+        let (ce, cx) = self.build(child, &mut flags.clone());
+        self.link(entry, TagLabel::Epsilon, ce);
+        if greedy {
+            self.link_prior(cx, entry, exit);
+        } else {
+            self.link_prior(cx, exit, entry);
+        }
+        (entry, exit)
+    }
+
+    fn build_option(&mut self, child: &Rexp, flags: &Flags, greedy: bool) -> (i64state, i64state) {
+        let entry = self.new_state();
+        let exit = self.new_state();
+        let (ce, cx) = self.build(child, &mut flags.clone());
+        if greedy {
+            self.link_prior(entry, ce, exit);
+        } else {
+            self.link_prior(entry, exit, ce);
+        }
+        self.link(cx, TagLabel::Epsilon, exit);
+        (entry, exit)
+    }
+
+    /// Builds `child{b,e}` as `child...child(child(child)?)?`
+    ///
+    /// Groups in the copies share the same indices, so the last iteration is
+    /// reported as usual.
+    fn build_repeat(&mut self, b: usize, e: Option<usize>, child: &Rexp, flags: &Flags,
+                    greedy: bool) -> (i64state, i64state) {
+        let first_group = self.ngroups;
+        let entry = self.new_state();
+        let mut cur = entry;
+
+        for _ in 0..b {
+            self.ngroups = first_group;
+            let (ce, cx) = self.build(child, &mut flags.clone());
+            self.link(cur, TagLabel::Epsilon, ce);
+            cur = cx;
+        }
+
+        match e {
+            None => {
+                self.ngroups = first_group;
+                let (se, sx) = self.build_star(child, flags, greedy);
+                self.link(cur, TagLabel::Epsilon, se);
+                cur = sx;
+            }
+            Some(e) => {
+                let exit = self.new_state();
+                for _ in b..e {
+                    self.ngroups = first_group;
+                    let (ce, cx) = self.build(child, &mut flags.clone());
+                    if greedy {
+                        self.link_prior(cur, ce, exit);
+                    } else {
+                        self.link_prior(cur, exit, ce);
+                    }
+                    cur = cx;
+                }
+                self.link(cur, TagLabel::Epsilon, exit);
+                cur = exit;
+            }
+        }
+
+        self.ngroups = first_group + count_groups(child);
+        (entry, cur)
+    }
+
+    /// Embeds an untagged automaton for `ast`
+    ///
+    /// Groups inside of intersections or complements are not tracked, and
+    /// never reported as matched.
+    fn build_leaf(&mut self, ast: &Rexp, flags: &mut Flags) -> (i64state, i64state) {
+        let fsa = rmeps(ast_to_fsa(ast, flags));
+        let offset = self.nfa.nstates().expect("Vector FSA must have a number of states");
+        let offset = offset as i64state;
+        for _ in fsa.states() {
+            self.new_state();
+        }
+        let exit = self.new_state();
+        for st in fsa.states() {
+            for arc in fsa.arcs(&st) {
+                let label = match arc.label().to_byte() {
+                    Some(b) => TagLabel::Byte(b),
+                    None => TagLabel::Epsilon,
+                };
+                self.link(st + offset, label, arc.nextstate() + offset);
+            }
+            if fsa.final_weight(&st) {
+                self.link(st + offset, TagLabel::Epsilon, exit);
+            }
+        }
+        self.ngroups += count_groups(ast);
+        (fsa.init_state() + offset, exit)
+    }
+}
+
+/// Label of `TaggedDFA` arcs
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum TDFALabel {
+    /// Never used in `TaggedDFA`
+    Epsilon,
+    /// Consumes the byte, and applies the register operations with the index
+    Byte(u8, usize),
+    /// Leads to the final state if the configuration with the index matches
+    Final(usize),
+}
+
+impl Label for TDFALabel {
+    fn epsilon() -> Self { TDFALabel::Epsilon }
+}
+
+/// Operations updating registers on a transition of `TaggedDFA`
+///
+/// Each state of the tagged DFA is an ordered list of NFA configurations, and
+/// each configuration owns a register for every slot. The registers of the
+/// `i`-th configuration of the next state are copied from those of the
+/// configuration `ops[i].0` of the current state, or cleared if `None`, and
+/// then the slots in `ops[i].1` are set to the current position.
+pub type RegisterOps = Vec<(Option<usize>, Vec<usize>)>;
+
+/// Tagged DFA (TDFA) simulating `TaggedNFA` in a single pass
+///
+/// `fsa` is deterministic on the input bytes. A state has a `Final(i)` arc to
+/// the only final state if a match ending there is reported with the registers
+/// of the `i`-th configuration.
+pub struct TaggedDFA {
+    pub fsa: VectorFSA<boolweight, TDFALabel>,
+    /// Operations applied at the beginning of the input
+    pub init_ops: RegisterOps,
+    /// Operations referred by `TDFALabel::Byte`
+    pub ops: Vec<RegisterOps>,
+    /// The number of configurations, i.e. of register sets, needed
+    pub nconfigs: usize,
+}
+
+/// Follows epsilon and save arcs from `sources` in the priority order
+///
+/// Returns states of the NFA that consume input or are final, and the register
+/// operations for them. Only the first path reaching a state is taken, which
+/// implements the leftmost-first disambiguation.
+fn tagged_closure(nfa: &TaggedNFA, sources: &[(Option<usize>, i64state)])
+                  -> (Vec<i64state>, RegisterOps) {
+    let nstates = nfa.nstates().expect("Vector FSA must have a number of states");
+    let mut seen = vec![false; nstates];
+    let mut configs = Vec::new();
+    let mut ops = Vec::new();
+    for &(src, st) in sources.iter() {
+        // Explicit stack is used instead of recursion since long repetitions
+        // produce long epsilon chains.
+        let mut stack = vec![(st, Vec::new())];
+        while let Some((st, saved)) = stack.pop() {
+            if seen[st as usize] {
+                continue;
+            }
+            seen[st as usize] = true;
+
+            let arcs = nfa.arcs_vec(&st);
+            let consuming = arcs.iter().any(|a| {
+                match a.label() {
+                    TagLabel::Byte(_) => true,
+                    _ => false
+                }
+            });
+            if consuming || nfa.final_weight(&st) {
+                configs.push(st);
+                ops.push((src, saved.clone()));
+            }
+
+            for arc in arcs.iter().rev() {
+                match arc.label() {
+                    TagLabel::Epsilon => {
+                        stack.push((arc.nextstate(), saved.clone()));
+                    }
+                    TagLabel::Save(slot) => {
+                        let mut saved = saved.clone();
+                        if let Err(idx) = saved.binary_search(&slot) {
+                            saved.insert(idx, slot);
+                        }
+                        stack.push((arc.nextstate(), saved));
+                    }
+                    TagLabel::Byte(_) => {}
+                }
+            }
+        }
+    }
+    (configs, ops)
+}
+
+/// Determinizes the tagged NFA into a TDFA
+///
+/// States of the result are ordered lists of NFA configurations. Since
+/// registers are attached to positions in the lists, the lists alone identify
+/// states. Configurations with lower priority than a final one are dropped as
+/// they can never be reported.
+pub fn determinize_tagged(nfa: &TaggedNFA) -> TaggedDFA {
+    let mut fsa = VectorFSA::new();
+    let mut ids: BTreeMap<Vec<i64state>, i64state> = BTreeMap::new();
+    let mut op_ids: BTreeMap<RegisterOps, usize> = BTreeMap::new();
+    let mut ops = Vec::new();
+
+    let (init, init_ops) = tagged_closure(nfa, &[(None, nfa.init_state())]);
+    let mut nconfigs = init.len();
+    let init_st = fsa.add_new_state();
+    let final_st = fsa.add_new_state();
+    fsa.set_final_weight(&final_st, true);
+    ids.insert(init.clone(), init_st);
+
+    let mut queue = vec![(init, init_st)];
+    while let Some((configs, st)) = queue.pop() {
+        let nalive = match configs.iter().position(|c| nfa.final_weight(c)) {
+            Some(idx) => {
+                fsa.add_arc(&st, SimpleArc::new(TDFALabel::Final(idx), true, final_st));
+                idx
+            }
+            None => configs.len()
+        };
+
+        let mut by_byte: Vec<Vec<(Option<usize>, i64state)>> = vec![Vec::new(); 0x100];
+        for (idx, c) in configs[..nalive].iter().enumerate() {
+            for arc in nfa.arcs(c) {
+                if let TagLabel::Byte(b) = arc.label() {
+                    by_byte[b as usize].push((Some(idx), arc.nextstate()));
+                }
+            }
+        }
+        for (b, sources) in by_byte.into_iter().enumerate() {
+            if sources.is_empty() {
+                continue;
+            }
+            let (next, next_ops) = tagged_closure(nfa, &sources);
+            if next.is_empty() {
+                continue;
+            }
+            nconfigs = nconfigs.max(next.len());
+
+            let next_st = match ids.get(&next) {
+                Some(next_st) => *next_st,
+                None => {
+                    let next_st = fsa.add_new_state();
+                    ids.insert(next.clone(), next_st);
+                    queue.push((next, next_st));
+                    next_st
+                }
+            };
+            let op_id = match op_ids.get(&next_ops) {
+                Some(op_id) => *op_id,
+                None => {
+                    ops.push(next_ops.clone());
+                    op_ids.insert(next_ops, ops.len() - 1);
+                    ops.len() - 1
+                }
+            };
+            fsa.add_arc(&st, SimpleArc::new(TDFALabel::Byte(b as u8, op_id), true, next_st));
+        }
+    }
+
+    TaggedDFA {
+        fsa: fsa,
+        init_ops: init_ops,
+        ops: ops,
+        nconfigs: nconfigs,
+    }
+}
+
+/// Minimizes the TDFA by treating register operations as parts of labels
+///
+/// Registers are identified by positions in configuration lists, so states
+/// with the same operations on all paths behave the same.
+pub fn minimize_tagged(tdfa: TaggedDFA) -> TaggedDFA {
+    let has_final = tdfa.fsa.states().any(|st| {
+        tdfa.fsa.arcs(&st).any(|a| match a.label() {
+            TDFALabel::Final(_) => true,
+            _ => false
+        })
+    });
+    if ! has_final {
+        // nothing can be matched, and `minimize` requires a final state
+        return tdfa;
+    }
+    TaggedDFA {
+        fsa: minimize(tdfa.fsa),
+        .. tdfa
+    }
+}
+
+/// Submatch extractor based on `TaggedDFA`
+///
+/// Matches are searched with the leftmost-first (Perl-like) semantics. The
+/// tagged NFA is prefixed by a lazy loop over any bytes, determinized and
+/// minimized, so each search is a single pass over the input.
+pub struct CaptureMatcher {
+    /// `transition[(st << 8) | b]` is the next state and the operation index
+    transition: Vec<Option<(usize, usize)>>,
+    /// The configuration reported if a match ends at the state
+    finals: Vec<Option<usize>>,
+    init_ops: RegisterOps,
+    ops: Vec<RegisterOps>,
+    nconfigs: usize,
+    names: Vec<Option<String>>,
+}
+
+impl CaptureMatcher {
+    pub fn new(rexp_src: &str) -> Result<CaptureMatcher, RexpError> {
+        CaptureMatcher::new_with_flags(rexp_src, Flags::default())
+    }
+
+    pub fn new_with_flags(rexp_src: &str, flags: Flags) -> Result<CaptureMatcher, RexpError> {
+        let ast = parse_rexp_with_flags(rexp_src, flags)?;
+
+        let mut names = vec![None]; // group 0 is the whole match
+        group_names(&ast, &mut names);
+        for (i, name) in names.iter().enumerate() {
+            if let &Some(ref name) = name {
+                if names[(i + 1)..].contains(&Some(name.clone())) {
+                    return Err(RexpError::DuplicateGroupName(name.clone()));
+                }
+            }
+        }
+
+        let mut builder = TaggedNFABuilder {
+            nfa: TaggedNFA::new(),
+            ngroups: 1,
+        };
+        let init = builder.new_state();
+        let (entry, exit) = builder.build(&ast, &mut flags.clone());
+        let finalst = builder.new_state();
+        let restart = builder.new_state();
+        builder.link(init, TagLabel::Save(0), entry);
+        builder.link(exit, TagLabel::Save(1), finalst);
+        builder.nfa.set_final_weight(&finalst, true);
+        // unanchored search, starting later than the matches already started
+        builder.link(init, TagLabel::Epsilon, restart);
+        for b in 0x00..0x100 {
+            builder.link(restart, TagLabel::Byte(b as u8), init);
+        }
+
+        let tdfa = minimize_tagged(determinize_tagged(&builder.nfa));
+        let nstates = tdfa.fsa.nstates().expect("Vector FSA must have a number of states");
+        let mut transition = vec![None; nstates << 8];
+        let mut finals = vec![None; nstates];
+        for st in tdfa.fsa.states() {
+            for arc in tdfa.fsa.arcs(&st) {
+                match arc.label() {
+                    TDFALabel::Byte(b, op) => {
+                        transition[((st as usize) << 8) | (b as usize)] =
+                            Some((arc.nextstate() as usize, op));
+                    }
+                    TDFALabel::Final(idx) => {
+                        finals[st as usize] = Some(idx);
+                    }
+                    TDFALabel::Epsilon => {
+                        panic!("Tagged DFA must not have epsilon arcs");
+                    }
+                }
+            }
+        }
+
+        Ok(CaptureMatcher {
+            transition: transition,
+            finals: finals,
+            init_ops: tdfa.init_ops,
+            ops: tdfa.ops,
+            nconfigs: tdfa.nconfigs,
+            names: names,
+        })
+    }
+
+    /// Returns the number of groups including the group 0 for the whole match
+    pub fn ngroups(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns the index of the group with the given name
+    pub fn group_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n.as_ref().map(|n| n.as_str()) == Some(name))
+    }
+
+    /// Returns the number of states of the tagged DFA
+    pub fn nstates(&self) -> usize {
+        self.finals.len()
+    }
+
+    /// Computes registers in `next` from `cur` by `ops`
+    fn apply_ops(&self, ops: &RegisterOps, cur: &[Option<usize>], next: &mut [Option<usize>],
+                 pos: usize) {
+        let nslots = self.names.len() * 2;
+        for (i, &(src, ref saved)) in ops.iter().enumerate() {
+            let dest = &mut next[(i * nslots)..((i + 1) * nslots)];
+            match src {
+                Some(src) => dest.copy_from_slice(&cur[(src * nslots)..((src + 1) * nslots)]),
+                None => {
+                    for r in dest.iter_mut() {
+                        *r = None;
+                    }
+                }
+            }
+            for slot in saved.iter() {
+                dest[*slot] = Some(pos);
+            }
+        }
+    }
+
+    /// Finds the leftmost-first match starting at or after `start`
+    ///
+    /// Returns ranges for all groups; the group 0 is always set, and the other
+    /// groups are `None` if they didn't participate in the match.
+    pub fn captures_at(&self, haystack: &[u8], start: usize)
+                       -> Option<Vec<Option<Range<usize>>>> {
+        if self.finals.is_empty() {
+            return None;
+        }
+        let nslots = self.names.len() * 2;
+        let mut regs = vec![None; self.nconfigs * nslots];
+        let mut next = regs.clone();
+        self.apply_ops(&self.init_ops, &regs, &mut next, start);
+        mem::swap(&mut regs, &mut next);
+
+        let mut matched: Option<Vec<Option<usize>>> = None;
+        let mut st = 0;
+        let mut pos = start;
+        loop {
+            if let Some(idx) = self.finals[st] {
+                matched = Some(regs[(idx * nslots)..((idx + 1) * nslots)].to_vec());
+            }
+            if pos >= haystack.len() {
+                break;
+            }
+            match self.transition[(st << 8) | (haystack[pos] as usize)] {
+                Some((next_st, op)) => {
+                    self.apply_ops(&self.ops[op], &regs, &mut next, pos + 1);
+                    mem::swap(&mut regs, &mut next);
+                    st = next_st;
+                }
+                None => { break; }
+            }
+            pos += 1;
+        }
+
+        matched.map(|slots| {
+            slots.chunks(2).map(|pair| {
+                match (pair[0], pair[1]) {
+                    (Some(b), Some(e)) => Some(b..e),
+                    _ => None
+                }
+            }).collect()
+        })
+    }
+
+    pub fn captures(&self, haystack: &[u8]) -> Option<Vec<Option<Range<usize>>>> {
+        self.captures_at(haystack, 0)
+    }
+
+    /// Returns an iterator over successive non-overlapping matches
+    pub fn captures_iter<'a, 'b>(&'a self, haystack: &'b [u8]) -> CapturesIter<'a, 'b> {
+        CapturesIter {
+            matcher: self,
+            haystack: haystack,
+            pos: 0,
+            last_end: None,
+        }
+    }
+
+    /// Appends `template` to `dest` with substituting captured substrings
+    ///
+    /// `$N`, `${N}` and `${name}` are replaced with the corresponding group,
+    /// and `$$` is replaced with `$`. Unknown or unmatched groups are replaced
+    /// with an empty string.
+    pub fn expand(&self, template: &str, caps: &[Option<Range<usize>>], haystack: &[u8],
+                  dest: &mut Vec<u8>) {
+        let tmpl = template.as_bytes();
+        let mut i = 0;
+        while i < tmpl.len() {
+            if tmpl[i] != b'$' || i + 1 == tmpl.len() {
+                dest.push(tmpl[i]);
+                i += 1;
+                continue;
+            }
+
+            let (name, next) = if tmpl[i + 1] == b'$' {
+                dest.push(b'$');
+                i += 2;
+                continue;
+            } else if tmpl[i + 1] == b'{' {
+                match tmpl[(i + 2)..].iter().position(|c| *c == b'}') {
+                    Some(len) => (&template[(i + 2)..(i + 2 + len)], i + 3 + len),
+                    None => ("", tmpl.len()),
+                }
+            } else {
+                let len = tmpl[(i + 1)..].iter().take_while(|c| c.is_ascii_digit()).count();
+                if len == 0 {
+                    dest.push(b'$');
+                    i += 1;
+                    continue;
+                }
+                (&template[(i + 1)..(i + 1 + len)], i + 1 + len)
+            };
+
+            let idx = name.parse::<usize>().ok().or_else(|| self.group_index(name));
+            if let Some(&Some(ref range)) = idx.and_then(|idx| caps.get(idx)) {
+                dest.extend_from_slice(&haystack[range.clone()]);
+            }
+            i = next;
+        }
+    }
+}
+
+pub struct CapturesIter<'a, 'b> {
+    matcher: &'a CaptureMatcher,
+    haystack: &'b [u8],
+    pos: usize,
+    last_end: Option<usize>,
+}
+
+impl<'a, 'b> Iterator for CapturesIter<'a, 'b> {
+    type Item = Vec<Option<Range<usize>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos <= self.haystack.len() {
+            let caps = match self.matcher.captures_at(self.haystack, self.pos) {
+                Some(caps) => caps,
+                None => {
+                    self.pos = self.haystack.len() + 1;
+                    return None;
+                }
+            };
+            let whole = caps[0].clone().expect("Group 0 must be matched");
+            if whole.start == whole.end {
+                self.pos = whole.end + 1;
+                // empty match right after the previous match is skipped
+                if self.last_end == Some(whole.end) {
+                    continue;
+                }
+            } else {
+                self.pos = whole.end;
+            }
+            self.last_end = Some(whole.end);
+            return Some(caps);
+        }
+        None
+    }
+}
+
+#[test]
+pub fn captures_test() {
+    let m = CaptureMatcher::new("(a+)(?P<rest>b*)(c)?").expect("Compile error");
+    assert_eq!(m.ngroups(), 4);
+    assert_eq!(m.group_index("rest"), Some(2));
+    assert_eq!(m.captures(b"xaabbd"), Some(vec![Some(1..5), Some(1..3), Some(3..5), None]));
+
+    // leftmost-first, lazy and alternation priority
+    let m = CaptureMatcher::new("(a|ab)(c|bcd)").expect("Compile error");
+    assert_eq!(m.captures(b"abcd"), Some(vec![Some(0..4), Some(0..1), Some(1..4)]));
+    let m = CaptureMatcher::new("a(.*?)b").expect("Compile error");
+    assert_eq!(m.captures(b"axbyb"), Some(vec![Some(0..3), Some(1..2)]));
+    let m = CaptureMatcher::new("(x){2,3}").expect("Compile error");
+    assert_eq!(m.captures(b"xxxx"), Some(vec![Some(0..3), Some(2..3)]));
+    assert_eq!(m.captures(b"x"), None);
+
+    let m = CaptureMatcher::new("a*").expect("Compile error");
+    let all: Vec<_> = m.captures_iter(b"baab").map(|caps| caps[0].clone().unwrap()).collect();
+    assert_eq!(all, vec![0..0, 1..3, 4..4]);
+
+    let m = CaptureMatcher::new("(?P<key>[a-z]+)=([0-9]+)").expect("Compile error");
+    let hay = b"x=1 key=42";
+    let caps = m.captures_at(hay, 1).expect("Match not found");
+    let mut replaced = Vec::new();
+    m.expand("$2:${key}$$", &caps, hay, &mut replaced);
+    assert_eq!(replaced, b"42:key$".to_vec());
+
+    assert!(CaptureMatcher::new("(?P<a>x)(?P<a>y)").err() ==
+            Some(RexpError::DuplicateGroupName("a".to_string())));
+}
+
+#[test]
+pub fn tagged_dfa_test() {
+    // the greedy star gives back only what the rest needs
+    let m = CaptureMatcher::new("(a*)(a)").expect("Compile error");
+    assert_eq!(m.captures(b"aaa"), Some(vec![Some(0..3), Some(0..2), Some(2..3)]));
+    // groups in a loop report the last iteration
+    let m = CaptureMatcher::new("(?:(a)|(b))+").expect("Compile error");
+    assert_eq!(m.captures(b"xab"), Some(vec![Some(1..3), Some(1..2), Some(2..3)]));
+    // a match starting earlier wins even if it ends later
+    let m = CaptureMatcher::new("b|abc").expect("Compile error");
+    assert_eq!(m.captures(b"abc"), Some(vec![Some(0..3)]));
+
+    // the search is a single pass over a minimized TDFA
+    let m = CaptureMatcher::new("abc").expect("Compile error");
+    assert!(m.nstates() < 8);
+    let hay: Vec<u8> = (0..10000).map(|i| if i % 7 == 0 { b'a' } else { b'b' }).collect();
+    assert_eq!(m.captures(&hay), None);
+}
@@ -0,0 +1,1338 @@
+pub mod capture;
+pub mod set;
+pub mod simplify;
+pub mod transducer;
+
+use automata::{MutableStateMachine,StateMachine,FSA,Arc,SimpleArc,Semiring,Label,
+                boolweight,i64state};
+use automata::vector::{VectorFSA,ByteVectorFSA,ByteLabel,CharVectorFSA,CharLabel};
+use automata::concat::concat;
+use automata::rmeps::rmeps;
+use automata::closure::{closure_plus, closure_star};
+use automata::union::{union,union_all};
+use automata::trie::trie;
+use automata::intersect::intersect;
+use automata::complement::complement;
+use automata::determinize::determinize;
+use automata::levenshtein::levenshtein;
+use rexp::simplify::simplify;
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use combine::{many,many1,sep_by,sep_by1,Stream,one_of,none_of,try,optional,Parser};
+use combine::parser::char::{char,string,alpha_num};
+
+/// Label-specific builders for leaves of ASTs
+///
+/// `ast_to_fsa_with` is generic over this trait, so the same AST can be
+/// compiled to automata over bytes (`ByteLeaves`) or over chars
+/// (`CharLeaves`).
+pub trait Leaves {
+    type Label: Label + Ord;
+
+    /// Builds an automaton that accepts any one of the given chars
+    fn char_union<I: Iterator<Item=char>>(chars: I) -> VectorFSA<boolweight, Self::Label>;
+
+    /// Returns labels used for completing automata in complementation
+    ///
+    /// `None` means the alphabet is too large to enumerate, and expressions
+    /// containing `~` must be rejected before compilation.
+    fn alphabet() -> Option<Vec<Self::Label>>;
+}
+
+/// Builds automata over UTF-8 bytes
+pub struct ByteLeaves;
+
+impl Leaves for ByteLeaves {
+    type Label = ByteLabel;
+
+    fn char_union<I: Iterator<Item=char>>(chars: I) -> ByteVectorFSA {
+        let mut fsa = ByteVectorFSA::new();
+        let one = boolweight::one();
+        let init = fsa.add_new_state();
+        let finalst = fsa.add_new_state();
+        fsa.set_final_weight(&finalst, one);
+
+        for ch in chars {
+            let mut buf = [0; 4];
+            let bytes = ch.encode_utf8(&mut buf).as_bytes();
+            let mut prev = init;
+            for (off, b) in bytes.iter().enumerate() {
+                let next = if off + 1 == bytes.len() {
+                    finalst
+                } else {
+                    fsa.add_new_state()
+                };
+                fsa.add_arc(&prev, SimpleArc::new(ByteLabel::from(*b), one, next));
+                prev = next;
+            }
+        }
+        fsa
+    }
+
+    fn alphabet() -> Option<Vec<ByteLabel>> {
+        Some(byte_alphabet())
+    }
+}
+
+/// Builds automata over Unicode scalar values
+pub struct CharLeaves;
+
+impl Leaves for CharLeaves {
+    type Label = CharLabel;
+
+    fn char_union<I: Iterator<Item=char>>(chars: I) -> CharVectorFSA {
+        let mut fsa = CharVectorFSA::new();
+        let one = boolweight::one();
+        let init = fsa.add_new_state();
+        let finalst = fsa.add_new_state();
+        fsa.set_final_weight(&finalst, one);
+
+        for ch in chars {
+            fsa.add_arc(&init, SimpleArc::new(CharLabel::from(ch), one, finalst));
+        }
+        fsa
+    }
+
+    /// Complements aren't supported since the set of all chars is too large
+    ///
+    /// Completing over only the chars seen in the operand would give wrong
+    /// results for the other chars, e.g. when the complement is intersected
+    /// or expanded to bytes.
+    fn alphabet() -> Option<Vec<CharLabel>> {
+        None
+    }
+}
+
+fn make_utf8_dot<B: Leaves>(dot_all: bool) -> VectorFSA<boolweight, B::Label> {
+    // TO DO: multi-byte is not implemented yet
+    let newline = if dot_all { Some('\n') } else { None };
+    B::char_union((0x20..0x7F).filter_map(char::from_u32).chain(newline.into_iter()))
+}
+
+/// Returns chars that are equivalent to `ch` under simple case folding
+///
+/// The equivalence class is approximated by closing `ch` over
+/// `to_lowercase`/`to_uppercase`, ignoring mappings that expand to multiple
+/// chars (e.g. 'ß' to "SS").
+fn simple_case_variants(ch: char) -> Vec<char> {
+    fn single<I: Iterator<Item=char>>(mut it: I) -> Option<char> {
+        match (it.next(), it.next()) {
+            (Some(c), None) => Some(c),
+            _ => None
+        }
+    }
+
+    let mut ret = vec![ch];
+    let mut queue = vec![ch];
+    while let Some(c) = queue.pop() {
+        for v in single(c.to_lowercase()).into_iter().chain(single(c.to_uppercase())) {
+            if ! ret.contains(&v) {
+                ret.push(v);
+                queue.push(v);
+            }
+        }
+    }
+    ret.sort();
+    ret
+}
+
+fn make_char_with_flags<B: Leaves>(ch: char, flags: &Flags) -> VectorFSA<boolweight, B::Label> {
+    if flags.case_insensitive {
+        B::char_union(simple_case_variants(ch).into_iter())
+    } else {
+        B::char_union(Some(ch).into_iter())
+    }
+}
+
+/// Returns true if `ch` is covered by the charset expressions
+fn charset_contains(exprs: &Vec<CharSetExpr>, ch: char) -> bool {
+    exprs.iter().any(|ex| {
+        match ex {
+            &CharSetExpr::Range(beg, end) => {
+                // reversed ranges are currently accepted as is
+                (beg <= ch && ch <= end) || (end <= ch && ch <= beg)
+            }
+            &CharSetExpr::Char(c) => {
+                c == ch
+            }
+        }
+    })
+}
+
+fn make_charset<B: Leaves>(exprs: &Vec<CharSetExpr>, flags: &Flags)
+                           -> VectorFSA<boolweight, B::Label> {
+    let mut chars = BTreeSet::new();
+    for ex in exprs.iter() {
+        let (beg, end) = match ex {
+            &CharSetExpr::Range(beg, end) => {
+                if end < beg { (end, beg) } else { (beg, end) } // should fail?
+            }
+            &CharSetExpr::Char(ch) => {
+                (ch, ch)
+            }
+        };
+        for ch in ((beg as u32)..=(end as u32)).filter_map(char::from_u32) {
+            if flags.case_insensitive {
+                chars.extend(simple_case_variants(ch));
+            } else {
+                chars.insert(ch);
+            }
+        }
+    }
+
+    B::char_union(chars.into_iter())
+}
+
+fn make_charset_inv<B: Leaves>(exprs: &Vec<CharSetExpr>, flags: &Flags)
+                               -> VectorFSA<boolweight, B::Label> {
+    // TO DO: Currenytly only support ASCII
+    let chars = (0x20..0x7F).filter_map(char::from_u32).filter(|ch| {
+        if flags.case_insensitive {
+            ! simple_case_variants(*ch).into_iter().any(|v| charset_contains(exprs, v))
+        } else {
+            ! charset_contains(exprs, *ch)
+        }
+    });
+
+    B::char_union(chars)
+}
+
+
+fn make_empty<L: Label>() -> VectorFSA<boolweight, L> {
+    let mut fsa = VectorFSA::new();
+    let init = fsa.add_new_state();
+    fsa.set_final_weight(&init, boolweight::one());
+    fsa
+}
+
+/// Appends a copy of `src` to `dest`, and returns the entry state of the copy
+///
+/// Final states of the copy are connected to `exit` with epsilon arcs.
+fn splice_copy<L: Label>(dest: &mut VectorFSA<boolweight, L>, src: &VectorFSA<boolweight, L>,
+                         exit: &i64state) -> i64state {
+    let offset = dest.nstates().expect("Vector FSA must have a number of states") as i64state;
+    for _ in src.states() {
+        dest.add_new_state();
+    }
+    for st in src.states() {
+        for arc in src.arcs(&st) {
+            dest.add_arc(&(st + offset),
+                         SimpleArc::new(arc.label(), arc.weight(), arc.nextstate() + offset));
+        }
+        let fw = src.final_weight(&st);
+        if fw.is_nonzero() {
+            dest.add_arc(&(st + offset), SimpleArc::new(L::epsilon(), fw, exit.clone()));
+        }
+    }
+    src.init_state() + offset
+}
+
+/// Builds `child{b,e}`, or `child{b,}` if `e` is `None`
+///
+/// Copies of the compiled `child` are chained through junction states, so
+/// the child is compiled only once however large the counts are. Optional
+/// copies get an epsilon bypass, and an open-ended tail is a closure of
+/// `child`.
+fn make_repeat<L: Label>(child: &VectorFSA<boolweight, L>, b: usize, e: Option<usize>)
+                         -> VectorFSA<boolweight, L> {
+    let one = boolweight::one();
+    let mut fsa = VectorFSA::new();
+    let mut junction = fsa.add_new_state();
+
+    let noptional = e.map(|e| e - b).unwrap_or(0);
+    for i in 0..(b + noptional) {
+        let next = fsa.add_new_state();
+        let entry = splice_copy(&mut fsa, child, &next);
+        fsa.add_arc(&junction, SimpleArc::new(L::epsilon(), one, entry));
+        if i >= b {
+            fsa.add_arc(&junction, SimpleArc::new(L::epsilon(), one, next));
+        }
+        junction = next;
+    }
+
+    if e.is_none() {
+        let next = fsa.add_new_state();
+        let entry = splice_copy(&mut fsa, &closure_star(child.clone()), &next);
+        fsa.add_arc(&junction, SimpleArc::new(L::epsilon(), one, entry));
+        junction = next;
+    }
+
+    fsa.set_final_weight(&junction, one);
+    fsa
+}
+
+/// Returns labels used for completing automata in complementation
+fn byte_alphabet() -> Vec<ByteLabel> {
+    (0..0x100).map(|b| ByteLabel::from(b as u8)).collect()
+}
+
+/// Matching options controlled by inline flags such as `(?i)`
+#[derive(Clone,Copy,Debug,PartialEq,Default)]
+pub struct Flags {
+    /// `i`: letters match regardless of their cases
+    pub case_insensitive: bool,
+    /// `s`: `.` also matches a newline
+    pub dot_all: bool,
+    /// `m`: `^` and `$` match at line boundaries
+    ///
+    /// Input is always matched line by line and the grammar has no anchors,
+    /// so this flag is accepted for compatibility but has no effect.
+    pub multiline: bool,
+    /// `x`: whitespaces and `#`-comments in the expression are ignored
+    ///
+    /// This flag only takes effect from the command line or in flags at the
+    /// very beginning of the expression.
+    pub extended: bool,
+}
+
+impl Flags {
+    /// Updates flags by an inline specification such as `i` or `is-x`
+    pub fn apply(&mut self, spec: &str) {
+        let mut enable = true;
+        for ch in spec.chars() {
+            match ch {
+                '-' => { enable = false; }
+                'i' => { self.case_insensitive = enable; }
+                's' => { self.dot_all = enable; }
+                'm' => { self.multiline = enable; }
+                'x' => { self.extended = enable; }
+                _ => { panic!("Unknown flag: {}", ch) }
+            }
+        }
+    }
+}
+
+/// Returns the flag specification if `src` starts with `(?flags)`
+fn leading_flags(src: &str) -> &str {
+    if src.starts_with("(?") {
+        if let Some(end) = src.find(')') {
+            let spec = &src[2..end];
+            if ! spec.is_empty() && spec.chars().all(|c| FLAG_CHARS.contains(c)) {
+                return spec;
+            }
+        }
+    }
+    ""
+}
+
+/// Removes whitespaces and `#`-comments outside of charsets
+fn strip_extended(src: &str) -> String {
+    let mut ret = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut in_charset = false;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                ret.push(ch);
+                if let Some(escaped) = chars.next() {
+                    ret.push(escaped);
+                }
+            }
+            '[' if ! in_charset => {
+                in_charset = true;
+                ret.push(ch);
+                if chars.peek() == Some(&'^') {
+                    ret.push(chars.next().unwrap());
+                }
+                if chars.peek() == Some(&']') { // literal ']' at the beginning
+                    ret.push(chars.next().unwrap());
+                }
+            }
+            ']' if in_charset => {
+                in_charset = false;
+                ret.push(ch);
+            }
+            '#' if ! in_charset => {
+                while let Some(c) = chars.next() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() && ! in_charset => {
+            }
+            c => {
+                ret.push(c);
+            }
+        }
+    }
+    ret
+}
+
+const FLAG_CHARS: &str = "imsx-";
+const META_CHARS: &str = ".\\*+?^$()[]|&~";
+const ESCAPABLE_CHARS: &str = ".\\*+?^$()[]|&~ #{}";
+
+/// Upper limit for counts in `{n,m}`
+pub const MAX_REPEAT: usize = 0xFFFF;
+
+/// Errors reported while compiling regular expressions
+#[derive(Clone,Debug,PartialEq)]
+pub enum RexpError {
+    /// The source couldn't be parsed
+    Syntax(String),
+    /// `{n,m}` with `m < n`
+    InvalidRepeatBounds(usize, usize),
+    /// A count in `{n,m}` exceeds `MAX_REPEAT`
+    RepeatTooLarge(usize),
+    /// The same name is used for several groups
+    DuplicateGroupName(String),
+}
+
+/// Element of `[...]`
+#[derive(Clone,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum CharSetExpr {
+    Range(char, char),
+    Char(char)
+}
+
+#[derive(Clone,Debug,PartialEq)]
+enum RepeatSpec {
+    Option,
+    Many1,
+    Many0,
+    Repeat(usize, Option<usize>)
+}
+
+/// AST of regular expressions
+#[derive(Clone,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum Rexp {
+    Char(char),
+    CharSet(Vec<CharSetExpr>),
+    CharSetInv(Vec<CharSetExpr>),
+    Dot,
+    /// `(...)`, capturing group
+    Group(Box<Rexp>),
+    /// `(?P<name>...)`, named capturing group
+    NamedGroup(String, Box<Rexp>),
+    //BOS,
+    //EOS,
+    Many1(Box<Rexp>),
+    Many0(Box<Rexp>),
+    Option(Box<Rexp>),
+    /// `{n,m}`, where the upper bound is `None` for `{n,}`
+    Repeat(usize, Option<usize>, Box<Rexp>),
+    /// Non-greedy version of the child quantifier
+    ///
+    /// Since the automata only decide acceptance, this doesn't change the
+    /// language of the child.
+    Lazy(Box<Rexp>),
+    Or(Vec<Box<Rexp>>),
+    /// `A&B`, strings accepted by all of the children
+    And(Vec<Box<Rexp>>),
+    /// `~A`, strings (of bytes) that are not accepted by the child
+    Not(Box<Rexp>),
+    Seq(Vec<Box<Rexp>>),
+    /// `(?flags)`, updates flags until the end of the enclosing group
+    SetFlags(String),
+    /// `(?flags:...)`, updates flags only inside the group
+    FlagGroup(String, Box<Rexp>)
+}
+
+/// Binding strength of AST nodes, used for deciding where parentheses are
+/// needed when printing
+const PREC_OR: u8 = 0;
+const PREC_AND: u8 = 1;
+const PREC_SEQ: u8 = 2;
+const PREC_QUANTIFIED: u8 = 3;
+const PREC_ATOM: u8 = 4;
+
+impl fmt::Display for CharSetExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &CharSetExpr::Range(b, e) => write!(f, "{}-{}", b, e),
+            &CharSetExpr::Char(ch) => write!(f, "{}", ch),
+        }
+    }
+}
+
+/// Writes the body of `[...]`
+///
+/// `]` must come first to be literal, `-` last not to form a range and `^`
+/// anywhere but first not to negate the set.
+fn fmt_charset(exprs: &Vec<CharSetExpr>, f: &mut fmt::Formatter) -> fmt::Result {
+    let is_char = |e: &CharSetExpr, ch: char| *e == CharSetExpr::Char(ch);
+    let has_caret = exprs.iter().any(|e| is_char(e, '^'));
+    let has_hyphen = exprs.iter().any(|e| is_char(e, '-'));
+    let mut ordered: Vec<&CharSetExpr> = exprs.iter().filter(|e| is_char(e, ']')).collect();
+    ordered.extend(exprs.iter().filter(|e| ! is_char(e, ']') && ! is_char(e, '^') &&
+                                       ! is_char(e, '-')));
+    if ordered.is_empty() && has_caret && has_hyphen {
+        // `[-^]`, since `[^-]` would be negated
+        return write!(f, "-^");
+    }
+    for e in ordered.into_iter() {
+        write!(f, "{}", e)?;
+    }
+    if has_caret {
+        write!(f, "^")?;
+    }
+    if has_hyphen {
+        write!(f, "-")?;
+    }
+    Ok(())
+}
+
+impl Rexp {
+    fn precedence(&self) -> u8 {
+        match self {
+            &Rexp::Or(ref children) if children.len() > 1 => PREC_OR,
+            &Rexp::And(_) => PREC_AND,
+            &Rexp::Seq(_) => PREC_SEQ,
+            &Rexp::Many1(_) | &Rexp::Many0(_) | &Rexp::Option(_) | &Rexp::Repeat(_, _, _) |
+            &Rexp::Lazy(_) => PREC_QUANTIFIED,
+            _ => PREC_ATOM
+        }
+    }
+
+    /// Returns true if the expression starts with flags enabling `x`
+    fn is_extended(&self) -> bool {
+        match self {
+            &Rexp::SetFlags(ref spec) => {
+                let mut flags = Flags::default();
+                flags.apply(spec);
+                flags.extended
+            }
+            &Rexp::Or(ref children) | &Rexp::And(ref children) | &Rexp::Seq(ref children) => {
+                children.first().map(|c| c.is_extended()).unwrap_or(false)
+            }
+            _ => false
+        }
+    }
+
+    /// Writes the expression, wrapping it by `(?:...)` if it binds weaker
+    /// than `prec`
+    fn fmt_prec(&self, f: &mut fmt::Formatter, prec: u8, extended: bool) -> fmt::Result {
+        if self.precedence() < prec {
+            write!(f, "(?:")?;
+            self.fmt_prec(f, PREC_OR, extended)?;
+            return write!(f, ")");
+        }
+
+        let join = |f: &mut fmt::Formatter, children: &Vec<Box<Rexp>>, sep: &str, prec: u8|
+                    -> fmt::Result {
+            for (i, child) in children.iter().enumerate() {
+                if i != 0 {
+                    write!(f, "{}", sep)?;
+                }
+                child.fmt_prec(f, prec, extended)?;
+            }
+            Ok(())
+        };
+
+        match self {
+            &Rexp::Char(ch) => {
+                let escaped = META_CHARS.contains(ch) || ch == '{' || ch == '}' ||
+                    (extended && (ch == ' ' || ch == '#'));
+                if escaped {
+                    write!(f, "\\{}", ch)
+                } else {
+                    write!(f, "{}", ch)
+                }
+            }
+            &Rexp::CharSet(ref exprs) => {
+                write!(f, "[")?;
+                fmt_charset(exprs, f)?;
+                write!(f, "]")
+            }
+            &Rexp::CharSetInv(ref exprs) => {
+                write!(f, "[^")?;
+                fmt_charset(exprs, f)?;
+                write!(f, "]")
+            }
+            &Rexp::Dot => write!(f, "."),
+            &Rexp::Group(ref child) => {
+                write!(f, "(")?;
+                child.fmt_prec(f, PREC_OR, extended)?;
+                write!(f, ")")
+            }
+            &Rexp::NamedGroup(ref name, ref child) => {
+                write!(f, "(?P<{}>", name)?;
+                child.fmt_prec(f, PREC_OR, extended)?;
+                write!(f, ")")
+            }
+            &Rexp::Many1(ref child) => {
+                child.fmt_prec(f, PREC_ATOM, extended)?;
+                write!(f, "+")
+            }
+            &Rexp::Many0(ref child) => {
+                child.fmt_prec(f, PREC_ATOM, extended)?;
+                write!(f, "*")
+            }
+            &Rexp::Option(ref child) => {
+                child.fmt_prec(f, PREC_ATOM, extended)?;
+                write!(f, "?")
+            }
+            &Rexp::Repeat(b, e, ref child) => {
+                child.fmt_prec(f, PREC_ATOM, extended)?;
+                match e {
+                    Some(e) if e == b => write!(f, "{{{}}}", b),
+                    Some(e) => write!(f, "{{{},{}}}", b, e),
+                    None => write!(f, "{{{},}}", b),
+                }
+            }
+            &Rexp::Lazy(ref child) => {
+                // only quantifiers can be lazy, so the child binds at this level
+                child.fmt_prec(f, PREC_QUANTIFIED, extended)?;
+                write!(f, "?")
+            }
+            &Rexp::Or(ref children) => join(f, children, "|", PREC_AND),
+            &Rexp::And(ref children) => join(f, children, "&", PREC_SEQ),
+            &Rexp::Not(ref child) => {
+                write!(f, "~")?;
+                child.fmt_prec(f, PREC_ATOM, extended)
+            }
+            &Rexp::Seq(ref children) => join(f, children, "", PREC_QUANTIFIED),
+            &Rexp::SetFlags(ref spec) => write!(f, "(?{})", spec),
+            &Rexp::FlagGroup(ref spec, ref child) => {
+                write!(f, "(?{}:", spec)?;
+                child.fmt_prec(f, PREC_OR, extended)?;
+                write!(f, ")")
+            }
+        }
+    }
+
+    /// Writes the tree structure, one node per line
+    fn explain_into(&self, depth: usize, dest: &mut String) {
+        let indent = "  ".repeat(depth);
+        let (desc, children): (String, Vec<&Rexp>) = match self {
+            &Rexp::Char(ch) => (format!("literal {:?}", ch), vec![]),
+            &Rexp::CharSet(_) | &Rexp::CharSetInv(_) | &Rexp::Dot => {
+                (format!("class {}", self), vec![])
+            }
+            &Rexp::Group(ref child) => ("capture group".to_string(), vec![&**child]),
+            &Rexp::NamedGroup(ref name, ref child) => {
+                (format!("capture group {:?}", name), vec![&**child])
+            }
+            &Rexp::Many1(ref child) => ("one or more".to_string(), vec![&**child]),
+            &Rexp::Many0(ref child) => ("zero or more".to_string(), vec![&**child]),
+            &Rexp::Option(ref child) => ("optional".to_string(), vec![&**child]),
+            &Rexp::Repeat(b, e, ref child) => {
+                let desc = match e {
+                    Some(e) if e == b => format!("exactly {} times", b),
+                    Some(e) => format!("{} to {} times", b, e),
+                    None => format!("at least {} times", b),
+                };
+                (desc, vec![&**child])
+            }
+            &Rexp::Lazy(ref child) => ("lazy".to_string(), vec![&**child]),
+            &Rexp::Or(ref children) => {
+                ("any of".to_string(), children.iter().map(|c| &**c).collect())
+            }
+            &Rexp::And(ref children) => {
+                ("all of".to_string(), children.iter().map(|c| &**c).collect())
+            }
+            &Rexp::Not(ref child) => ("anything but".to_string(), vec![&**child]),
+            &Rexp::Seq(ref children) => {
+                ("sequence".to_string(), children.iter().map(|c| &**c).collect())
+            }
+            &Rexp::SetFlags(ref spec) => (format!("set flags {:?}", spec), vec![]),
+            &Rexp::FlagGroup(ref spec, ref child) => {
+                (format!("with flags {:?}", spec), vec![&**child])
+            }
+        };
+        dest.push_str(&indent);
+        dest.push_str(&desc);
+        dest.push('\n');
+        for child in children {
+            child.explain_into(depth + 1, dest);
+        }
+    }
+
+    /// Describes how the expression is understood, as an indented tree
+    pub fn explain(&self) -> String {
+        let mut ret = String::new();
+        self.explain_into(0, &mut ret);
+        ret
+    }
+}
+
+/// Prints the expression in the canonical syntax
+///
+/// Parsing the output gives the same AST. Non-capturing parentheses `(?:...)`
+/// are only inserted for ASTs that can't be obtained from the parser.
+impl fmt::Display for Rexp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_prec(f, PREC_OR, self.is_extended())
+    }
+}
+
+parser! {
+    fn charset_tail[I]()(I) -> Vec<CharSetExpr> where [I: Stream<Item=char>] {
+        let chars = none_of("]".chars()).map(|c| { CharSetExpr::Char(c) });
+        let range = (none_of("]".chars()), char('-'), none_of("]".chars())).map(|t| {
+            CharSetExpr::Range(t.0, t.2)
+        });
+        many1(try(range).or(chars)).map(|v: Vec<CharSetExpr>| v)
+    }
+}
+
+parser! {
+    fn charset_expr[I]()(I) -> Vec<CharSetExpr> where [I: Stream<Item=char>] {
+        (optional(char(']')), charset_tail()).map(|(ebr, tail)| {
+            match ebr {
+                Some(']') => {
+                    let mut ret = tail.clone();
+                    ret.push(CharSetExpr::Char(']'));
+                    ret
+                }
+                Some(_) => {
+                    panic!("should not reach here")
+                }
+                None => {
+                    tail
+                }
+            }
+        })
+    }
+}
+
+parser! {
+    fn rexp_repeatable[I]()(I) -> Rexp where [I: Stream<Item=char>] {
+        let escaped_char = (char('\\'), one_of(ESCAPABLE_CHARS.chars())).map(|x| x.1);
+        let lit_char = escaped_char.or(none_of(META_CHARS.chars())).map(|x| Rexp::Char(x));
+        let group = (char('('), rexp(), char(')')).map(|x| Rexp::Group(box x.1));
+        let named_group = (string("(?P<"), many1::<String, _>(alpha_num().or(char('_'))),
+                           char('>'), rexp(), char(')'))
+            .map(|t| Rexp::NamedGroup(t.1, box t.3));
+        let set_flags = (char('('), char('?'),
+                         many1::<String, _>(one_of(FLAG_CHARS.chars())), char(')'))
+            .map(|t| Rexp::SetFlags(t.2));
+        let flag_group = (char('('), char('?'),
+                          many::<String, _>(one_of(FLAG_CHARS.chars())), char(':'),
+                          rexp(), char(')'))
+            .map(|t| Rexp::FlagGroup(t.2, box t.4));
+        let charset = (char('['), charset_expr(), char(']')).map(
+            |t| Rexp::CharSet(t.1)
+        );
+        let charset_inv = (char('['), char('^'), charset_expr(), char(']')).map(
+            |t| Rexp::CharSetInv(t.2)
+        );
+        let not = (char('~'), rexp_repeatable()).map(|t| Rexp::Not(box t.1));
+        try(set_flags)
+            .or(try(flag_group))
+            .or(try(named_group))
+            .or(not)
+            .or(try(group))
+            .or(try(charset_inv))
+            .or(try(charset))
+            .or(char('.').map(|_| Rexp::Dot))
+            .or(lit_char)
+    }
+}
+
+parser! {
+    fn repeat_spec[I]()(I) -> RepeatSpec where [I: Stream<Item=char>] {
+        let digits = || { one_of("0123456789".chars()) };
+        let num = || {
+            // overflowing counts are rejected later by `validate`
+            many1(digits()).map(|s: String| s.parse::<usize>().unwrap_or(usize::max_value()))
+        };
+
+        one_of("?*+".chars()).map(|ch| {
+            match ch {
+                '?' => RepeatSpec::Option,
+                '*' => RepeatSpec::Many0,
+                '+' => RepeatSpec::Many1,
+                _ => panic!("Unknown repetition specifier"),
+            }
+        }).or(try((char('{'), num(), char('}')).map(|t| {
+            RepeatSpec::Repeat(t.1, Some(t.1))
+        }))).or(try((char('{'), num(), char(','), char('}')).map(|t| {
+            RepeatSpec::Repeat(t.1, None)
+        }))).or(try((char('{'), num(), char(','), num(), char('}')).map(|t| {
+            RepeatSpec::Repeat(t.1, Some(t.3))
+        }))).or(try((char('{'), char(','), num(), char('}')).map(|t| {
+            RepeatSpec::Repeat(0, Some(t.2))
+        })))
+    }
+}
+
+parser! {
+    fn rexp[I]()(I) -> Rexp where [I: Stream<Item=char>] {
+        let repeat = (rexp_repeatable::<I>(),
+                      optional((repeat_spec::<I>(), optional(char('?'))))).map(|(at, rpm)| {
+            match rpm {
+                Some((spec, lazy)) => {
+                    let quantified = match spec {
+                        RepeatSpec::Option => Rexp::Option(box at),
+                        RepeatSpec::Many0 => Rexp::Many0(box at),
+                        RepeatSpec::Many1 => Rexp::Many1(box at),
+                        RepeatSpec::Repeat(b, e) => Rexp::Repeat(b, e, box at),
+                    };
+                    if lazy.is_some() {
+                        Rexp::Lazy(box quantified)
+                    } else {
+                        quantified
+                    }
+                }
+                None => at
+            }
+        });
+
+        let or_comp = many1(repeat).map(|v: Vec<Rexp>| {
+            if v.len() == 1 {
+                v[0].clone()
+            } else {
+                Rexp::Seq(v.into_iter().map(|x| box x).collect())
+            }
+        });
+
+        let and_comp = sep_by1(or_comp, char('&')).map(|v: Vec<Rexp>| {
+            if v.len() == 1 {
+                v[0].clone()
+            } else {
+                Rexp::And(v.into_iter().map(|x| box x).collect())
+            }
+        });
+
+        sep_by(and_comp, char('|')).map(|v: Vec<Rexp>| {
+            if v.len() == 1 {
+                v[0].clone()
+            } else {
+                Rexp::Or(v.into_iter().map(|x| box x).collect())
+            }
+        })
+    }
+}
+
+pub fn compile_rexp_nfa(rexp_src: &str) -> Result<ByteVectorFSA, RexpError> {
+    compile_rexp_nfa_with_flags(rexp_src, Flags::default())
+}
+
+/// Compiles the expression with the given initial flags
+pub fn compile_rexp_nfa_with_flags(rexp_src: &str, flags: Flags)
+                                   -> Result<ByteVectorFSA, RexpError> {
+    let ast = simplify(parse_rexp_with_flags(rexp_src, flags)?);
+    let mut flags = flags;
+    Ok(ast_to_fsa(&ast, &mut flags))
+}
+
+/// Returns the bytes to match if the expression is a plain literal
+///
+/// Expressions containing any meta character, or compiled with flags that
+/// change the meaning of literals, are not treated as literals.
+fn as_literal(rexp_src: &str, flags: &Flags) -> Option<Vec<ByteLabel>> {
+    if flags.case_insensitive || flags.extended {
+        return None;
+    }
+    if rexp_src.chars().any(|c| META_CHARS.contains(c) || c == '{') {
+        return None;
+    }
+    Some(rexp_src.bytes().map(ByteLabel::from).collect())
+}
+
+/// Compiles expressions into a single automaton accepting any of them
+///
+/// Plain literals are gathered into a trie, and the other expressions are
+/// joined with `union_all`. On error, the index of the first invalid
+/// expression is returned with the cause.
+pub fn compile_rexp_list_nfa<S: AsRef<str>>(rexp_srcs: &[S], flags: Flags)
+                                            -> Result<ByteVectorFSA, (usize, RexpError)> {
+    let mut literals = Vec::new();
+    let mut machines = Vec::new();
+    for (id, src) in rexp_srcs.iter().enumerate() {
+        match as_literal(src.as_ref(), &flags) {
+            Some(word) => literals.push((word, true)),
+            None => {
+                machines.push(compile_rexp_nfa_with_flags(src.as_ref(), flags)
+                              .map_err(|e| (id, e))?);
+            }
+        }
+    }
+    if ! literals.is_empty() {
+        machines.push(trie(literals));
+    }
+    Ok(union_all(machines))
+}
+
+/// Expands the automaton to accept strings within `max_edits` byte edits
+///
+/// Edits are counted in bytes, so replacing a multi-byte UTF-8 character
+/// may need several edits. Newlines are never inserted or substituted, so
+/// that matches don't span lines.
+pub fn with_max_edits(m: ByteVectorFSA, max_edits: usize) -> ByteVectorFSA {
+    if max_edits == 0 {
+        return m;
+    }
+    let alphabet: Vec<ByteLabel> = byte_alphabet().into_iter()
+        .filter(|&l| l != ByteLabel::from(b'\n'))
+        .collect();
+    levenshtein(&rmeps(m), max_edits, &alphabet)
+}
+
+/// Returns true if the AST contains `~`
+fn contains_not(ast: &Rexp) -> bool {
+    match ast {
+        &Rexp::Not(_) => true,
+        &Rexp::Group(ref child) | &Rexp::NamedGroup(_, ref child) |
+        &Rexp::Many1(ref child) | &Rexp::Many0(ref child) | &Rexp::Option(ref child) |
+        &Rexp::Repeat(_, _, ref child) | &Rexp::Lazy(ref child) |
+        &Rexp::FlagGroup(_, ref child) => contains_not(&child),
+        &Rexp::Or(ref children) | &Rexp::And(ref children) | &Rexp::Seq(ref children) => {
+            children.iter().any(|c| contains_not(&c))
+        }
+        _ => false
+    }
+}
+
+/// Compiles the expression to an automaton over chars
+///
+/// Use `expand_utf8` for running the result on byte streams. Complements
+/// (`~`) are rejected since they would have to be taken over all chars.
+pub fn compile_rexp_char_nfa(rexp_src: &str) -> Result<CharVectorFSA, RexpError> {
+    compile_rexp_char_nfa_with_flags(rexp_src, Flags::default())
+}
+
+pub fn compile_rexp_char_nfa_with_flags(rexp_src: &str, flags: Flags)
+                                        -> Result<CharVectorFSA, RexpError> {
+    let ast = simplify(parse_rexp_with_flags(rexp_src, flags)?);
+    if contains_not(&ast) {
+        return Err(RexpError::Syntax("~ is not supported for char automata".to_string()));
+    }
+    let mut flags = flags;
+    Ok(ast_to_fsa_with::<CharLeaves>(&ast, &mut flags))
+}
+
+/// Parses and validates the expression
+pub fn parse_rexp(rexp_src: &str) -> Result<Rexp, RexpError> {
+    parse_rexp_with_flags(rexp_src, Flags::default())
+}
+
+/// Parses and validates the expression with the given initial flags
+///
+/// Flags given here are not recorded in the AST.
+pub fn parse_rexp_with_flags(rexp_src: &str, flags: Flags) -> Result<Rexp, RexpError> {
+    let mut leading = flags;
+    leading.apply(leading_flags(rexp_src));
+    let stripped;
+    let rexp_src = if leading.extended {
+        stripped = strip_extended(rexp_src);
+        stripped.as_str()
+    } else {
+        rexp_src
+    };
+
+    let ast = match rexp().parse(rexp_src) {
+        Ok((ast, rest)) => {
+            if ! rest.is_empty() {
+                return Err(RexpError::Syntax(format!("Unexpected input: {}", rest)));
+            }
+            ast
+        }
+        Err(what) => {
+            return Err(RexpError::Syntax(format!("{:?}", what)));
+        }
+    };
+    validate(&ast, true)?;
+    Ok(ast)
+}
+
+/// Checks the counts of `{b,e}`
+fn validate_repeat_bounds(b: usize, e: Option<usize>) -> Result<(), RexpError> {
+    if let Some(e) = e {
+        if e < b {
+            return Err(RexpError::InvalidRepeatBounds(b, e));
+        }
+    }
+    let bound = e.unwrap_or(b);
+    if bound > MAX_REPEAT {
+        return Err(RexpError::RepeatTooLarge(bound));
+    }
+    Ok(())
+}
+
+/// Checks constraints that are not expressed in the grammar
+///
+/// `leading` is true if `ast` is at the very beginning of the expression.
+fn validate(ast: &Rexp, leading: bool) -> Result<(), RexpError> {
+    match ast {
+        &Rexp::Repeat(b, e, ref child) => {
+            validate_repeat_bounds(b, e)?;
+            validate(&child, false)
+        }
+        &Rexp::SetFlags(ref spec) => {
+            if ! leading && spec.contains('x') {
+                return Err(RexpError::Syntax(
+                    "(?x) must be at the beginning of the expression".to_string()));
+            }
+            Ok(())
+        }
+        &Rexp::FlagGroup(ref spec, ref child) => {
+            if spec.contains('x') {
+                return Err(RexpError::Syntax(
+                    "(?x) must be at the beginning of the expression".to_string()));
+            }
+            validate(&child, false)
+        }
+        &Rexp::Group(ref child) | &Rexp::NamedGroup(_, ref child) |
+        &Rexp::Many1(ref child) | &Rexp::Many0(ref child) | &Rexp::Option(ref child) |
+        &Rexp::Lazy(ref child) | &Rexp::Not(ref child) => {
+            validate(&child, false)
+        }
+        &Rexp::Or(ref children) | &Rexp::And(ref children) | &Rexp::Seq(ref children) => {
+            for (i, child) in children.iter().enumerate() {
+                validate(&child, leading && i == 0)?;
+            }
+            Ok(())
+        }
+        _ => Ok(())
+    }
+}
+
+/// Builds an automaton from the AST
+///
+/// `flags` holds the flags in effect; it is updated by `(?flags)`, and the
+/// update is visible to the following siblings but not outside of the
+/// enclosing group.
+fn ast_to_fsa(ast: &Rexp, flags: &mut Flags) -> ByteVectorFSA {
+    ast_to_fsa_with::<ByteLeaves>(ast, flags)
+}
+
+/// Builds an automaton over the labels of `B` from the AST
+fn ast_to_fsa_with<B: Leaves>(ast: &Rexp, flags: &mut Flags) -> VectorFSA<boolweight, B::Label> {
+    match ast {
+        &Rexp::Char(ch) => make_char_with_flags::<B>(ch, flags),
+        &Rexp::CharSet(ref chars) => make_charset::<B>(chars, flags),
+        &Rexp::CharSetInv(ref chars) => make_charset_inv::<B>(chars, flags),
+        &Rexp::Dot => make_utf8_dot::<B>(flags.dot_all),
+        &Rexp::Group(ref child) | &Rexp::NamedGroup(_, ref child) => {
+            ast_to_fsa_with::<B>(&child, &mut flags.clone())
+        }
+        &Rexp::SetFlags(ref spec) => {
+            flags.apply(spec);
+            make_empty()
+        }
+        &Rexp::FlagGroup(ref spec, ref child) => {
+            let mut inner = flags.clone();
+            inner.apply(spec);
+            ast_to_fsa_with::<B>(&child, &mut inner)
+        }
+        &Rexp::Many1(ref child) => {
+            closure_plus(ast_to_fsa_with::<B>(&child, &mut flags.clone()))
+        }
+        &Rexp::Many0(ref child) => {
+            closure_star(ast_to_fsa_with::<B>(&child, &mut flags.clone()))
+        }
+        &Rexp::Repeat(b, e, ref child) => {
+            make_repeat(&ast_to_fsa_with::<B>(&child, &mut flags.clone()), b, e)
+        }
+        &Rexp::Lazy(ref child) => ast_to_fsa_with::<B>(&child, flags),
+        &Rexp::Option(ref child) => {
+            union(ast_to_fsa_with::<B>(&child, &mut flags.clone()), make_empty())
+        }
+        &Rexp::Or(ref children) => {
+            if children.is_empty() {
+                // the empty pattern, e.g. `""` or `()`, matches the empty string
+                make_empty()
+            } else {
+                union_all(children.iter().map(|child| {
+                    ast_to_fsa_with::<B>(&child, flags)
+                }).collect())
+            }
+        }
+        &Rexp::And(ref children) => {
+            let mut ret = rmeps(ast_to_fsa_with::<B>(&children[0], flags));
+            for child in children.iter().skip(1) {
+                ret = intersect(ret, rmeps(ast_to_fsa_with::<B>(&child, flags)));
+            }
+            ret
+        }
+        &Rexp::Not(ref child) => {
+            let dfa = determinize(rmeps(ast_to_fsa_with::<B>(&child, &mut flags.clone())));
+            let alphabet = B::alphabet().expect("Complements must be rejected for this alphabet");
+            complement(dfa, &alphabet)
+        }
+        &Rexp::Seq(ref children) => {
+            if children.len() == 0 {
+                make_empty()
+            } else if children.len() == 1 {
+                ast_to_fsa_with::<B>(children.first().unwrap(), flags)
+            } else {
+                let mut ret = concat(ast_to_fsa_with::<B>(&children[0], flags),
+                                     ast_to_fsa_with::<B>(&children[1], flags));
+                for i in 2..(children.len()) {
+                    ret = concat(ret, ast_to_fsa_with::<B>(&children[i], flags));
+                }
+                ret
+            }
+        }
+    }
+}
+
+#[test]
+pub fn rexp_parser_test() {
+    use automata::DumpTSV;
+
+    use self::Rexp::{Seq,Option,Group,Char,Dot,CharSet,Many0,Many1,Or};
+    use self::CharSetExpr::{Range};
+    use automata::{Arc};
+    use automata::arcsort::arcsort;
+    use automata::determinize::determinize;
+
+    let rexp_src = r#"(a\.)?(abc)+.*[A-Z]+|abc"#;
+    let result = rexp().parse(rexp_src);
+
+    let ast = match result {
+        Ok((ast, rest)) => {
+            let expected =
+                Or(vec!(
+                    box Seq(vec!(box Option(box Group(box Seq(vec!(box Char('a'), box Char('.'))))),
+                             box Many1(box Group(box Seq(vec!(box Char('a'), box Char('b'),
+                                                              box Char('c'))))),
+                             box Many0(box Dot),
+                             box Many1(box CharSet(vec!(Range('A', 'Z')))))
+                    ),
+                    box Seq(vec!(box Char('a'), box Char('b'), box Char('c')))
+                ));
+            assert!(rest == "");
+            assert!(ast == expected);
+            ast
+        }
+        Err(what) => {
+            println!("Parse Error: {:?}", what);
+            panic!("parse error")
+        }
+    };
+    let fsa = arcsort(determinize(rmeps(ast_to_fsa(&ast, &mut Flags::default()))),
+                      |ref x, ref y| { x.label().cmp(&y.label()) } );
+
+    let mut dump_buf = Vec::<u8>::new();
+    fsa.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+
+    println!("Rexp Parse Result[{}]\n====\n{}", rexp_src, dumped);
+}
+
+#[test]
+pub fn compile_rexp_test() {
+    use automata::{DumpTSV};
+
+    {
+        let expected = "
+0	1	97	true
+1	2	98	true
+2	3	99	true
+3	true
+".trim();
+        let fsa = rmeps(compile_rexp_nfa("abc").expect("Compile error"));
+
+        let mut dump_buf = Vec::<u8>::new();
+        fsa.dump_tsv(&mut dump_buf);
+        let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+
+        assert!(dumped.trim() == expected);
+    }
+
+    {
+        let expected = "
+0	1	97	true
+1	2	97	true
+2	3	97	true
+3	true
+".trim();
+        let fsa = rmeps(compile_rexp_nfa("a{3}").expect("Compile error"));
+
+        let mut dump_buf = Vec::<u8>::new();
+        fsa.dump_tsv(&mut dump_buf);
+        let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+
+        assert_eq!(dumped.trim(), expected);
+    }
+
+}
+
+#[test]
+pub fn repeat_spec_test() {
+    use automata::{DumpTSV};
+    use self::Rexp::{Char,Repeat,Lazy};
+
+    assert_eq!(rexp().parse("a{,2}").map(|t| t.0), Ok(Repeat(0, Some(2), box Char('a'))));
+    assert_eq!(rexp().parse("a{2,}").map(|t| t.0), Ok(Repeat(2, None, box Char('a'))));
+    assert_eq!(rexp().parse("a{2,3}?").map(|t| t.0),
+               Ok(Lazy(box Repeat(2, Some(3), box Char('a')))));
+
+    assert!(compile_rexp_nfa("a{3,2}").err() == Some(RexpError::InvalidRepeatBounds(3, 2)));
+    assert!(compile_rexp_nfa("a{99999999}").err() == Some(RexpError::RepeatTooLarge(99999999)));
+    assert!(compile_rexp_nfa("a{1000}").is_ok());
+
+    let expected = "
+0	1	97	true
+1	2	97	true
+2	true
+2	3	97	true
+3	true
+3	3	97	true
+".trim();
+    let fsa = rmeps(compile_rexp_nfa("a{2,}").expect("Compile error"));
+
+    let mut dump_buf = Vec::<u8>::new();
+    fsa.dump_tsv(&mut dump_buf);
+    let dumped = String::from_utf8(dump_buf).expect("UTF-8 error");
+
+    assert_eq!(dumped.trim(), expected);
+}
+
+/// Returns true if the whole `input` is accepted by `fsa`
+#[cfg(test)]
+fn accepts(fsa: ByteVectorFSA, input: &str) -> bool {
+    use automata::determinize::determinize;
+
+    let dfa = determinize(rmeps(fsa));
+    let mut st = dfa.init_state();
+    for b in input.bytes() {
+        match dfa.arcs(&st).find(|a| a.label() == ByteLabel::from(b)) {
+            Some(a) => { st = a.nextstate(); }
+            None => { return false; }
+        }
+    }
+    dfa.final_weight(&st)
+}
+
+#[test]
+pub fn inline_flags_test() {
+    let compile = |src: &str| compile_rexp_nfa(src).expect("Compile error");
+
+    assert!(accepts(compile("(?i)ab"), "aB"));
+    assert!(accepts(compile("(?i)ab"), "AB"));
+    assert!(! accepts(compile("(?i)ab"), "AC"));
+    assert!(accepts(compile("a(?i:b)c"), "aBc"));
+    assert!(! accepts(compile("a(?i:b)c"), "abC"));
+    assert!(! accepts(compile("a(?i)b(?-i)c"), "aBC"));
+    assert!(accepts(compile("(?i)[a-c]x"), "BX"));
+    assert!(! accepts(compile("(?i)[^a]"), "A"));
+    assert!(accepts(compile("(?i)\u{3c3}"), "\u{3a3}"));
+
+    assert!(! accepts(compile("a.b"), "a\nb"));
+    assert!(accepts(compile("(?s)a.b"), "a\nb"));
+
+    assert!(accepts(compile("(?x) a b+  # comment\n c [ ]"), "abbc "));
+    assert!(compile_rexp_nfa("a(?x)b").is_err());
+    // matching is line by line, so `m` doesn't change the language
+    assert!(accepts(compile("(?m)a"), "a"));
+    assert!(accepts(compile("a(?im:b)"), "aB"));
+    assert!(! accepts(compile("(?m)a.b"), "a\nb"));
+
+    let ignore_case = Flags { case_insensitive: true, .. Flags::default() };
+    assert!(accepts(compile_rexp_nfa_with_flags("ab", ignore_case).expect("Compile error"),
+                    "Ab"));
+}
+
+#[test]
+pub fn intersect_complement_test() {
+    use self::Rexp::{Char,And,Not,Seq,Many0,Dot};
+
+    assert_eq!(rexp().parse("~a.*&b").map(|t| t.0),
+               Ok(And(vec!(box Seq(vec!(box Not(box Char('a')), box Many0(box Dot))),
+                           box Char('b')))));
+
+    let compile = |src: &str| compile_rexp_nfa(src).expect("Compile error");
+    let foo_not_bar = ".*foo.*&~(.*bar.*)";
+    assert!(accepts(compile(foo_not_bar), "a foo"));
+    assert!(! accepts(compile(foo_not_bar), "a foo bar"));
+    assert!(! accepts(compile(foo_not_bar), "a baz"));
+    assert!(accepts(compile("a\\&b\\~"), "a&b~"));
+}
+
+#[test]
+pub fn rexp_list_test() {
+    let fsa = compile_rexp_nfa("ab|cd|e").expect("Compile error");
+    assert!(accepts(fsa.clone(), "cd"));
+    assert!(accepts(fsa.clone(), "e"));
+    assert!(! accepts(fsa.clone(), "abcd"));
+
+    let fsa = compile_rexp_list_nfa(&["foo", "fo+bar", "food", "x{2}"], Flags::default())
+        .expect("Compile error");
+    assert!(accepts(fsa.clone(), "foo"));
+    assert!(accepts(fsa.clone(), "food"));
+    assert!(accepts(fsa.clone(), "fooobar"));
+    assert!(accepts(fsa.clone(), "xx"));
+    assert!(! accepts(fsa.clone(), "fo"));
+    assert!(! accepts(fsa.clone(), "x{2}"));
+
+    assert!(compile_rexp_list_nfa(&["a", "("], Flags::default()).is_err());
+
+    // a blank line in a pattern file matches the empty string, as with `-f`
+    let ignore_case = Flags { case_insensitive: true, .. Flags::default() };
+    let fsa = compile_rexp_list_nfa(&["abc", ""], ignore_case).expect("Compile error");
+    assert!(accepts(fsa.clone(), ""));
+    assert!(accepts(fsa.clone(), "ABC"));
+    assert!(! accepts(fsa.clone(), "ab"));
+    let fsa = compile_rexp_nfa("a()b").expect("Compile error");
+    assert!(accepts(fsa.clone(), "ab"));
+}
+
+#[test]
+pub fn display_roundtrip_test() {
+    let canonical = vec![
+        "abc", "a|b*|c+?", r"(a\.)?(abc)+.*[A-Z]+|abc", "a{3}b{2,}c{0,4}?", "[]a-z][^0-9_]",
+        "(?i)a(?-i:b)(?P<tail>c)", r"\{\}\|\&\~", "~(ab)&.*c.*|d", "(?x)a\\ b\\#",
+    ];
+    for src in canonical.iter() {
+        let ast = parse_rexp(src).expect("Parse error");
+        assert_eq!(format!("{}", ast), *src);
+    }
+
+    let normalized = vec![("a{,2}", "a{0,2}"), ("a{2,2}", "a{2}"),
+                          ("(?x) a b # comment", "(?x)ab")];
+    for &(src, expected) in normalized.iter() {
+        let ast = parse_rexp(src).expect("Parse error");
+        let printed = format!("{}", ast);
+        assert_eq!(printed, expected);
+        assert_eq!(parse_rexp(&printed), Ok(ast));
+    }
+
+    let ast = Rexp::Many0(box Rexp::Seq(vec![box Rexp::Char('a'), box Rexp::Char('b')]));
+    assert_eq!(format!("{}", ast), "(?:ab)*");
+
+    // `^` and `-` from merged alternatives must stay literal
+    let sets = vec![(vec!['^', 'a'], "[a^]"), (vec!['a', '-', 'z'], "[az-]"),
+                    (vec!['-', '^'], "[-^]"), (vec![']', '-', 'b'], "[]b-]")];
+    for &(ref chars, expected) in sets.iter() {
+        let ast = Rexp::CharSet(chars.iter().map(|&c| CharSetExpr::Char(c)).collect());
+        let printed = format!("{}", ast);
+        assert_eq!(printed, expected);
+        let mut reparsed = match parse_rexp(&printed) {
+            Ok(Rexp::CharSet(exprs)) => exprs,
+            other => panic!("Unexpected parse result: {:?}", other),
+        };
+        let mut original: Vec<CharSetExpr> = chars.iter().map(|&c| CharSetExpr::Char(c)).collect();
+        reparsed.sort();
+        original.sort();
+        assert_eq!(reparsed, original);
+    }
+    assert_eq!(parse_rexp("a+|b").expect("Parse error").explain(),
+               "any of\n  one or more\n    literal 'a'\n  literal 'b'\n");
+}
+
+#[test]
+pub fn char_nfa_test() {
+    use automata::utf8::expand_utf8;
+
+    let accepts_chars = |fsa: CharVectorFSA, input: &str| -> bool {
+        let dfa = determinize(rmeps(fsa));
+        let mut st = dfa.init_state();
+        for ch in input.chars() {
+            match dfa.arcs(&st).find(|a| a.label() == CharLabel::from(ch)) {
+                Some(a) => { st = a.nextstate(); }
+                None => { return false; }
+            }
+        }
+        dfa.final_weight(&st)
+    };
+
+    let fsa = compile_rexp_char_nfa("(é|[α-γ])+x{2}").expect("Compile error");
+    // each char is a single arc
+    assert_eq!(rmeps(compile_rexp_char_nfa("é").expect("Compile error")).nstates(), Some(2));
+    assert!(accepts_chars(fsa.clone(), "éβxx"));
+    assert!(! accepts_chars(fsa.clone(), "δxx"));
+    assert!(accepts(expand_utf8(&fsa), "γéxx"));
+    assert!(! accepts(expand_utf8(&fsa), "éx"));
+
+    let fsa = compile_rexp_char_nfa("(?i)aΩ.&.ωc").expect("Compile error");
+    assert!(accepts_chars(fsa.clone(), "aωc"));
+    assert!(! accepts_chars(fsa.clone(), "abc"));
+    assert!(compile_rexp_char_nfa("a~(b)").is_err());
+}
@@ -0,0 +1,50 @@
+use automata::{StateMachine,MutableStateMachine,Arc,SimpleArc,PatternSet};
+use automata::vector::{VectorFSA,ByteVectorFSA,ByteLabel};
+use automata::union::union_all;
+use automata::trie::trie;
+use rexp::{Flags,RexpError,compile_rexp_nfa_with_flags,as_literal};
+
+/// Automaton whose final weights are sets of accepted pattern ids
+pub type PatternSetFSA = VectorFSA<PatternSet, ByteLabel>;
+
+/// Converts an unweighted automaton to the one accepting the pattern `id`
+pub fn with_pattern_id(m: &ByteVectorFSA, id: usize) -> PatternSetFSA {
+    let mut ret = PatternSetFSA::new();
+    for _ in m.states() {
+        ret.add_new_state();
+    }
+    for st in m.states() {
+        if m.final_weight(&st) {
+            ret.set_final_weight(&st, PatternSet::singleton(id));
+        }
+        for arc in m.arcs(&st) {
+            ret.add_arc(&st, SimpleArc::new(arc.label(), PatternSet::All, arc.nextstate()));
+        }
+    }
+    ret.copy_symbols(m);
+    ret
+}
+
+/// Compiles patterns into a single NFA, where pattern ids are the indices in
+/// `rexp_srcs`
+///
+/// As in `compile_rexp_list_nfa`, plain literals are gathered into a trie. On
+/// error, the index of the first invalid pattern is returned with the cause.
+pub fn compile_rexp_set_nfa<S: AsRef<str>>(rexp_srcs: &[S], flags: Flags)
+                                           -> Result<PatternSetFSA, (usize, RexpError)> {
+    let mut literals = Vec::new();
+    let mut machines = Vec::new();
+    for (id, src) in rexp_srcs.iter().enumerate() {
+        match as_literal(src.as_ref(), &flags) {
+            Some(word) => literals.push((word, PatternSet::singleton(id))),
+            None => {
+                let nfa = compile_rexp_nfa_with_flags(src.as_ref(), flags).map_err(|e| (id, e))?;
+                machines.push(with_pattern_id(&nfa, id));
+            }
+        }
+    }
+    if ! literals.is_empty() {
+        machines.push(trie(literals));
+    }
+    Ok(union_all(machines))
+}
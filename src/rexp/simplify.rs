@@ -0,0 +1,261 @@
+use rexp::{Rexp,CharSetExpr};
+
+use std::collections::{BTreeMap,BTreeSet};
+
+#[allow(unused_imports)]
+use test::Bencher;
+
+/// Returns true if `(?flags)` in the expression may affect the following
+/// siblings, i.e. it is not enclosed by a group or a quantifier
+fn leaks_flags(ast: &Rexp) -> bool {
+    match ast {
+        &Rexp::SetFlags(_) => true,
+        &Rexp::Seq(ref children) | &Rexp::Or(ref children) | &Rexp::And(ref children) => {
+            children.iter().any(|c| leaks_flags(c))
+        }
+        _ => false
+    }
+}
+
+fn unbox(children: Vec<Box<Rexp>>) -> Vec<Rexp> {
+    children.into_iter().map(|c| *c).collect()
+}
+
+/// Returns the elements of the expression as a sequence
+fn seq_items(ast: Rexp) -> Vec<Rexp> {
+    match ast {
+        Rexp::Seq(children) => unbox(children),
+        other => vec![other]
+    }
+}
+
+fn make_seq(mut items: Vec<Rexp>) -> Rexp {
+    if items.len() == 1 {
+        items.pop().unwrap()
+    } else {
+        Rexp::Seq(items.into_iter().map(|x| box x).collect())
+    }
+}
+
+fn make_or(mut alts: Vec<Rexp>) -> Rexp {
+    if alts.len() == 1 {
+        alts.pop().unwrap()
+    } else {
+        Rexp::Or(alts.into_iter().map(|x| box x).collect())
+    }
+}
+
+/// Builds `child{b,e}` with collapsing nested quantifiers
+fn make_quantified(b: usize, e: Option<usize>, child: Rexp) -> Rexp {
+    match (b, e) {
+        (1, Some(1)) => child,
+        (0, Some(1)) => {
+            match child {
+                Rexp::Many0(x) | Rexp::Many1(x) => Rexp::Many0(x),
+                Rexp::Option(x) => Rexp::Option(x),
+                other => Rexp::Option(box other)
+            }
+        }
+        (0, None) => {
+            match child {
+                Rexp::Many0(x) | Rexp::Many1(x) | Rexp::Option(x) => Rexp::Many0(x),
+                other => Rexp::Many0(box other)
+            }
+        }
+        (1, None) => {
+            match child {
+                Rexp::Many0(x) | Rexp::Option(x) => Rexp::Many0(x),
+                Rexp::Many1(x) => Rexp::Many1(x),
+                other => Rexp::Many1(box other)
+            }
+        }
+        (b, e) => Rexp::Repeat(b, e, box child)
+    }
+}
+
+/// Simplifies alternatives that don't contain `(?flags)`
+fn simplify_or(alts: Vec<Rexp>) -> Rexp {
+    // flatten and remove duplicates
+    //
+    // Sets and maps keyed by subexpressions are used for lookups, since word
+    // lists can have many thousands of alternatives.
+    let mut flat: Vec<Rexp> = Vec::new();
+    let mut seen: BTreeSet<Rexp> = BTreeSet::new();
+    for alt in alts.into_iter() {
+        let alt_items = match alt {
+            // an empty alternation is the empty string, not a missing alternative
+            Rexp::Or(children) => {
+                if children.is_empty() {
+                    vec![Rexp::Or(children)]
+                } else {
+                    unbox(children)
+                }
+            }
+            other => vec![other]
+        };
+        for a in alt_items.into_iter() {
+            if seen.insert(a.clone()) {
+                flat.push(a);
+            }
+        }
+    }
+
+    // factor out common prefixes, keeping the order of the first occurrences
+    let mut groups: Vec<Vec<Vec<Rexp>>> = Vec::new();
+    let mut by_first: BTreeMap<Rexp, usize> = BTreeMap::new();
+    for alt in flat.into_iter() {
+        let items = seq_items(alt);
+        let pos = items.first().and_then(|first| by_first.get(first).cloned());
+        match pos {
+            Some(pos) => groups[pos].push(items),
+            None => {
+                if let Some(first) = items.first() {
+                    by_first.insert(first.clone(), groups.len());
+                }
+                groups.push(vec![items]);
+            }
+        }
+    }
+
+    let mut factored = Vec::new();
+    for mut members in groups.into_iter() {
+        if members.len() == 1 {
+            factored.push(make_seq(members.pop().unwrap()));
+            continue;
+        }
+        let mut plen = 0;
+        while members.iter().all(|m| plen < m.len() && m[plen] == members[0][plen]) {
+            plen += 1;
+        }
+        let mut prefix: Vec<Rexp> = members[0][..plen].to_vec();
+        let has_empty = members.iter().any(|m| m.len() == plen);
+        let rests: Vec<Rexp> = members.into_iter()
+            .filter(|m| m.len() > plen)
+            .map(|mut m| make_seq(m.split_off(plen)))
+            .collect();
+        if ! rests.is_empty() {
+            let mut rest = simplify_or(rests);
+            if has_empty {
+                rest = make_quantified(0, Some(1), rest);
+            }
+            prefix.extend(seq_items(rest));
+        }
+        factored.push(make_seq(prefix));
+    }
+
+    // merge single characters into a charset
+    let mut merged = Vec::new();
+    let mut charset: Option<(usize, Vec<CharSetExpr>)> = None;
+    let mut ncharalts = 0;
+    for alt in factored.into_iter() {
+        let exprs = match alt {
+            Rexp::Char(ch) => vec![CharSetExpr::Char(ch)],
+            Rexp::CharSet(exprs) => exprs,
+            other => {
+                merged.push(other);
+                continue;
+            }
+        };
+        ncharalts += 1;
+        if let Some((_, ref mut acc)) = charset {
+            acc.extend(exprs);
+            continue;
+        }
+        charset = Some((merged.len(), exprs));
+    }
+    if let Some((pos, exprs)) = charset {
+        let node = match (ncharalts, exprs.first()) {
+            (1, Some(&CharSetExpr::Char(ch))) if exprs.len() == 1 => Rexp::Char(ch),
+            _ => Rexp::CharSet(exprs),
+        };
+        merged.insert(pos, node);
+    }
+
+    make_or(merged)
+}
+
+/// Rewrites the expression into a smaller one accepting the same language
+///
+/// Capturing groups and lazy quantifiers are dropped, so the result is only
+/// for building automata. Alternatives and sequences containing `(?flags)`
+/// are left as they are since their scopes depend on the structure.
+pub fn simplify(ast: Rexp) -> Rexp {
+    match ast {
+        Rexp::Group(child) | Rexp::NamedGroup(_, child) => {
+            let child = simplify(*child);
+            if leaks_flags(&child) {
+                Rexp::Group(box child)
+            } else {
+                child
+            }
+        }
+        Rexp::FlagGroup(spec, child) => {
+            let child = simplify(*child);
+            if spec.is_empty() && ! leaks_flags(&child) {
+                child
+            } else {
+                Rexp::FlagGroup(spec, box child)
+            }
+        }
+        Rexp::Lazy(child) => simplify(*child),
+        Rexp::Many0(child) => make_quantified(0, None, simplify(*child)),
+        Rexp::Many1(child) => make_quantified(1, None, simplify(*child)),
+        Rexp::Option(child) => make_quantified(0, Some(1), simplify(*child)),
+        Rexp::Repeat(b, e, child) => make_quantified(b, e, simplify(*child)),
+        Rexp::Not(child) => Rexp::Not(box simplify(*child)),
+        Rexp::And(children) => {
+            Rexp::And(children.into_iter().map(|c| box simplify(*c)).collect())
+        }
+        Rexp::Seq(children) => {
+            let children: Vec<Rexp> = children.into_iter().map(|c| simplify(*c)).collect();
+            if children.iter().any(leaks_flags) {
+                return Rexp::Seq(children.into_iter().map(|x| box x).collect());
+            }
+            let mut items = Vec::new();
+            for child in children.into_iter() {
+                items.extend(seq_items(child));
+            }
+            make_seq(items)
+        }
+        Rexp::Or(children) => {
+            let children: Vec<Rexp> = children.into_iter().map(|c| simplify(*c)).collect();
+            if children.len() < 2 || children.iter().any(leaks_flags) {
+                return Rexp::Or(children.into_iter().map(|x| box x).collect());
+            }
+            simplify_or(children)
+        }
+        other => other
+    }
+}
+
+#[test]
+pub fn simplify_test() {
+    use rexp::parse_rexp;
+
+    let simplified = |src: &str| -> String {
+        format!("{}", simplify(parse_rexp(src).expect("Parse error")))
+    };
+
+    assert_eq!(simplified("abc|abd|ab"), "ab[cd]?");
+    assert_eq!(simplified("((a*)*)b{1}"), "a*b");
+    assert_eq!(simplified("x{0,1}y{1,}?"), "x?y+");
+    assert_eq!(simplified("a|b|[cd]|e+|a"), "[abcd]|e+");
+    assert_eq!(simplified("(a|(b|c))d"), "[abc]d");
+    assert_eq!(simplified(r"\^|a"), "[a^]");
+    assert_eq!(simplified("a|-|z"), "[az-]");
+    assert_eq!(simplified("(foo|bar)+|foo"), "(?:foo|bar)+|foo");
+    assert_eq!(simplified("(?i)a|b"), "(?i)a|b");
+    assert_eq!(simplified("a((?i)b)c"), "a((?i)b)c");
+}
+
+#[bench]
+pub fn simplify_word_list_bench(b: &mut Bencher) {
+    use rexp::parse_rexp;
+
+    // a word list with shared prefixes, as in dictionaries
+    let words: Vec<String> = (0..5000).map(|i| format!("w{}x{}", i % 97, i)).collect();
+    let ast = parse_rexp(&words.join("|")).expect("Parse error");
+    b.iter(|| {
+        simplify(ast.clone())
+    })
+}
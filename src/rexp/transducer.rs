@@ -0,0 +1,348 @@
+use automata::{StateMachine,MutableStateMachine,Arc,SimpleArc,Semiring,Label,boolweight};
+use automata::vector::{ByteVectorFSA,ByteVectorFST,ByteLabel};
+use automata::concat::concat;
+use automata::union::union_all;
+use automata::closure::{closure_plus,closure_star};
+use rexp::{Rexp,Flags,RexpError,RepeatSpec,ast_to_fsa,charset_expr,repeat_spec,make_repeat,
+           validate_repeat_bounds};
+
+use combine::{many,many1,sep_by,Stream,one_of,none_of,try,optional,Parser};
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet,BinaryHeap};
+use combine::parser::char::char;
+
+const TRANSDUCER_META_CHARS: &str = ".\\*+?^$()[]|&~:\"{}";
+const TRANSDUCER_ESCAPABLE_CHARS: &str = ".\\*+?^$()[]|&~:\" #{}";
+
+/// AST of transducer expressions
+///
+/// `:` binds tighter than concatenation and looser than quantifiers, so
+/// `a+:b` maps `a+` to `b`. Each side of `:` must be an acceptor, i.e. an
+/// expression without `:`, and may be parenthesized. Without `:`, an acceptor
+/// maps strings to themselves.
+///
+/// Acceptors are made of literals, `"..."`, `.`, `[...]` and groups with
+/// `?`, `*`, `+` and `{n,m}`. `&`, `~` and inline flags are not supported.
+#[derive(Clone,Debug,PartialEq)]
+pub enum TransducerRexp {
+    /// `x:y`, pairs of strings accepted by the input and the output sides
+    Cross(Box<TransducerRexp>, Box<TransducerRexp>),
+    Identity(Rexp),
+    Group(Box<TransducerRexp>),
+    Many1(Box<TransducerRexp>),
+    Many0(Box<TransducerRexp>),
+    Option(Box<TransducerRexp>),
+    Repeat(usize, Option<usize>, Box<TransducerRexp>),
+    Or(Vec<Box<TransducerRexp>>),
+    Seq(Vec<Box<TransducerRexp>>),
+}
+
+parser! {
+    fn acceptor[I]()(I) -> Rexp where [I: Stream<Item=char>] {
+        let escaped_char = || {
+            (char('\\'), one_of(TRANSDUCER_ESCAPABLE_CHARS.chars())).map(|x| x.1)
+        };
+        let quoted = (char('"'), many(escaped_char().or(none_of("\"\\".chars()))), char('"'))
+            .map(|t: (char, String, char)| {
+                Rexp::Seq(t.1.chars().map(|c| box Rexp::Char(c)).collect())
+            });
+        let charset_inv = (char('['), char('^'), charset_expr(), char(']')).map(
+            |t| Rexp::CharSetInv(t.2)
+        );
+        let charset = (char('['), charset_expr(), char(']')).map(|t| Rexp::CharSet(t.1));
+        let lit_char = escaped_char().or(none_of(TRANSDUCER_META_CHARS.chars()))
+            .map(|c| Rexp::Char(c));
+        quoted
+            .or(try(charset_inv))
+            .or(try(charset))
+            .or(char('.').map(|_| Rexp::Dot))
+            .or(lit_char)
+    }
+}
+
+parser! {
+    fn transducer_term[I]()(I) -> TransducerRexp where [I: Stream<Item=char>] {
+        let group = (char('('), transducer_rexp(), char(')'))
+            .map(|t| TransducerRexp::Group(box t.1));
+        group.or(acceptor().map(TransducerRexp::Identity))
+    }
+}
+
+parser! {
+    fn transducer_repeat[I]()(I) -> TransducerRexp where [I: Stream<Item=char>] {
+        (transducer_term(), optional(repeat_spec())).map(|(t, spec)| {
+            match spec {
+                Some(RepeatSpec::Option) => TransducerRexp::Option(box t),
+                Some(RepeatSpec::Many0) => TransducerRexp::Many0(box t),
+                Some(RepeatSpec::Many1) => TransducerRexp::Many1(box t),
+                Some(RepeatSpec::Repeat(b, e)) => TransducerRexp::Repeat(b, e, box t),
+                None => t,
+            }
+        })
+    }
+}
+
+parser! {
+    fn transducer_rexp[I]()(I) -> TransducerRexp where [I: Stream<Item=char>] {
+        let cross = (transducer_repeat(), optional((char(':'), transducer_repeat())))
+            .map(|(input, output)| {
+                match output {
+                    Some((_, output)) => TransducerRexp::Cross(box input, box output),
+                    None => input,
+                }
+            });
+
+        let seq = many1(cross).map(|mut v: Vec<TransducerRexp>| {
+            if v.len() == 1 {
+                v.pop().unwrap()
+            } else {
+                TransducerRexp::Seq(v.into_iter().map(|x| box x).collect())
+            }
+        });
+
+        sep_by(seq, char('|')).map(|mut v: Vec<TransducerRexp>| {
+            if v.len() == 1 {
+                v.pop().unwrap()
+            } else {
+                TransducerRexp::Or(v.into_iter().map(|x| box x).collect())
+            }
+        })
+    }
+}
+
+/// Returns the acceptor expression, or `None` if `ast` contains `:`
+fn to_acceptor(ast: &TransducerRexp) -> Option<Rexp> {
+    let children = |cs: &Vec<Box<TransducerRexp>>| -> Option<Vec<Box<Rexp>>> {
+        cs.iter().map(|c| to_acceptor(&c).map(|r| box r)).collect()
+    };
+    Some(match ast {
+        &TransducerRexp::Cross(_, _) => { return None; }
+        &TransducerRexp::Identity(ref acceptor) => acceptor.clone(),
+        &TransducerRexp::Group(ref child) => Rexp::Group(box to_acceptor(&child)?),
+        &TransducerRexp::Many1(ref child) => Rexp::Many1(box to_acceptor(&child)?),
+        &TransducerRexp::Many0(ref child) => Rexp::Many0(box to_acceptor(&child)?),
+        &TransducerRexp::Option(ref child) => Rexp::Option(box to_acceptor(&child)?),
+        &TransducerRexp::Repeat(b, e, ref child) => Rexp::Repeat(b, e, box to_acceptor(&child)?),
+        &TransducerRexp::Or(ref cs) => Rexp::Or(children(cs)?),
+        &TransducerRexp::Seq(ref cs) => Rexp::Seq(children(cs)?),
+    })
+}
+
+/// Checks that both sides of every `:` are acceptors and counts of `{n,m}`
+fn validate(ast: &TransducerRexp) -> Result<(), RexpError> {
+    match ast {
+        &TransducerRexp::Cross(ref input, ref output) => {
+            if to_acceptor(&input).is_none() || to_acceptor(&output).is_none() {
+                return Err(RexpError::Syntax("Both sides of ':' must not contain ':'".to_string()));
+            }
+            Ok(())
+        }
+        &TransducerRexp::Identity(_) => Ok(()),
+        &TransducerRexp::Group(ref child) | &TransducerRexp::Many1(ref child) |
+        &TransducerRexp::Many0(ref child) | &TransducerRexp::Option(ref child) => {
+            validate(&child)
+        }
+        &TransducerRexp::Repeat(b, e, ref child) => {
+            validate_repeat_bounds(b, e)?;
+            validate(&child)
+        }
+        &TransducerRexp::Or(ref children) | &TransducerRexp::Seq(ref children) => {
+            for child in children.iter() {
+                validate(&child)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Converts an acceptor to a transducer by mapping non-epsilon labels
+fn map_labels<F>(m: &ByteVectorFSA, f: F) -> ByteVectorFST
+    where F: Fn(ByteLabel) -> (ByteLabel, ByteLabel) {
+    let mut ret = ByteVectorFST::new();
+    for _ in m.states() {
+        ret.add_new_state();
+    }
+    for st in m.states() {
+        ret.set_final_weight(&st, m.final_weight(&st));
+        for arc in m.arcs(&st) {
+            let l = if arc.label() == ByteLabel::epsilon() {
+                <(ByteLabel, ByteLabel)>::epsilon()
+            } else {
+                f(arc.label())
+            };
+            ret.add_arc(&st, SimpleArc::new(l, arc.weight(), arc.nextstate()));
+        }
+    }
+    ret
+}
+
+fn make_empty_fst() -> ByteVectorFST {
+    let mut fst = ByteVectorFST::new();
+    let init = fst.add_new_state();
+    fst.set_final_weight(&init, boolweight::one());
+    fst
+}
+
+fn transducer_to_fst(ast: &TransducerRexp, flags: &Flags) -> ByteVectorFST {
+    match ast {
+        &TransducerRexp::Cross(ref input, ref output) => {
+            // reads the input first, and then writes the output
+            let input = to_acceptor(&input).expect("Input side must be an acceptor");
+            let output = to_acceptor(&output).expect("Output side must be an acceptor");
+            let input = map_labels(&ast_to_fsa(&input, &mut flags.clone()),
+                                   |l| (l, ByteLabel::epsilon()));
+            let output = map_labels(&ast_to_fsa(&output, &mut flags.clone()),
+                                    |l| (ByteLabel::epsilon(), l));
+            concat(input, output)
+        }
+        &TransducerRexp::Identity(ref acceptor) => {
+            map_labels(&ast_to_fsa(acceptor, &mut flags.clone()), |l| (l, l))
+        }
+        &TransducerRexp::Group(ref child) => transducer_to_fst(&child, flags),
+        &TransducerRexp::Many1(ref child) => closure_plus(transducer_to_fst(&child, flags)),
+        &TransducerRexp::Many0(ref child) => closure_star(transducer_to_fst(&child, flags)),
+        &TransducerRexp::Option(ref child) => {
+            union_all(vec![transducer_to_fst(&child, flags), make_empty_fst()])
+        }
+        &TransducerRexp::Repeat(b, e, ref child) => {
+            make_repeat(&transducer_to_fst(&child, flags), b, e)
+        }
+        &TransducerRexp::Or(ref children) => {
+            if children.is_empty() {
+                // the empty expression as in `()`, mapping the empty string to itself
+                make_empty_fst()
+            } else {
+                union_all(children.iter().map(|c| transducer_to_fst(&c, flags)).collect())
+            }
+        }
+        &TransducerRexp::Seq(ref children) => {
+            let mut ret = make_empty_fst();
+            for child in children.iter() {
+                ret = concat(ret, transducer_to_fst(&child, flags));
+            }
+            ret
+        }
+    }
+}
+
+pub fn parse_transducer_rexp(rexp_src: &str) -> Result<TransducerRexp, RexpError> {
+    match transducer_rexp().parse(rexp_src) {
+        Ok((ast, rest)) => {
+            if ! rest.is_empty() {
+                return Err(RexpError::Syntax(format!("Unexpected input: {}", rest)));
+            }
+            validate(&ast)?;
+            Ok(ast)
+        }
+        Err(what) => Err(RexpError::Syntax(format!("{:?}", what)))
+    }
+}
+
+pub fn compile_rexp_fst(rexp_src: &str) -> Result<ByteVectorFST, RexpError> {
+    compile_rexp_fst_with_flags(rexp_src, Flags::default())
+}
+
+/// Compiles a transducer expression such as `"abc":"xyz"|[0-9]:""`
+pub fn compile_rexp_fst_with_flags(rexp_src: &str, flags: Flags)
+                                   -> Result<ByteVectorFST, RexpError> {
+    let ast = parse_transducer_rexp(rexp_src)?;
+    Ok(transducer_to_fst(&ast, &flags))
+}
+
+/// Returns the shortest output of the transducer for the whole `input`
+///
+/// Among equally short outputs, the lexicographically smallest one is
+/// returned. Returns `None` if the transducer doesn't accept `input`.
+pub fn rewrite(fst: &ByteVectorFST, input: &[u8]) -> Option<Vec<u8>> {
+    // Outputs only grow along paths and keep their order when extended by the
+    // same byte, so the first output reaching a state at a position is the
+    // best one there, and input-epsilon cycles are never taken twice.
+    let mut settled = BTreeSet::new();
+    let mut queue = BinaryHeap::new();
+    queue.push(Reverse((0, Vec::new(), fst.init_state(), 0)));
+    while let Some(Reverse((_, out, st, pos))) = queue.pop() {
+        if ! settled.insert((st, pos)) {
+            continue;
+        }
+        if pos == input.len() && fst.final_weight(&st) {
+            return Some(out);
+        }
+        for arc in fst.arcs(&st) {
+            let (i, o) = arc.label();
+            let next_pos = match i.to_byte() {
+                None => pos,
+                Some(b) if pos < input.len() && input[pos] == b => pos + 1,
+                Some(_) => continue,
+            };
+            if settled.contains(&(arc.nextstate(), next_pos)) {
+                continue;
+            }
+            let mut next_out = out.clone();
+            next_out.extend(o.to_byte());
+            queue.push(Reverse((next_out.len(), next_out, arc.nextstate(), next_pos)));
+        }
+    }
+    None
+}
+
+#[test]
+pub fn transducer_test() {
+    use automata::rmeps::rmeps;
+    use self::TransducerRexp::{Cross,Identity,Many0};
+
+    let transduce = |fst: &ByteVectorFST, input: &str| -> Option<String> {
+        rewrite(&rmeps(fst.clone()), input.as_bytes())
+            .map(|o| String::from_utf8(o).expect("UTF-8 error"))
+    };
+
+    assert_eq!(parse_transducer_rexp(r#""ab":x"#),
+               Ok(Cross(box Identity(Rexp::Seq(vec![box Rexp::Char('a'), box Rexp::Char('b')])),
+                        box Identity(Rexp::Char('x')))));
+    assert_eq!(parse_transducer_rexp("(a)*"),
+               Ok(Many0(box TransducerRexp::Group(box Identity(Rexp::Char('a'))))));
+
+    let fst = compile_rexp_fst(r#""abc":"xyz"|[0-9]:""|a:b"#).expect("Compile error");
+    assert_eq!(transduce(&fst, "abc"), Some("xyz".to_string()));
+    assert_eq!(transduce(&fst, "7"), Some("".to_string()));
+    assert_eq!(transduce(&fst, "a"), Some("b".to_string()));
+    assert_eq!(transduce(&fst, "ab"), None);
+
+    // deletes digits and keeps the others
+    let fst = compile_rexp_fst(r#"([0-9]:""|[^0-9])*"#).expect("Compile error");
+    assert_eq!(transduce(&fst, "a1b22"), Some("ab".to_string()));
+    assert!(compile_rexp_fst("a:").is_err());
+
+    // sides of ':' can be any acceptors
+    let fst = compile_rexp_fst(r#"(ab|c)+:x|"z"?:y"#).expect("Compile error");
+    assert_eq!(transduce(&fst, "abcab"), Some("x".to_string()));
+    assert_eq!(transduce(&fst, ""), Some("y".to_string()));
+    assert_eq!(transduce(&fst, "z"), Some("y".to_string()));
+    assert!(compile_rexp_fst("(a:b):c").is_err());
+
+    // input-epsilon cycles don't make the search diverge
+    let fst = compile_rexp_fst(r#"("":a)*b"#).expect("Compile error");
+    assert_eq!(transduce(&fst, "b"), Some("b".to_string()));
+
+    // only the shortest of exponentially many outputs is searched for
+    let fst = compile_rexp_fst(r#"([0-9]+:"N"|[^0-9])*"#).expect("Compile error");
+    let digits: String = (0..200).map(|i| if i % 50 == 0 { 'x' } else { '7' }).collect();
+    assert_eq!(transduce(&fst, &digits), Some("xNxNxNxN".to_string()));
+    assert_eq!(transduce(&fst, "a12b3"), Some("aNbN".to_string()));
+    // ties are broken lexicographically
+    let fst = compile_rexp_fst("a:b|a:c").expect("Compile error");
+    assert_eq!(transduce(&fst, "a"), Some("b".to_string()));
+
+    // quantifiers with counts, and braces are not literals
+    let fst = compile_rexp_fst("a{2}:b|[0-9]{1,3}:N").expect("Compile error");
+    assert_eq!(transduce(&fst, "aa"), Some("b".to_string()));
+    assert_eq!(transduce(&fst, "a{2}"), None);
+    assert_eq!(transduce(&fst, "123"), Some("N".to_string()));
+    assert_eq!(transduce(&fst, "1234"), None);
+    assert_eq!(compile_rexp_fst("a{3,2}:b").err(), Some(RexpError::InvalidRepeatBounds(3, 2)));
+    assert!(compile_rexp_fst("a}:b").is_err());
+    assert!(compile_rexp_fst("(?i)a:b").is_err());
+    assert!(compile_rexp_fst("~a:b").is_err());
+    let fst = compile_rexp_fst(r"\{:\}").expect("Compile error");
+    assert_eq!(transduce(&fst, "{"), Some("}".to_string()));
+}
@@ -0,0 +1,145 @@
+#[cfg(feature = "jit")]
+use automata::vector::ByteVectorFSA;
+#[cfg(feature = "jit")]
+use runner::jit::CompileContext;
+
+#[cfg(feature = "jit")]
+use utils::llvm::{create_module,get_default_target_triple,TargetMachine};
+
+#[cfg(feature = "jit")]
+use std::ffi::CString;
+use std::io::{self,Write};
+
+/// Returns true if `name` can be used as a C identifier
+pub fn is_c_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(ch) if ch == '_' || ch.is_ascii_alphabetic() => {}
+        _ => { return false; }
+    }
+    chars.all(|ch| ch == '_' || ch.is_ascii_alphanumeric())
+}
+
+/// Compiles the DFA into an object file defining a function named `symbol`
+///
+/// The function has the same signature as the one used by the JIT runner;
+/// see `c_header` for the C declaration. The code is position-independent,
+/// and doesn't refer to any external symbols. This requires the `jit` feature.
+#[cfg(feature = "jit")]
+pub fn compile_object(optfsa: ByteVectorFSA, symbol: &str, target_triple: Option<String>)
+                      -> Result<Vec<u8>, String> {
+    if ! is_c_identifier(symbol) {
+        return Err(format!("Invalid symbol name: {}", symbol));
+    }
+    let triple = match target_triple {
+        Some(ref t) => CString::new(t.as_str()).map_err(|_| "Invalid target".to_string())?,
+        None => get_default_target_triple()
+    };
+    let (module, _) = create_module("rustre_aot", target_triple.clone());
+    let machine = TargetMachine::new_pic(triple.as_ptr())?;
+
+    let ctx = CompileContext::new_with_name(module, optfsa, symbol);
+    ctx.optimize_ir(&machine);
+    ctx.emit_object(&machine)
+}
+
+fn ar_member_header(name: &str, size: usize) -> String {
+    format!("{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", name, 0, 0, 0, 644, size)
+}
+
+/// Writes a static library containing one object file
+///
+/// The archive is in the GNU format with a symbol index listing `symbols`,
+/// so that linkers accept it without running `ranlib`. `member` must be
+/// shorter than 16 bytes.
+pub fn write_static_library(obj: &[u8], member: &str, symbols: &[&str], dest: &mut Write)
+                            -> io::Result<()> {
+    assert!(member.len() < 16 && ! member.contains('/'), "Member name must be short");
+
+    let symtab_size = 4 + 4 * symbols.len() +
+        symbols.iter().map(|s| s.len() + 1).sum::<usize>();
+    let obj_offset = 8 + 60 + symtab_size + symtab_size % 2;
+
+    let mut buf = Vec::new();
+    buf.extend_from_slice(b"!<arch>\n");
+    buf.extend_from_slice(ar_member_header("/", symtab_size).as_bytes());
+    buf.extend((0..4).rev().map(|i| (symbols.len() >> (8 * i)) as u8));
+    for _ in symbols.iter() {
+        buf.extend((0..4).rev().map(|i| (obj_offset >> (8 * i)) as u8));
+    }
+    for sym in symbols.iter() {
+        buf.extend_from_slice(sym.as_bytes());
+        buf.push(0);
+    }
+    if buf.len() % 2 != 0 {
+        buf.push(b'\n');
+    }
+    buf.extend_from_slice(ar_member_header(&format!("{}/", member), obj.len()).as_bytes());
+    buf.extend_from_slice(obj);
+    if buf.len() % 2 != 0 {
+        buf.push(b'\n');
+    }
+    dest.write_all(&buf)
+}
+
+/// Returns a C header declaring the function compiled by `compile_object`
+pub fn c_header(symbol: &str, exprs: &[String]) -> String {
+    let upper = symbol.to_uppercase();
+    let mut ret = String::new();
+    ret.push_str("/* Generated by rustre aot; do not edit.\n *\n * Expressions:\n");
+    for expr in exprs.iter() {
+        ret.push_str(&format!(" *   {}\n", expr.replace("*/", "*\\/")));
+    }
+    ret.push_str(" */\n");
+    ret.push_str(&format!("#ifndef {0}_H\n#define {0}_H\n\n#include <stdint.h>\n\n", upper));
+    ret.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+    ret.push_str(&format!("/* State to start matching from the beginning of input */
+#define {0}_INITIAL_STATE UINT64_C(0)
+
+/* Set in the output when the state is final, i.e. a match ends at the byte */
+#define {0}_FINAL_BIT UINT64_C(0x8000000000000000)
+
+/*
+ * Runs the DFA over `len` bytes of `buf` from the state `start`.
+ *
+ * For each byte, `out` receives the state after reading the byte, with
+ * {0}_FINAL_BIT set if the state is final. Since any substring is matched,
+ * a line contains a match if the bit is set for any byte of the line. Returns
+ * the state after the last byte, which can be passed as `start` for the next
+ * chunk.
+ *
+ * `len` must be a positive multiple of 8, and both `buf` and `out` must be
+ * aligned to 64 bytes.
+ */
+uint64_t {1}(uint64_t start, uint64_t len, const uint8_t *buf, uint64_t *out);
+", upper, symbol));
+    ret.push_str(&format!("\n#ifdef __cplusplus\n}}\n#endif\n\n#endif /* {}_H */\n", upper));
+    ret
+}
+
+#[test]
+pub fn aot_test() {
+    assert!(is_c_identifier("rustre_run"));
+    assert!(is_c_identifier("_x1"));
+    assert!(! is_c_identifier("1x"));
+    assert!(! is_c_identifier("a-b"));
+    assert!(! is_c_identifier(""));
+
+    let mut buf = Vec::<u8>::new();
+    write_static_library(b"OBJ", "rustre.o", &["rustre_run"], &mut buf).expect("Write failed");
+    assert_eq!(&buf[0..8], b"!<arch>\n");
+    assert_eq!(&buf[8..24], b"/               ");
+    // the symbol index has a count, an offset and a name, padded to 2 bytes
+    assert_eq!(&buf[66..68], b"`\n");
+    assert_eq!(&buf[68..76], &[0, 0, 0, 1, 0, 0, 0, 0x58]);
+    assert_eq!(&buf[76..88], b"rustre_run\0\n");
+    assert_eq!(&buf[88..104], b"rustre.o/       ");
+    assert_eq!(&buf[136..148], b"3         `\n");
+    assert_eq!(&buf[148..], b"OBJ\n");
+
+    let header = c_header("my_run", &vec!["a*/b".to_string()]);
+    assert!(header.contains(" *   a*\\/b\n"));
+    assert!(header.contains("#ifndef MY_RUN_H\n"));
+    assert!(header.contains(
+        "uint64_t my_run(uint64_t start, uint64_t len, const uint8_t *buf, uint64_t *out);\n"));
+}
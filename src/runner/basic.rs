@@ -1,51 +1,51 @@
 use runner::{optimize_fsa,Runner};
+use runner::matcher::{Matcher,print_matching_lines};
 use automata::{StateMachine,Arc,boolweight,FSA};
-use automata::vector::VectorFSA;
+use automata::vector::{VectorFSA,ByteLabel};
 
-use std::io::{Read, BufReader,BufRead};
+use std::io::Read;
 
 pub struct BasicFSARunner {
-    fsa: VectorFSA<boolweight, u8>,
+    fsa: VectorFSA<boolweight, ByteLabel>,
 }
 
 impl BasicFSARunner {
-    pub fn new<M: FSA<Weight=boolweight, Label=u8>>(m: M) -> BasicFSARunner {
+    pub fn new<M: FSA<Weight=boolweight, Label=ByteLabel>>(m: M) -> BasicFSARunner {
         BasicFSARunner {
             fsa: optimize_fsa(m)
         }
     }
 }
 
-impl<R: Read> Runner<R> for BasicFSARunner {
-    fn run(&mut self, input: R) {
-        let input = BufReader::new(input);
-        for l in input.lines() {
-            let mut st = self.fsa.init_state();
-            let mut accepted = false;
-            let l = l.expect("Read failed");
-            for b in l.bytes() {
-                let arcvec = self.fsa.arcs_vec(&st);
-                let searchres = arcvec.as_slice().binary_search_by(|ref a| {
-                    a.label().cmp(&b)
-                });
-                match searchres {
-                    Ok(idx) => {
-                        st = arcvec[idx].nextstate();
-                        if self.fsa.final_weight(&st) != false {
-                            accepted = true;
-                            break
-                        }
-                    }
-                    Err(_) => {
-                        accepted = false;
-                        break
+impl Matcher for BasicFSARunner {
+    fn is_match(&self, line: &[u8]) -> bool {
+        let mut st = self.fsa.init_state();
+        if self.fsa.final_weight(&st) != false {
+            return true;
+        }
+        for b in line.iter() {
+            let arcvec = self.fsa.arcs_vec(&st);
+            let searchres = arcvec.as_slice().binary_search_by(|ref a| {
+                a.label().cmp(&ByteLabel::from(*b))
+            });
+            match searchres {
+                Ok(idx) => {
+                    st = arcvec[idx].nextstate();
+                    if self.fsa.final_weight(&st) != false {
+                        return true;
                     }
                 }
-            }
-            if accepted {
-                println!("{}", l)
+                Err(_) => {
+                    return false;
+                }
             }
         }
+        false
+    }
+}
 
+impl<R: Read> Runner<R> for BasicFSARunner {
+    fn run(&mut self, input: R) {
+        print_matching_lines(self, input)
     }
 }
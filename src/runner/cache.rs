@@ -0,0 +1,254 @@
+use automata::vector::ByteVectorFSA;
+use automata::binary::{self,write_binary,read_binary};
+use runner::MatchMode;
+use rexp::Flags;
+#[cfg(feature = "jit")]
+use utils::llvm::get_default_target_triple;
+
+use std::cmp::max;
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs::{self,File};
+use std::hash::{Hash,Hasher};
+use std::io::{self,Read,Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::UNIX_EPOCH;
+
+/// Default bound of the total size of files in the cache directory
+pub const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// On-disk cache of compiled patterns
+///
+/// Each entry consists of files sharing a name derived from the key: the key
+/// itself for detecting hash collisions, the minimized DFA in the native
+/// binary format, and optionally object code emitted for the JIT runner.
+///
+/// Whenever an entry is stored, the oldest entries are evicted until the
+/// directory fits in `max_bytes`.
+pub struct Cache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Cache {
+        Cache::with_max_bytes(dir, DEFAULT_MAX_BYTES)
+    }
+
+    pub fn with_max_bytes(dir: PathBuf, max_bytes: u64) -> Cache {
+        Cache {
+            dir: dir,
+            max_bytes: max_bytes,
+        }
+    }
+
+    /// Opens `$XDG_CACHE_HOME/rustre`, or `~/.cache/rustre` if unset
+    ///
+    /// The cache is used by default with `DEFAULT_MAX_BYTES`. Returns `None`
+    /// if `RUSTRE_NO_CACHE` is set or no directory is found.
+    pub fn open_default() -> Option<Cache> {
+        if env::var_os("RUSTRE_NO_CACHE").map(|v| v.len() != 0).unwrap_or(false) {
+            return None;
+        }
+        let base = match env::var_os("XDG_CACHE_HOME") {
+            Some(ref d) if d.len() != 0 => PathBuf::from(d),
+            _ => env::home_dir()?.join(".cache")
+        };
+        Some(Cache::new(base.join("rustre")))
+    }
+
+    pub fn entry(&self, key: &str) -> CacheEntry {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        CacheEntry {
+            key: key.to_string(),
+            dir: self.dir.clone(),
+            stem: format!("{:016x}", hasher.finish()),
+            max_bytes: self.max_bytes,
+        }
+    }
+}
+
+/// Returns the target triple that object code is emitted for
+#[cfg(feature = "jit")]
+fn target_triple() -> String {
+    get_default_target_triple().to_string_lossy().into_owned()
+}
+
+#[cfg(not(feature = "jit"))]
+fn target_triple() -> String {
+    format!("{}-{}", env::consts::ARCH, env::consts::OS)
+}
+
+/// Returns the cache key for the pattern and how it is run
+///
+/// The key includes the versions of the crate and the binary format, and the
+/// target triple, since the DFA and object code may not be loadable across
+/// them.
+pub fn cache_key(exprs: &[String], flags: Flags, max_edits: usize, mode: MatchMode,
+                 runner: &str) -> String {
+    let mut ret = format!("rustre {}\nformat {}\ntarget {}\n",
+                          env!("CARGO_PKG_VERSION"), binary::VERSION, target_triple());
+    ret.push_str(&format!("runner {}\nmode {:?}\nflags {:?}\nmax-edits {}\n",
+                          runner, mode, flags, max_edits));
+    for expr in exprs.iter() {
+        ret.push_str(&format!("expr {:?}\n", expr));
+    }
+    ret
+}
+
+pub struct CacheEntry {
+    key: String,
+    dir: PathBuf,
+    stem: String,
+    max_bytes: u64,
+}
+
+impl CacheEntry {
+    fn path(&self, ext: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", self.stem, ext))
+    }
+
+    fn read_file(&self, ext: &str) -> Option<Vec<u8>> {
+        let mut buf = Vec::new();
+        File::open(self.path(ext)).and_then(|mut f| f.read_to_end(&mut buf)).ok()?;
+        Some(buf)
+    }
+
+    /// Writes to a temporary file first, so that concurrent readers never see
+    /// partially written files
+    fn write_file(&self, ext: &str, data: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp = self.path(&format!("{}.tmp{}", ext, process::id()));
+        File::create(&tmp).and_then(|mut f| f.write_all(data))?;
+        fs::rename(&tmp, self.path(ext))
+    }
+
+    /// Removes the oldest entries until the directory fits in `max_bytes`
+    ///
+    /// Entries are ordered by the last modification of their files, i.e. by
+    /// when they were stored. This entry is kept even if it alone exceeds
+    /// the bound.
+    fn evict(&self) -> io::Result<()> {
+        let mut entries: BTreeMap<String, (u64, u64, Vec<PathBuf>)> = BTreeMap::new();
+        for file in fs::read_dir(&self.dir)? {
+            let file = file?;
+            let meta = file.metadata()?;
+            if ! meta.is_file() {
+                continue;
+            }
+            let modified = meta.modified()?.duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs()).unwrap_or(0);
+            let name = file.file_name().to_string_lossy().into_owned();
+            let stem = name.split('.').next().unwrap_or("").to_string();
+            let entry = entries.entry(stem).or_insert((0, 0, Vec::new()));
+            entry.0 = max(entry.0, modified);
+            entry.1 += meta.len();
+            entry.2.push(file.path());
+        }
+
+        let mut total: u64 = entries.values().map(|e| e.1).sum();
+        let mut by_age: Vec<(u64, u64, Vec<PathBuf>)> = entries.into_iter()
+            .filter(|&(ref stem, _)| *stem != self.stem)
+            .map(|(_, e)| e)
+            .collect();
+        by_age.sort_by_key(|e| e.0);
+        for (_, size, paths) in by_age.into_iter() {
+            if total <= self.max_bytes {
+                break;
+            }
+            for path in paths.iter() {
+                // may have been removed by another process
+                let _ = fs::remove_file(path);
+            }
+            total -= size;
+        }
+        Ok(())
+    }
+
+    fn has_valid_key(&self) -> bool {
+        self.read_file("key").map(|k| k == self.key.as_bytes()).unwrap_or(false)
+    }
+
+    /// Returns the cached DFA, or `None` on any errors
+    pub fn load_fsa(&self) -> Option<ByteVectorFSA> {
+        if ! self.has_valid_key() {
+            return None;
+        }
+        read_binary(&self.read_file("fsa")?[..]).ok()
+    }
+
+    /// Stores the DFA, and invalidates the object code of the old entry
+    ///
+    /// Since the cache is optional, callers may just report errors as
+    /// warnings.
+    pub fn store_fsa(&self, fsa: &ByteVectorFSA) -> io::Result<()> {
+        let mut buf = Vec::new();
+        write_binary(fsa, &mut buf).expect("Serialization of DFA failed");
+        let _ = fs::remove_file(self.path("o"));
+        self.write_file("fsa", &buf)?;
+        self.write_file("key", self.key.as_bytes())?;
+        self.evict()
+    }
+
+    /// Returns the cached object code, or `None` on any errors
+    pub fn load_object(&self) -> Option<Vec<u8>> {
+        if ! self.has_valid_key() {
+            return None;
+        }
+        self.read_file("o")
+    }
+
+    pub fn store_object(&self, obj: &[u8]) -> io::Result<()> {
+        self.write_file("o", obj)?;
+        self.evict()
+    }
+}
+
+#[test]
+pub fn cache_test() {
+    use automata::{LoadTSV,DumpTSV};
+
+    let dir = env::temp_dir().join(format!("rustre-cache-test-{}", process::id()));
+    let cache = Cache::new(dir.clone());
+    let exprs = vec!["ab".to_string()];
+    let key = cache_key(&exprs, Flags::default(), 0, MatchMode::Substring, "table");
+    assert!(key != cache_key(&exprs, Flags::default(), 1, MatchMode::Substring, "table"));
+    assert!(key != cache_key(&exprs, Flags::default(), 0, MatchMode::Substring, "jit"));
+
+    let entry = cache.entry(&key);
+    assert!(entry.load_fsa().is_none());
+    let src = "0	1	97	true\n1	true";
+    let fsa = ByteVectorFSA::load_tsv(src.as_bytes());
+    entry.store_fsa(&fsa).expect("Store failed");
+    let mut dump_buf = Vec::<u8>::new();
+    cache.entry(&key).load_fsa().expect("Cache miss").dump_tsv(&mut dump_buf);
+    assert_eq!(String::from_utf8(dump_buf).expect("UTF-8 error").trim(), src);
+    assert!(entry.load_object().is_none());
+    entry.store_object(b"\x7fELF").expect("Store failed");
+    assert_eq!(entry.load_object(), Some(b"\x7fELF".to_vec()));
+
+    // storing the DFA again drops the stale object code
+    entry.store_fsa(&fsa).expect("Store failed");
+    assert!(entry.load_object().is_none());
+
+    // entries with the same file name but different keys are ignored
+    let other = CacheEntry { key: "other".to_string(), dir: dir.clone(), stem: entry.stem.clone(),
+                             max_bytes: DEFAULT_MAX_BYTES };
+    assert!(other.load_fsa().is_none());
+
+    // older entries are evicted when the directory exceeds the bound
+    let small = Cache::with_max_bytes(dir.join("small"), 1);
+    let first = small.entry(&key);
+    first.store_fsa(&fsa).expect("Store failed");
+    assert!(first.load_fsa().is_some());
+    let second = small.entry(&cache_key(&exprs, Flags::default(), 1, MatchMode::Substring,
+                                        "table"));
+    second.store_fsa(&fsa).expect("Store failed");
+    assert!(first.load_fsa().is_none());
+    assert!(second.load_fsa().is_some());
+
+    fs::remove_dir_all(&dir).expect("Remove failed");
+}
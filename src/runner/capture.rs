@@ -0,0 +1,76 @@
+use runner::Runner;
+use rexp::capture::CaptureMatcher;
+
+use std::io::{Read,BufReader,BufRead,Write,stdout};
+use std::ops::Range;
+
+/// Runner printing matched substrings or substituted lines
+///
+/// Unlike the other runners, this runner searches each match separately with
+/// the tagged DFA, so it is slower but it knows where the matches are.
+pub struct CaptureRunner {
+    matcher: CaptureMatcher,
+    only_matching: bool,
+    replacement: Option<String>,
+}
+
+impl CaptureRunner {
+    pub fn new(matcher: CaptureMatcher, only_matching: bool, replacement: Option<String>)
+               -> CaptureRunner {
+        CaptureRunner {
+            matcher: matcher,
+            only_matching: only_matching,
+            replacement: replacement,
+        }
+    }
+
+    /// Returns the text printed for a match
+    fn format_match(&self, caps: &[Option<Range<usize>>], line: &[u8],
+                    dest: &mut Vec<u8>) {
+        match self.replacement {
+            Some(ref tmpl) => self.matcher.expand(tmpl, caps, line, dest),
+            None => {
+                let whole = caps[0].clone().expect("Group 0 must be matched");
+                dest.extend_from_slice(&line[whole]);
+            }
+        }
+    }
+}
+
+impl<R: Read> Runner<R> for CaptureRunner {
+    fn run(&mut self, input: R) {
+        let input = BufReader::new(input);
+        let out = stdout();
+        let mut out = out.lock();
+        for l in input.split(b'\n') {
+            let l = l.expect("Read failed");
+            let line = &l[..];
+            let mut buf = Vec::new();
+            let mut last = 0;
+            let mut found = false;
+            for caps in self.matcher.captures_iter(line) {
+                found = true;
+                let whole = caps[0].clone().expect("Group 0 must be matched");
+                if self.only_matching {
+                    if whole.start == whole.end {
+                        continue;
+                    }
+                    self.format_match(&caps, line, &mut buf);
+                    buf.push(b'\n');
+                } else {
+                    buf.extend_from_slice(&line[last..whole.start]);
+                    self.format_match(&caps, line, &mut buf);
+                    last = whole.end;
+                }
+            }
+            if !found {
+                continue;
+            }
+            if !self.only_matching {
+                buf.extend_from_slice(&line[last..]);
+                buf.push(b'\n');
+            }
+            out.write_all(&buf).expect("Write failed");
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use runner::{optimize_fsa_with_mode,MatchMode};
+use runner::aot::is_c_identifier;
+use automata::{StateMachine,Arc,FSA};
+use automata::vector::ByteVectorFSA;
+use rexp::{compile_rexp_list_nfa,Flags,RexpError};
+
+use std::fmt::Write;
+
+/// Keywords that cannot be used as identifiers, including reserved ones
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "alignof", "as", "async", "await", "become", "box", "break", "const",
+    "continue", "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for",
+    "if", "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "offsetof",
+    "override", "priv", "proc", "pub", "pure", "ref", "return", "self", "Self", "sizeof",
+    "static", "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized",
+    "use", "virtual", "where", "while", "yield",
+];
+
+/// Returns true if `name` can be used as a Rust identifier
+pub fn is_rust_identifier(name: &str) -> bool {
+    is_c_identifier(name) && name != "_" && ! RUST_KEYWORDS.contains(&name)
+}
+
+/// Returns a Rust module named `name` matching lines with the DFA
+///
+/// `optfsa` must be the output of `optimize_fsa_with_mode` for `mode`. The
+/// module consists of static transition tables and `is_match(&[u8]) -> bool`
+/// taking a line without the line terminator, and depends on nothing but
+/// `core`. It is intended to be written from `build.rs` and `include!`d.
+pub fn generate_rust_source(optfsa: &ByteVectorFSA, mode: MatchMode, name: &str) -> String {
+    assert!(is_rust_identifier(name), "Module name must be an identifier but not a keyword");
+    let nstates = optfsa.nstates().expect("Number of states should be known");
+
+    // Same as `TableFSARunner`, missing transitions go to the initial state in
+    // the substring mode, and to an extra dead state otherwise.
+    let (nrows, missing) = match mode {
+        MatchMode::Substring => (nstates, 0),
+        MatchMode::WholeLine => (nstates + 1, nstates),
+    };
+    let state_type = if nrows <= 0x100 {
+        "u8"
+    } else if nrows <= 0x10000 {
+        "u16"
+    } else {
+        "u32"
+    };
+
+    let mut ret = String::new();
+    writeln!(ret, "// Generated by rustre; do not edit.").unwrap();
+    writeln!(ret, "#[allow(dead_code)]").unwrap();
+    writeln!(ret, "pub mod {} {{", name).unwrap();
+    writeln!(ret, "    pub const NSTATES: usize = {};", nrows).unwrap();
+    writeln!(ret, "").unwrap();
+    writeln!(ret, "    #[cfg_attr(rustfmt, rustfmt_skip)]").unwrap();
+    writeln!(ret, "    static TRANSITIONS: [[{}; 256]; {}] = [", state_type, nrows).unwrap();
+    for st in 0..nrows {
+        let mut row = vec![missing; 256];
+        if st < nstates {
+            for arc in optfsa.arcs(&(st as i64)) {
+                row[arc.label().index()] = arc.nextstate() as usize;
+            }
+        }
+        let vals: Vec<String> = row.iter().map(|s| s.to_string()).collect();
+        writeln!(ret, "        [{}],", vals.join(", ")).unwrap();
+    }
+    writeln!(ret, "    ];").unwrap();
+    writeln!(ret, "").unwrap();
+    let finals: Vec<&str> = (0..nrows)
+        .map(|st| st < nstates && optfsa.final_weight(&(st as i64)))
+        .map(|f| if f { "true" } else { "false" })
+        .collect();
+    writeln!(ret, "    #[cfg_attr(rustfmt, rustfmt_skip)]").unwrap();
+    writeln!(ret, "    static FINALS: [bool; {}] = [{}];", nrows, finals.join(", ")).unwrap();
+    writeln!(ret, "").unwrap();
+
+    match mode {
+        MatchMode::Substring => {
+            ret.push_str("    /// Returns true if any substring of `line` matches
+    pub fn is_match(line: &[u8]) -> bool {
+        let mut st = 0usize;
+        if FINALS[st] {
+            return true;
+        }
+        for b in line.iter() {
+            st = TRANSITIONS[st][*b as usize] as usize;
+            if FINALS[st] {
+                return true;
+            }
+        }
+        false
+    }
+");
+        }
+        MatchMode::WholeLine => {
+            ret.push_str("    /// Returns true if the whole `line` matches
+    pub fn is_match(line: &[u8]) -> bool {
+        let mut st = 0usize;
+        for b in line.iter() {
+            st = TRANSITIONS[st][*b as usize] as usize;
+        }
+        FINALS[st]
+    }
+");
+        }
+    }
+    writeln!(ret, "}}").unwrap();
+    ret
+}
+
+/// Compiles expressions into a Rust module; see `generate_rust_source`
+pub fn compile_rust_source<S: AsRef<str>>(exprs: &[S], flags: Flags, mode: MatchMode, name: &str)
+                                          -> Result<String, (usize, RexpError)> {
+    let nfa = compile_rexp_list_nfa(exprs, flags)?;
+    let optfsa = optimize_fsa_with_mode(nfa, mode);
+    Ok(generate_rust_source(&optfsa, mode, name))
+}
+
+#[cfg(test)]
+include!("codegen_sample.rs");
+
+#[test]
+pub fn codegen_test() {
+    use automata::{LoadTSV,MutableStateMachine,SimpleArc};
+    use automata::vector::ByteLabel;
+
+    // substring DFA for "a"
+    let mut dfa = ByteVectorFSA::load_tsv("1	true".as_bytes());
+    for st in 0..2 {
+        for b in 0..0x100 {
+            let next = if b == 97 { 1 } else { 0 };
+            dfa.add_arc(&st, SimpleArc::new(ByteLabel::from(b as u8), true, next));
+        }
+    }
+
+    let src = generate_rust_source(&dfa, MatchMode::Substring, "pat_a");
+    println!(" === Result[codegen] ===\n{}", src);
+    assert!(src.starts_with("// Generated by rustre; do not edit.\n"));
+    assert!(src.contains("\npub mod pat_a {\n    pub const NSTATES: usize = 2;\n"));
+    assert!(src.contains("    static TRANSITIONS: [[u8; 256]; 2] = [\n"));
+    assert!(src.contains("    static FINALS: [bool; 2] = [false, true];\n"));
+    assert!(src.contains(", 0, 1, 0, "));
+    assert!(src.contains("            if FINALS[st] {\n"));
+
+    let src = generate_rust_source(&dfa, MatchMode::WholeLine, "pat_a");
+    assert!(src.contains("    static FINALS: [bool; 3] = [false, true, false];\n"));
+    assert!(src.contains("        [2, 2, 2, "));
+
+    let src = compile_rust_source(&["ab"], Flags::default(), MatchMode::WholeLine, "pat_ab")
+        .expect("Compile error");
+    assert!(src.contains("pub fn is_match(line: &[u8]) -> bool"));
+    assert!(compile_rust_source(&["a("], Flags::default(), MatchMode::WholeLine, "x").is_err());
+}
+
+#[test]
+pub fn codegen_sample_test() {
+    use runner::table::TableFSARunner;
+    use runner::matcher::Matcher;
+
+    // `codegen_sample.rs` is checked in as generated, and must be kept up to date
+    let src = compile_rust_source(&["ab+c|[0-9]{3}"], Flags::default(), MatchMode::Substring,
+                                  "sample").expect("Compile error");
+    assert_eq!(src, include_str!("codegen_sample.rs"));
+
+    let nfa = compile_rexp_list_nfa(&["ab+c|[0-9]{3}"], Flags::default()).expect("Compile error");
+    let table = TableFSARunner::<i32>::new(nfa, MatchMode::Substring);
+    let lines = ["", "abc", "xabbbcx", "ac", "12", "a123", "1a2b3", "abab", "ab\u{ff}c"];
+    for line in lines.iter().map(|l| l.as_bytes()) {
+        assert_eq!(sample::is_match(line), table.is_match(line), "{:?}", line);
+    }
+    assert!(sample::is_match(b"xabbbcx"));
+    assert!(! sample::is_match(b"1a2b3"));
+
+    assert!(is_rust_identifier("pat_a"));
+    assert!(! is_rust_identifier("match"));
+    assert!(! is_rust_identifier("_"));
+    assert!(! is_rust_identifier("1a"));
+}
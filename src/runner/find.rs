@@ -0,0 +1,205 @@
+use runner::{make_head_skipper,Runner};
+use automata::{StateMachine,Arc,boolweight,FSA};
+use automata::vector::{ByteVectorFSA,ByteLabel};
+use automata::rmeps::rmeps;
+use automata::concat::concat;
+use automata::reverse::reverse;
+use automata::determinize::determinize;
+
+use std::io::{Read,BufReader,BufRead,stdout,Write,BufWriter};
+use std::ops::Range;
+
+/// Transition table of a DFA with an explicit dead state
+struct DenseDFA {
+    transition: Vec<usize>,
+    finals: Vec<bool>,
+    init: usize,
+    dead: usize,
+}
+
+impl DenseDFA {
+    fn new(dfa: &ByteVectorFSA) -> DenseDFA {
+        let nstates = dfa.nstates().expect("Number of states should be known");
+        let dead = nstates;
+        let mut trans = vec![dead; (nstates + 1) * 256];
+        let mut finals = vec![false; nstates + 1];
+
+        for st in dfa.states() {
+            let stidx = st as usize;
+            finals[stidx] = dfa.final_weight(&st);
+            for arc in dfa.arcs(&st) {
+                trans[(stidx << 8) | arc.label().index()] = arc.nextstate() as usize;
+            }
+        }
+
+        DenseDFA {
+            transition: trans,
+            finals: finals,
+            init: dfa.init_state() as usize,
+            dead: dead,
+        }
+    }
+
+    #[inline]
+    fn next(&self, st: usize, b: u8) -> usize {
+        self.transition[(st << 8) | (b as usize)]
+    }
+}
+
+/// Match finder with the POSIX leftmost-longest semantics
+///
+/// A DFA for the reversed pattern preceded by `.*` is run from the end of the
+/// haystack to find every position where a match starts. Then, for the
+/// leftmost start, the DFA for the pattern is run forward until it dies to find
+/// the longest match.
+pub struct LongestMatcher {
+    forward: DenseDFA,
+    reverse: DenseDFA,
+}
+
+impl LongestMatcher {
+    pub fn new<M: FSA<Weight=boolweight, Label=ByteLabel>>(m: M) -> LongestMatcher {
+        let m = rmeps(m);
+        let forward = determinize(m.clone());
+        let reverse = determinize(rmeps(concat(make_head_skipper(), reverse(&m))));
+
+        LongestMatcher {
+            forward: DenseDFA::new(&forward),
+            reverse: DenseDFA::new(&reverse),
+        }
+    }
+
+    /// Marks the positions where a match starts
+    fn match_starts(&self, haystack: &[u8]) -> Vec<bool> {
+        let rev = &self.reverse;
+        let mut starts = vec![false; haystack.len() + 1];
+        let mut st = rev.init;
+        starts[haystack.len()] = rev.finals[st];
+        for (i, b) in haystack.iter().enumerate().rev() {
+            st = rev.next(st, *b);
+            if st == rev.dead {
+                break;
+            }
+            starts[i] = rev.finals[st];
+        }
+        starts
+    }
+
+    /// Returns the end of the longest match starting at `start`
+    fn longest_end(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        let fwd = &self.forward;
+        let mut st = fwd.init;
+        let mut end = if fwd.finals[st] { Some(start) } else { None };
+        for (i, b) in haystack[start..].iter().enumerate() {
+            st = fwd.next(st, *b);
+            if st == fwd.dead {
+                break;
+            }
+            if fwd.finals[st] {
+                end = Some(start + i + 1);
+            }
+        }
+        end
+    }
+
+    /// Returns an iterator over successive non-overlapping matches
+    pub fn find_iter<'a, 'b>(&'a self, haystack: &'b [u8]) -> FindIter<'a, 'b> {
+        FindIter {
+            matcher: self,
+            starts: self.match_starts(haystack),
+            haystack: haystack,
+            pos: 0,
+            last_end: None,
+        }
+    }
+}
+
+pub struct FindIter<'a, 'b> {
+    matcher: &'a LongestMatcher,
+    haystack: &'b [u8],
+    starts: Vec<bool>,
+    pos: usize,
+    last_end: Option<usize>,
+}
+
+impl<'a, 'b> Iterator for FindIter<'a, 'b> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        while self.pos <= self.haystack.len() {
+            let start = match self.starts[self.pos..].iter().position(|s| *s) {
+                Some(offset) => self.pos + offset,
+                None => {
+                    self.pos = self.haystack.len() + 1;
+                    return None;
+                }
+            };
+            let end = self.matcher.longest_end(self.haystack, start)
+                .expect("Match must exist at the start position");
+            if start == end {
+                self.pos = end + 1;
+                // empty match right after the previous match is skipped
+                if self.last_end == Some(end) {
+                    continue;
+                }
+            } else {
+                self.pos = end;
+            }
+            self.last_end = Some(end);
+            return Some(start..end);
+        }
+        None
+    }
+}
+
+/// Runner printing each non-empty match in a separate line
+pub struct OnlyMatchingRunner {
+    matcher: LongestMatcher,
+}
+
+impl OnlyMatchingRunner {
+    pub fn new<M: FSA<Weight=boolweight, Label=ByteLabel>>(m: M) -> OnlyMatchingRunner {
+        OnlyMatchingRunner {
+            matcher: LongestMatcher::new(m)
+        }
+    }
+}
+
+impl<R: Read> Runner<R> for OnlyMatchingRunner {
+    fn run(&mut self, input: R) {
+        let out = stdout();
+        let mut out = BufWriter::new(out.lock());
+        let input = BufReader::with_capacity(8 * 1024, input);
+
+        for l in input.split(b'\n') {
+            let l = l.expect("Read failed");
+            for m in self.matcher.find_iter(&l) {
+                if m.start == m.end {
+                    continue;
+                }
+                out.write_all(&l[m]).expect("Write error");
+                out.write_all(b"\n").expect("Write error");
+            }
+        }
+    }
+}
+
+#[test]
+pub fn find_iter_test() {
+    use rexp::compile_rexp_nfa;
+
+    let find_all = |rexp: &str, haystack: &str| -> Vec<Range<usize>> {
+        let m = LongestMatcher::new(compile_rexp_nfa(rexp).expect("Compile error"));
+        m.find_iter(haystack.as_bytes()).collect()
+    };
+
+    assert_eq!(find_all("ab?c?", "xabcab"), vec![1..4, 4..6]);
+    // the leftmost match is preferred to the one ending first
+    assert_eq!(find_all("(ab)?cd?", "abcd"), vec![0..4]);
+    assert_eq!(find_all("[ab]*c", "abcbbcc"), vec![0..3, 3..6, 6..7]);
+    assert_eq!(find_all("a*", "baab"), vec![0..0, 1..3, 4..4]);
+    assert_eq!(find_all("xyz", "abc"), vec![]);
+    // NUL is an ordinary byte, not epsilon
+    assert_eq!(find_all("a\0b", "ab a\0b"), vec![3..6]);
+    assert_eq!(find_all("x\0*y", "x\0\0y"), vec![0..4]);
+}
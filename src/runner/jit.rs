@@ -1,7 +1,10 @@
-use automata::{StateMachine,Arc,FSA};
-use automata::vector::{ByteVectorFSA};
+use automata::{StateMachine,Arc,FSA,MutableStateMachine,Semiring,SimpleArc};
+use automata::vector::{ByteVectorFSA,ByteLabel};
 use runner::{Runner};
-use std::io::{Read};
+use runner::matcher::{Matcher,LineMatch,print_matching_lines};
+use runner::cache::CacheEntry;
+use std::io::{self,Read};
+use std::cell::RefCell;
 use std::mem;
 use std::ffi::{CString};
 use std::env;
@@ -13,7 +16,8 @@ use utils::llvm::{
     ExecutionEngine,add_function,ValueMethod,insert_basic_block,int_with_type,
     int64,int32,int8,int64_ptr_type,BranchTable,int8_type,int16_type,const_array,
     int32_type,insert_basic_block_after,TargetMachine,int128,int128_type,
-    int128_ptr_type,int32_ptr_type,int16_ptr_type,int16,create_metadata_node};
+    int128_ptr_type,int32_ptr_type,int16_ptr_type,int16,create_metadata_node,
+    OrcJIT,get_default_target_triple};
 
 use utils::rawbuffer::{RawBuffer};
 
@@ -24,8 +28,6 @@ use llvm_sys::prelude::{
 use llvm_sys::core::LLVMMDString;
 use llvm_sys::analysis::LLVMVerifierFailureAction;
 
-use std::io::{stdout, Write, BufWriter};
-
 
 pub struct CompileContext {
     module: Module,
@@ -34,6 +36,15 @@ pub struct CompileContext {
 
 impl CompileContext {
     pub fn new(module: Module, optfsa: ByteVectorFSA) -> CompileContext {
+        CompileContext::new_with_name(module, optfsa, "run")
+    }
+
+    /// Builds the matcher function with the given symbol name
+    ///
+    /// The function takes the start state, the buffer length, the input buffer
+    /// and the output buffer, and returns the state after the whole buffer.
+    pub fn new_with_name(module: Module, optfsa: ByteVectorFSA, fn_name: &str)
+                         -> CompileContext {
         let nstates = optfsa.nstates().expect("Number of states should be known");
 
         let mut module = module;
@@ -43,7 +54,7 @@ impl CompileContext {
             int8_ptr_type(), // INPUT BUFFER
             int64_ptr_type(), // OUTPUT BUFFER
         ];
-        let main_fn = add_function(&mut module, "run", &mut main_args, int64_type());
+        let main_fn = add_function(&mut module, fn_name, &mut main_args, int64_type());
 
         let (arg_startst, arg_buflen, arg_inpbuf, arg_outbuf) = (
             main_fn.get_param(0), main_fn.get_param(1),
@@ -68,7 +79,7 @@ impl CompileContext {
                 let mut row = Vec::new();
                 row.extend_from_slice(&[0; 256]);
                 for arc in optfsa.arcs(&(st as i64)) {
-                    row[arc.label() as usize] = arc.nextstate() as u64;
+                    row[arc.label().index()] = arc.nextstate() as u64;
                 }
                 let row_vals: Vec<_> =
                     row.iter().map(|s| int_with_type(state_type, *s)).collect();
@@ -219,16 +230,20 @@ impl CompileContext {
         }
     }
 
-    fn optimize_ir(&self, tm: &TargetMachine) {
+    pub fn optimize_ir(&self, tm: &TargetMachine) {
         self.module.optimize(self.main_fn, tm);
     }
 
+    pub fn emit_object(&self, tm: &TargetMachine) -> Result<Vec<u8>, String> {
+        tm.emit_object(&self.module)
+    }
+
     #[allow(dead_code)]
     fn verify(&self) -> (bool, String) {
         self.module.verify(LLVMVerifierFailureAction::LLVMPrintMessageAction)
     }
 
-    fn compile(self) -> (ExecutionEngine, extern "C" fn(u64, u64, *const u8, *mut u64) -> u64) {
+    fn compile(self) -> (ExecutionEngine, RunFn) {
         let ee = ExecutionEngine::new_mcjit(self.module);
 
         let addr = ee.get_function_addr(CString::new("run").unwrap().as_ptr());
@@ -241,19 +256,101 @@ impl CompileContext {
 }
 
 
+type RunFn = extern "C" fn(u64, u64, *const u8, *mut u64) -> u64;
+
+/// Engine owning the compiled code of `JITFSARunner`
+#[allow(dead_code)]
+enum Engine {
+    MCJIT(ExecutionEngine),
+    /// Loaded from object code
+    Orc(OrcJIT),
+}
+
+fn load_object(obj: &[u8]) -> Result<(Engine, RunFn), String> {
+    let triple = get_default_target_triple();
+    let machine = TargetMachine::new(triple.as_ptr())?;
+    let mut jit = OrcJIT::new(machine);
+    jit.add_object(obj)?;
+    let addr = jit.get_function_addr(CString::new("run").unwrap().as_ptr());
+    if addr == 0 {
+        return Err("Symbol not found: run".to_string());
+    }
+    let f = unsafe {
+        mem::transmute(addr)
+    };
+    Ok((Engine::Orc(jit), f))
+}
+
+/// Returns the DFA with every newline leading to the initial state
+///
+/// `find_lines_with` runs the compiled function across line boundaries, so
+/// this makes each line matched from the initial state as in `is_match`,
+/// even for DFAs moving on newlines, e.g. of `(?s)` or `~`.
+fn reset_on_newline(optfsa: ByteVectorFSA) -> ByteVectorFSA {
+    let newline = ByteLabel::from(b'\n');
+    let init = optfsa.init_state();
+    let mut ret = ByteVectorFSA::new();
+    for _ in optfsa.states() {
+        ret.add_new_state();
+    }
+    for st in optfsa.states() {
+        let fw = optfsa.final_weight(&st);
+        if fw.is_nonzero() {
+            ret.set_final_weight(&st, fw);
+        }
+        for arc in optfsa.arcs(&st) {
+            let next = if arc.label() == newline { init } else { arc.nextstate() };
+            ret.add_arc(&st, SimpleArc::new(arc.label(), arc.weight(), next));
+        }
+    }
+    ret
+}
+
 // Output u64 design
 // |is_final:1|blank:15|outsym:16|state:32|
 pub struct JITFSARunner {
     #[allow(dead_code)]
-    ee: ExecutionEngine, // need to own engine while func is being used
-    func: extern "C" fn(u64, u64, *const u8, *mut u64) -> u64
+    ee: Engine, // need to own engine while func is being used
+    func: RunFn,
+    /// The compiled function only reports states after reading bytes
+    init_final: bool,
+    /// Input and output buffers of `is_match`, grown for the longest line
+    buffers: RefCell<(RawBuffer, RawBuffer)>,
 }
 
 impl JITFSARunner {
-    pub fn new_with_optimized_fsa(optfsa: ByteVectorFSA) -> JITFSARunner {
+    fn with_engine(ee: Engine, func: RunFn, init_final: bool) -> JITFSARunner {
+        JITFSARunner {
+            ee: ee,
+            func: func,
+            init_final: init_final,
+            buffers: RefCell::new((RawBuffer::new(8, 64), RawBuffer::new(8 * 8, 64))),
+        }
+    }
+
+    /// Compiles the DFA, or loads object code from the cache entry if any
+    ///
+    /// On a cache miss, the object code is emitted and stored in the entry.
+    /// Problems with the cache don't stop compilation, and are returned as
+    /// warnings.
+    pub fn new_with_optimized_fsa_and_cache(optfsa: ByteVectorFSA, cache: Option<&CacheEntry>)
+                                            -> (JITFSARunner, Vec<String>) {
+        let mut warnings = Vec::new();
+        let init_final = optfsa.final_weight(&optfsa.init_state());
+        if let Some(obj) = cache.and_then(|c| c.load_object()) {
+            match load_object(&obj) {
+                Ok((ee, f)) => {
+                    return (JITFSARunner::with_engine(ee, f, init_final), warnings);
+                }
+                Err(what) => {
+                    warnings.push(format!("Cannot load the cached object code: {}", what));
+                }
+            }
+        }
+
         let (module, machine) = create_module("runner", None);
 
-        let mut ctx = CompileContext::new(module, optfsa);
+        let mut ctx = CompileContext::new(module, reset_on_newline(optfsa));
 
         ctx.verify();
 
@@ -261,7 +358,7 @@ impl JITFSARunner {
         if env::var_os("RUSTRE_JIT_NOOPT").unwrap_or(empty.clone()).len() == 0 {
             ctx.optimize_ir(&machine);
         } else {
-            eprintln!("[WARN] JIT optimization isn't applied");
+            warnings.push("JIT optimization isn't applied".to_string());
         }
 
         if env::var_os("RUSTRE_JIT_DUMPIR").unwrap_or(empty.clone()).len() != 0 {
@@ -278,78 +375,164 @@ impl JITFSARunner {
             _ => {}
         }
 
+        if let Some(entry) = cache {
+            let loaded = ctx.emit_object(&machine).and_then(|obj| {
+                let ret = load_object(&obj)?;
+                if let Err(what) = entry.store_object(&obj) {
+                    warnings.push(format!("Cannot write the cache: {}", what));
+                }
+                Ok(ret)
+            });
+            match loaded {
+                Ok((ee, f)) => {
+                    return (JITFSARunner::with_engine(ee, f, init_final), warnings);
+                }
+                Err(what) => {
+                    warnings.push(format!("Object code cannot be cached: {}", what));
+                }
+            }
+        }
+
         let (ee, f) = ctx.compile();
 
-        JITFSARunner {
-            ee: ee,
-            func: f,
-        }
+        (JITFSARunner::with_engine(Engine::MCJIT(ee), f, init_final), warnings)
     }
 }
 
 const BUFSIZE: usize = 64 * 1024;
 
-impl<R: Read> Runner<R> for JITFSARunner {
-    fn run(&mut self, input: R) {
-        let out = stdout();
-        let mut out = BufWriter::new(out.lock());
+const FINAL_BIT: u64 = 0x8000_0000_0000_0000;
+
+/// Returns `sz` rounded up to a positive multiple of 8, as the compiled
+/// function requires
+fn padded_len(sz: usize) -> usize {
+    if sz == 0 { 8 } else { (sz + 7) & !7 }
+}
+
+impl Matcher for JITFSARunner {
+    fn is_match(&self, line: &[u8]) -> bool {
+        if self.init_final {
+            return true;
+        }
+        let len = padded_len(line.len());
+        let mut buffers = self.buffers.borrow_mut();
+        if buffers.0.size() < len {
+            *buffers = (RawBuffer::new(len, 64), RawBuffer::new(len * mem::size_of::<u64>(), 64));
+        }
+        let &mut (ref mut inputbuf, ref mut outputbuf) = &mut *buffers;
+        inputbuf.as_slice_mut::<u8>()[..line.len()].copy_from_slice(line);
+
+        (self.func)(0, len as u64, inputbuf.as_ptr::<u8>(), outputbuf.as_mut_ptr::<u64>());
+        // outputs for the padding are ignored
+        outputbuf.as_slice::<u64>()[..line.len()].iter().any(|o| (*o & FINAL_BIT) != 0)
+    }
 
-        let mut input = input;
+    /// Runs the compiled function over large chunks rather than line by line
+    ///
+    /// States aren't carried over lines since newlines lead to the initial
+    /// state in the compiled DFA; see `reset_on_newline`.
+    fn find_lines_with(&self, input: &mut Read, sink: &mut FnMut(LineMatch) -> bool)
+                       -> io::Result<()> {
         let mut inputbuf =  RawBuffer::new(BUFSIZE, 64);
         let mut outputbuf =  RawBuffer::new(BUFSIZE * mem::size_of::<u64>(), 64);
 
         let mut linebuf = Vec::<u8>::with_capacity(4 * 1024);
-        let mut accepted = false; // this needs to kept for several chunks
+        let mut accepted = self.init_final; // this needs to kept for several chunks
+        let mut line_number = 1;
+        let mut offset = 0;
+        let mut linestart_offset = 0;
 
         let mut prev_endstate = 0;
-        let mut finished = false;
-        while ! finished {
+        loop {
             let readsz = match input.read(inputbuf.as_slice_mut()) {
-                Ok(sz) => {
-                    if sz < inputbuf.size() {
-                        finished = true;
-                    }
-                    sz
-                },
-                Err(_) => {
-                    panic!("Read error")
-                }
+                Ok(0) => { break; }
+                Ok(sz) => sz,
+                Err(ref what) if what.kind() == io::ErrorKind::Interrupted => { continue; }
+                Err(what) => { return Err(what); }
             };
 
-            prev_endstate = (self.func)(prev_endstate,
-                                        inputbuf.size() as u64,
-                                        inputbuf.as_ptr::<u8>(),
-                                        outputbuf.as_mut_ptr::<u64>());
+            (self.func)(prev_endstate,
+                        padded_len(readsz) as u64,
+                        inputbuf.as_ptr::<u8>(),
+                        outputbuf.as_mut_ptr::<u64>());
+            let inputs = &inputbuf.as_slice::<u8>()[..readsz];
+            let outputs = &outputbuf.as_slice::<u64>()[..readsz];
+            // bytes after `readsz` are stale, so the state is taken at the last read byte
+            prev_endstate = outputs[readsz - 1] & !FINAL_BIT;
 
             let mut linestart = 0;
-
-            for (loc, (i, o)) in
-                inputbuf.as_slice::<u8>().iter()
-                .zip(outputbuf.as_slice::<u64>().iter())
-                .enumerate()
-            {
-                if loc >= readsz {
-                    break;
-                }
-
+            for (loc, (i, o)) in inputs.iter().zip(outputs.iter()).enumerate() {
                 if *i == b'\n' {
                     if accepted {
-                        linebuf.extend_from_slice(&inputbuf.as_slice::<u8>()[linestart..loc]);
-                        out.write(linebuf.as_slice()).expect("Write error");
-                        out.write(b"\n").expect("Write error");
+                        linebuf.extend_from_slice(&inputs[linestart..loc]);
+                        let m = LineMatch {
+                            line_number: line_number,
+                            offset: linestart_offset,
+                            line: linebuf.clone(),
+                        };
+                        if ! sink(m) {
+                            return Ok(());
+                        }
                     }
                     // reset
                     linebuf.clear();
                     linestart = loc + 1;
-                    accepted = false;
-                }
-                if (*o & 0x8000_0000_0000_0000) != 0 {
+                    linestart_offset = offset + loc + 1;
+                    line_number += 1;
+                    accepted = self.init_final;
+                } else if (*o & FINAL_BIT) != 0 {
                     accepted = true;
                 }
             }
+            // the rest of the line continues to the next chunk
+            linebuf.extend_from_slice(&inputs[linestart..]);
+            offset += readsz;
+        }
 
-            prev_endstate = prev_endstate & 0x7FFF_FFFF_FFFF_FFFF;
+        // the last line without the terminator
+        if accepted && offset > linestart_offset {
+            sink(LineMatch {
+                line_number: line_number,
+                offset: linestart_offset,
+                line: linebuf,
+            });
         }
+        Ok(())
     }
 }
 
+impl<R: Read> Runner<R> for JITFSARunner {
+    fn run(&mut self, input: R) {
+        print_matching_lines(self, input)
+    }
+}
+
+#[test]
+pub fn jit_matcher_test() {
+    use runner::{optimize_fsa_with_mode,MatchMode};
+    use runner::table::TableFSARunner;
+    use rexp::compile_rexp_nfa;
+
+    let find_all = |m: &Matcher, input: &str| -> Vec<(usize, usize, Vec<u8>)> {
+        let mut found = Vec::new();
+        m.find_lines_with(&mut input.as_bytes(), &mut |l| {
+            found.push((l.line_number, l.offset, l.line));
+            true
+        }).expect("Read error");
+        found
+    };
+
+    // DFAs moving on newlines must not carry states over to the next line
+    let input = "a\nb\nab\n\nxa\nbz\naxb";
+    for src in ["b", "(?s)a.b", "~(a?)", "(?s)a.*b"].iter() {
+        let nfa = compile_rexp_nfa(src).expect("Compile error");
+        let table = TableFSARunner::<i32>::new(nfa.clone(), MatchMode::Substring);
+        let optfsa = optimize_fsa_with_mode(nfa, MatchMode::Substring);
+        let (jit, _) = JITFSARunner::new_with_optimized_fsa_and_cache(optfsa, None);
+        for line in input.split('\n') {
+            assert_eq!(jit.is_match(line.as_bytes()), table.is_match(line.as_bytes()),
+                       "{} on {:?}", src, line);
+        }
+        assert_eq!(find_all(&jit, input), find_all(&table, input), "{}", src);
+    }
+}
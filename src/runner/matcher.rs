@@ -0,0 +1,197 @@
+use std::io::{self,Read,BufReader,BufRead,stdout,Write,BufWriter};
+
+/// Line selected by a `Matcher`
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct LineMatch {
+    /// Line number starting from 1
+    pub line_number: usize,
+    /// Byte offset of the beginning of the line in the input
+    pub offset: usize,
+    /// Content of the line without the line terminator
+    pub line: Vec<u8>,
+}
+
+/// Selects lines without deciding what to do with them
+///
+/// Lines are separated by `\n`, which is not passed to `is_match` nor
+/// included in `LineMatch`. The last line doesn't need the terminator.
+pub trait Matcher {
+    /// Returns true if `line`, without the line terminator, is selected
+    fn is_match(&self, line: &[u8]) -> bool;
+
+    /// Calls `sink` for each selected line until it returns false
+    fn find_lines_with(&self, input: &mut Read, sink: &mut FnMut(LineMatch) -> bool)
+                       -> io::Result<()> {
+        let mut input = BufReader::with_capacity(8 * 1024, input);
+        let mut l = Vec::new();
+        let mut line_number = 0;
+        let mut offset = 0;
+        loop {
+            l.clear();
+            let sz = input.read_until(b'\n', &mut l)?;
+            if sz == 0 {
+                return Ok(());
+            }
+            line_number += 1;
+            if l.last() == Some(&b'\n') {
+                l.pop();
+            }
+            if self.is_match(&l) {
+                let m = LineMatch {
+                    line_number: line_number,
+                    offset: offset,
+                    line: l.clone(),
+                };
+                if ! sink(m) {
+                    return Ok(());
+                }
+            }
+            offset += sz;
+        }
+    }
+
+    /// Returns an iterator over selected lines
+    ///
+    /// The iteration stops at the first read error, which can be obtained
+    /// with `LineMatches::take_error`.
+    fn find_lines<R: Read>(&self, input: R) -> LineMatches<Self, R> where Self: Sized {
+        LineMatches {
+            matcher: self,
+            input: BufReader::with_capacity(8 * 1024, input),
+            buf: Vec::new(),
+            line_number: 0,
+            offset: 0,
+            error: None,
+        }
+    }
+}
+
+impl<'a, M: Matcher + ?Sized> Matcher for &'a M {
+    fn is_match(&self, line: &[u8]) -> bool {
+        (**self).is_match(line)
+    }
+
+    fn find_lines_with(&self, input: &mut Read, sink: &mut FnMut(LineMatch) -> bool)
+                       -> io::Result<()> {
+        (**self).find_lines_with(input, sink)
+    }
+}
+
+/// Makes boxed matchers, e.g. from `compile_matcher`, usable with `find_lines`
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn is_match(&self, line: &[u8]) -> bool {
+        (**self).is_match(line)
+    }
+
+    fn find_lines_with(&self, input: &mut Read, sink: &mut FnMut(LineMatch) -> bool)
+                       -> io::Result<()> {
+        (**self).find_lines_with(input, sink)
+    }
+}
+
+pub struct LineMatches<'a, M: 'a + ?Sized, R: Read> {
+    matcher: &'a M,
+    input: BufReader<R>,
+    buf: Vec<u8>,
+    line_number: usize,
+    offset: usize,
+    error: Option<io::Error>,
+}
+
+impl<'a, M: 'a + Matcher + ?Sized, R: Read> LineMatches<'a, M, R> {
+    /// Returns the error that stopped the iteration, if any
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+impl<'a, M: 'a + Matcher + ?Sized, R: Read> Iterator for LineMatches<'a, M, R> {
+    type Item = LineMatch;
+
+    fn next(&mut self) -> Option<LineMatch> {
+        loop {
+            self.buf.clear();
+            let sz = match self.input.read_until(b'\n', &mut self.buf) {
+                Ok(0) => { return None; }
+                Ok(sz) => sz,
+                Err(what) => {
+                    self.error = Some(what);
+                    return None;
+                }
+            };
+            self.line_number += 1;
+            let offset = self.offset;
+            self.offset += sz;
+            if self.buf.last() == Some(&b'\n') {
+                self.buf.pop();
+            }
+            if self.matcher.is_match(&self.buf) {
+                return Some(LineMatch {
+                    line_number: self.line_number,
+                    offset: offset,
+                    line: self.buf.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Writes lines selected by the matcher to stdout
+pub fn print_matching_lines<M: Matcher + ?Sized, R: Read>(matcher: &M, input: R) {
+    let out = stdout();
+    let mut out = BufWriter::new(out.lock());
+    let mut input = input;
+    matcher.find_lines_with(&mut input, &mut |m| {
+        out.write_all(&m.line).expect("Write error");
+        out.write_all(b"\n").expect("Write error");
+        true
+    }).expect("Read error");
+}
+
+#[test]
+pub fn matcher_test() {
+    use runner::basic::BasicFSARunner;
+    use runner::table::TableFSARunner;
+    use runner::MatchMode;
+    use rexp::compile_rexp_nfa;
+
+    let input = "abc\nxbz\n\nab\nb";
+    let nfa = compile_rexp_nfa("b").expect("Compile error");
+    let matchers: Vec<Box<Matcher>> = vec![
+        box BasicFSARunner::new(nfa.clone()),
+        box TableFSARunner::<i8>::new(nfa.clone(), MatchMode::Substring),
+        box TableFSARunner::<i32>::new(nfa.clone(), MatchMode::Substring),
+    ];
+    for m in matchers.iter() {
+        assert!(m.is_match(b"abc"));
+        assert!(! m.is_match(b"ac"));
+        assert!(! m.is_match(b""));
+
+        let mut found = Vec::new();
+        m.find_lines_with(&mut input.as_bytes(), &mut |l| {
+            found.push((l.line_number, l.offset, l.line));
+            true
+        }).expect("Read error");
+        assert_eq!(found, vec![(1, 0, b"abc".to_vec()), (2, 4, b"xbz".to_vec()),
+                               (4, 9, b"ab".to_vec()), (5, 12, b"b".to_vec())]);
+
+        // the sink can stop the search
+        let mut count = 0;
+        m.find_lines_with(&mut input.as_bytes(), &mut |_| { count += 1; count < 2 })
+            .expect("Read error");
+        assert_eq!(count, 2);
+    }
+
+    let whole = TableFSARunner::<i8>::new(nfa.clone(), MatchMode::WholeLine);
+    let lines: Vec<usize> = whole.find_lines(input.as_bytes()).map(|l| l.line_number).collect();
+    assert_eq!(lines, vec![5]);
+    let substr = BasicFSARunner::new(nfa.clone());
+    let lines: Vec<LineMatch> = substr.find_lines(input.as_bytes()).collect();
+    assert_eq!(lines[1], LineMatch { line_number: 2, offset: 4, line: b"xbz".to_vec() });
+    assert_eq!(lines.len(), 4);
+
+    // the empty expression selects all lines including empty ones
+    let empty = TableFSARunner::<i8>::new(compile_rexp_nfa("a*").expect("Compile error"),
+                                          MatchMode::Substring);
+    assert_eq!(empty.find_lines("x\n\ny".as_bytes()).count(), 3);
+}
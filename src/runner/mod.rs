@@ -1,39 +1,69 @@
-use automata::{Arc,SimpleArc,boolweight,FSA,MutableStateMachine,DumpTSV};
+use automata::{Arc,SimpleArc,boolweight,FSA,MutableStateMachine,DumpTSV,Semiring};
 
 use automata::rmeps::rmeps;
 use automata::concat::concat;
 use automata::determinize::determinize;
 use automata::arcsort::arcsort;
 use automata::minimize::minimize_unweighted;
-use automata::vector::VectorFSA;
+use automata::vector::{VectorFSA,ByteLabel};
 use std::env;
 use std::ffi::OsStr;
 use std::io;
+use runner::cache::CacheEntry;
+use runner::matcher::print_matching_lines;
+
+pub use runner::matcher::{Matcher,LineMatch};
 
 #[allow(unused_imports)]
 use test::Bencher;
 
+pub mod matcher;
 pub mod table;
-#[allow(dead_code)]
 pub mod basic;
+#[cfg(feature = "jit")]
 pub mod jit;
+pub mod capture;
+pub mod find;
+pub mod set;
+pub mod rewrite;
+pub mod cache;
+pub mod aot;
+pub mod codegen;
 
 use std::io::Read;
 
-fn make_head_skipper() -> VectorFSA<boolweight, u8> {
+fn make_head_skipper<W: Semiring>() -> VectorFSA<W, ByteLabel> {
     let mut ret = VectorFSA::new();
     let init = ret.add_new_state();
-    ret.set_final_weight(&init, true);
+    ret.set_final_weight(&init, W::one());
     for l in 0x00..0x100 {
-        ret.add_arc(&init, SimpleArc::new(l as u8, true, init.clone()));
+        ret.add_arc(&init, SimpleArc::new(ByteLabel::from(l as u8), W::one(), init.clone()));
     }
     ret
 }
 
-fn optimize_fsa<M: FSA<Weight=boolweight, Label=u8>>(m: M) -> VectorFSA<boolweight, u8> {
-    let m = concat(make_head_skipper(), m);
+/// Specifies which part of a line must be accepted for selecting the line
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum MatchMode {
+    /// Any substring of the line
+    Substring,
+    /// The whole line excluding the line terminator
+    WholeLine,
+}
+
+fn optimize_fsa<M>(m: M) -> VectorFSA<boolweight, ByteLabel>
+    where M: FSA<Weight=boolweight, Label=ByteLabel> {
+    optimize_fsa_with_mode(m, MatchMode::Substring)
+}
+
+/// Returns the minimal DFA that runners actually run for the mode
+pub fn optimize_fsa_with_mode<M>(m: M, mode: MatchMode) -> VectorFSA<boolweight, ByteLabel>
+    where M: FSA<Weight=boolweight, Label=ByteLabel> {
     //eprintln!("          Raw NFA: #States = {:?}", m.nstates());
-    let m = rmeps(m);
+    let m = match mode {
+        MatchMode::Substring => rmeps(concat(make_head_skipper(), m)),
+        MatchMode::WholeLine => rmeps(m),
+    };
     //eprintln!("      After RmEps: #States = {:?}", m.nstates());
     let m = determinize(m);
     //eprintln!("After Determinize: #States = {:?}", m.nstates());
@@ -47,32 +77,93 @@ pub trait Runner<R: Read> {
     fn run(&mut self, input: R);
 }
 
-pub fn find_best_runner<M: FSA<Weight=boolweight, Label=u8>, R: Read>(m: M, use_jit: bool)
-                                                                      -> Box<Runner<R>> {
-    let optfsa = optimize_fsa(m);
+/// Selects a runner for the automaton
+///
+/// Warnings from `find_best_matcher_with_optimized_fsa` are discarded.
+pub fn find_best_runner<M: FSA<Weight=boolweight, Label=ByteLabel>, R: Read>(m: M, use_jit: bool,
+                                                                             mode: MatchMode)
+                                                                             -> Box<Runner<R>> {
+    let optfsa = optimize_fsa_with_mode(m, mode);
+    find_best_runner_with_optimized_fsa(optfsa, use_jit, mode, None).0
+}
+
+/// Selects a runner for the output of `optimize_fsa_with_mode`
+///
+/// See `find_best_matcher_with_optimized_fsa`.
+pub fn find_best_runner_with_optimized_fsa<R: Read>(optfsa: VectorFSA<boolweight, ByteLabel>,
+                                                    use_jit: bool, mode: MatchMode,
+                                                    cache: Option<&CacheEntry>)
+                                                    -> (Box<Runner<R>>, Vec<String>) {
+    let (matcher, warnings) = find_best_matcher_with_optimized_fsa(optfsa, use_jit, mode, cache);
+    (box matcher, warnings)
+}
+
+impl<R: Read> Runner<R> for Box<Matcher> {
+    fn run(&mut self, input: R) {
+        print_matching_lines(&**self, input)
+    }
+}
 
+/// Selects a matcher for the automaton
+///
+/// Warnings from `find_best_matcher_with_optimized_fsa` are discarded.
+pub fn find_best_matcher<M: FSA<Weight=boolweight, Label=ByteLabel>>(m: M, use_jit: bool,
+                                                                     mode: MatchMode)
+                                                                     -> Box<Matcher> {
+    let optfsa = optimize_fsa_with_mode(m, mode);
+    find_best_matcher_with_optimized_fsa(optfsa, use_jit, mode, None).0
+}
+
+/// Selects a matcher for the output of `optimize_fsa_with_mode`
+///
+/// If `cache` is given, the JIT runner reuses object code in the entry, or
+/// stores the emitted one. Without the `jit` feature, the table runner is
+/// always used. Fallbacks and cache errors are returned as warnings for the
+/// caller to report.
+pub fn find_best_matcher_with_optimized_fsa(optfsa: VectorFSA<boolweight, ByteLabel>,
+                                            use_jit: bool, mode: MatchMode,
+                                            cache: Option<&CacheEntry>)
+                                            -> (Box<Matcher>, Vec<String>) {
+    let mut warnings = Vec::new();
     let empty = OsStr::new("").to_os_string();
     if env::var_os("RUSTRE_DUMP_OPTFSA").unwrap_or(empty.clone()).len() != 0 {
         optfsa.dump_tsv(&mut io::stderr());
     }
 
-    let nst = optfsa.nstates().unwrap();
-    if use_jit {
-        box jit::JITFSARunner::new_with_optimized_fsa(optfsa)
-    } else {
-        if nst < 0x80 {
-            box table::TableFSARunner::<i8>::new_with_optimized_fsa(optfsa)
+    #[cfg(feature = "jit")]
+    {
+        if use_jit && mode != MatchMode::Substring {
+            warnings.push("JIT only supports substring matching, table runner is used".to_string());
         }
-        else if nst < 0x8000 {
-            box table::TableFSARunner::<i16>::new_with_optimized_fsa(optfsa)
+        if use_jit && mode == MatchMode::Substring {
+            let (runner, jit_warnings) =
+                jit::JITFSARunner::new_with_optimized_fsa_and_cache(optfsa, cache);
+            warnings.extend(jit_warnings);
+            return (box runner, warnings);
         }
-        else if nst < 0x80000000 {
-            box table::TableFSARunner::<i32>::new_with_optimized_fsa(optfsa)
-        }
-        else {
-            box table::TableFSARunner::<i64>::new_with_optimized_fsa(optfsa)
+    }
+    #[cfg(not(feature = "jit"))]
+    {
+        if use_jit {
+            warnings.push("JIT is not enabled in this build, table runner is used".to_string());
         }
+        let _ = cache;
+    }
+
+    let nst = optfsa.nstates().unwrap();
+    let matcher: Box<Matcher> = if nst < 0x80 {
+        box table::TableFSARunner::<i8>::new_with_optimized_fsa(optfsa, mode)
+    }
+    else if nst < 0x8000 {
+        box table::TableFSARunner::<i16>::new_with_optimized_fsa(optfsa, mode)
+    }
+    else if nst < 0x80000000 {
+        box table::TableFSARunner::<i32>::new_with_optimized_fsa(optfsa, mode)
     }
+    else {
+        box table::TableFSARunner::<i64>::new_with_optimized_fsa(optfsa, mode)
+    };
+    (matcher, warnings)
 }
 
 #[bench]
@@ -80,7 +171,7 @@ pub fn compile_rexp_bench(b: &mut Bencher) {
     use rexp::compile_rexp_nfa;
     //let rexp = "(([02468][13579]){5})+((A[02468]B[13579]C){5})+";
     let rexp = "(([02468][13579]){5})+";
-    let nfa = compile_rexp_nfa(rexp);
+    let nfa = compile_rexp_nfa(rexp).expect("Compile error");
     b.iter(|| {
         optimize_fsa(nfa.clone())
     })
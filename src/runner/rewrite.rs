@@ -0,0 +1,54 @@
+use runner::Runner;
+use automata::rmeps::rmeps;
+use automata::vector::ByteVectorFST;
+use rexp::transducer::rewrite;
+
+use std::io::{Read,BufReader,BufRead,stdout,Write,BufWriter};
+
+/// Runner printing rewrites of whole lines by a transducer
+///
+/// The shortest output of each line is printed. Lines without any output are
+/// not printed.
+pub struct RewriteRunner {
+    fst: ByteVectorFST,
+}
+
+impl RewriteRunner {
+    pub fn new(fst: ByteVectorFST) -> RewriteRunner {
+        RewriteRunner {
+            fst: rmeps(fst)
+        }
+    }
+
+    /// Returns the shortest output for the whole `line`
+    pub fn rewrite(&self, line: &[u8]) -> Option<Vec<u8>> {
+        rewrite(&self.fst, line)
+    }
+}
+
+impl<R: Read> Runner<R> for RewriteRunner {
+    fn run(&mut self, input: R) {
+        let out = stdout();
+        let mut out = BufWriter::new(out.lock());
+        let input = BufReader::with_capacity(8 * 1024, input);
+
+        for l in input.split(b'\n') {
+            let l = l.expect("Read failed");
+            if let Some(o) = self.rewrite(&l) {
+                out.write_all(&o).expect("Write error");
+                out.write_all(b"\n").expect("Write error");
+            }
+        }
+    }
+}
+
+#[test]
+pub fn rewrite_runner_test() {
+    use rexp::transducer::compile_rexp_fst;
+
+    let fst = compile_rexp_fst(r#"([0-9]+:"N"|[^0-9])*"#).expect("Compile error");
+    let r = RewriteRunner::new(fst);
+    // digits are rewritten all at once rather than one by one
+    assert_eq!(r.rewrite(b"a12b3"), Some(b"aNbN".to_vec()));
+    assert_eq!(r.rewrite(b""), Some(b"".to_vec()));
+}
@@ -0,0 +1,129 @@
+use runner::{make_head_skipper,Runner,MatchMode};
+use automata::{StateMachine,Arc,FSA,PatternSet,Semiring};
+use automata::rmeps::rmeps;
+use automata::concat::concat;
+use automata::determinize::determinize;
+use automata::minimize::minimize;
+use rexp::set::PatternSetFSA;
+
+use std::io::{Read,BufReader,BufRead,stdout,Write,BufWriter};
+use std::collections::BTreeSet;
+
+/// DFA matcher reporting which patterns in a set are matched
+pub struct PatternSetMatcher {
+    transition: Vec<u32>,
+    /// Pattern ids accepted at each state, empty for non-final states
+    accepts: Vec<Vec<usize>>,
+    mode: MatchMode,
+}
+
+impl PatternSetMatcher {
+    pub fn new(m: PatternSetFSA, mode: MatchMode) -> PatternSetMatcher {
+        let m = match mode {
+            MatchMode::Substring => rmeps(concat(make_head_skipper(), m)),
+            MatchMode::WholeLine => rmeps(m),
+        };
+        let dfa = minimize(determinize(m));
+
+        let nstates = dfa.nstates().expect("Number of states should be known");
+        let dead = nstates as u32;
+        let mut trans = vec![dead; (nstates + 1) * 256];
+        let mut accepts = vec![Vec::new(); nstates + 1];
+        for st in dfa.states() {
+            let stidx = st as usize;
+            accepts[stidx] = match dfa.final_weight(&st) {
+                PatternSet::Ids(ids) => ids.into_iter().collect(),
+                PatternSet::All => panic!("Final weights must be sets of pattern ids"),
+            };
+            for arc in dfa.arcs(&st) {
+                debug_assert!(arc.weight() == PatternSet::one());
+                trans[(stidx << 8) | arc.label().index()] = arc.nextstate() as u32;
+            }
+        }
+
+        PatternSetMatcher {
+            transition: trans,
+            accepts: accepts,
+            mode: mode,
+        }
+    }
+
+    /// Returns ids of the patterns matched with `haystack`
+    pub fn matches(&self, haystack: &[u8]) -> BTreeSet<usize> {
+        let mut st = 0;
+        let mut ret = BTreeSet::new();
+        match self.mode {
+            MatchMode::Substring => {
+                ret.extend(self.accepts[st].iter().cloned());
+                for b in haystack.iter() {
+                    st = self.transition[(st << 8) | (*b as usize)] as usize;
+                    ret.extend(self.accepts[st].iter().cloned());
+                }
+            }
+            MatchMode::WholeLine => {
+                for b in haystack.iter() {
+                    st = self.transition[(st << 8) | (*b as usize)] as usize;
+                }
+                ret.extend(self.accepts[st].iter().cloned());
+            }
+        }
+        ret
+    }
+}
+
+/// Runner printing lines matched by any pattern, prefixed by matched ids
+pub struct PatternSetRunner {
+    matcher: PatternSetMatcher,
+}
+
+impl PatternSetRunner {
+    pub fn new(m: PatternSetFSA, mode: MatchMode) -> PatternSetRunner {
+        PatternSetRunner {
+            matcher: PatternSetMatcher::new(m, mode)
+        }
+    }
+}
+
+impl<R: Read> Runner<R> for PatternSetRunner {
+    fn run(&mut self, input: R) {
+        let out = stdout();
+        let mut out = BufWriter::new(out.lock());
+        let input = BufReader::with_capacity(8 * 1024, input);
+
+        for l in input.split(b'\n') {
+            let l = l.expect("Read failed");
+            let ids = self.matcher.matches(&l);
+            if ids.is_empty() {
+                continue;
+            }
+            let ids: Vec<String> = ids.iter().map(|i| i.to_string()).collect();
+            write!(out, "{}:", ids.join(",")).expect("Write error");
+            out.write_all(&l).expect("Write error");
+            out.write_all(b"\n").expect("Write error");
+        }
+    }
+}
+
+#[test]
+pub fn pattern_set_test() {
+    use rexp::Flags;
+    use rexp::set::compile_rexp_set_nfa;
+
+    let nfa = compile_rexp_set_nfa(&["foo", "ba[rz]", "o+"], Flags::default())
+        .expect("Compile error");
+    let m = PatternSetMatcher::new(nfa.clone(), MatchMode::Substring);
+    let ids = |s: &str| -> Vec<usize> { m.matches(s.as_bytes()).into_iter().collect() };
+    assert_eq!(ids("xfoox"), vec![0, 2]);
+    assert_eq!(ids("baz foo"), vec![0, 1, 2]);
+    assert_eq!(ids("bar"), vec![1]);
+    assert_eq!(ids("xyz"), vec![]);
+
+    let m = PatternSetMatcher::new(nfa, MatchMode::WholeLine);
+    let ids = |s: &str| -> Vec<usize> { m.matches(s.as_bytes()).into_iter().collect() };
+    assert_eq!(ids("oo"), vec![2]);
+    assert_eq!(ids("foo"), vec![0]);
+    assert_eq!(ids("foox"), vec![]);
+
+    assert!(compile_rexp_set_nfa(&["a", "b{3,1}"], Flags::default()).err() ==
+            Some((1, ::rexp::RexpError::InvalidRepeatBounds(3, 1))));
+}
@@ -1,8 +1,9 @@
-use runner::{optimize_fsa,Runner};
+use runner::{optimize_fsa_with_mode,Runner,MatchMode};
+use runner::matcher::{Matcher,print_matching_lines};
 use automata::{StateMachine,Arc,boolweight,FSA};
-use automata::vector::{ByteVectorFSA};
+use automata::vector::{ByteVectorFSA,ByteLabel};
 
-use std::io::{Read,BufReader,BufRead,stdout,Write,BufWriter};
+use std::io::Read;
 
 use num_traits::{NumCast,Num};
 
@@ -23,29 +24,43 @@ impl TableElement for i8 {
 
 pub struct TableFSARunner<I: TableElement> {
     transition: Vec<I>,
+    finals: Vec<bool>,
+    mode: MatchMode,
 }
 
 impl<I: TableElement> TableFSARunner<I> {
-    #[allow(dead_code)]
-    pub fn new<M: FSA<Weight=boolweight, Label=u8>>(m: M) -> TableFSARunner<I> {
-        let optfsa = optimize_fsa(m);
-        TableFSARunner::new_with_optimized_fsa(optfsa)
+    pub fn new<M: FSA<Weight=boolweight, Label=ByteLabel>>(m: M, mode: MatchMode)
+                                                            -> TableFSARunner<I> {
+        let optfsa = optimize_fsa_with_mode(m, mode);
+        TableFSARunner::new_with_optimized_fsa(optfsa, mode)
     }
 
-    pub fn new_with_optimized_fsa(optfsa: ByteVectorFSA) -> TableFSARunner<I> {
+    pub fn new_with_optimized_fsa(optfsa: ByteVectorFSA, mode: MatchMode) -> TableFSARunner<I> {
         let nstates = optfsa.nstates().expect("Number of states should be known");
 
+        // In the substring mode, missing transitions can go back to the initial
+        // state since it has a self-loop for all bytes. Otherwise, they go to
+        // an extra dead state.
+        let (nrows, missing) = match mode {
+            MatchMode::Substring => (nstates, I::zero()),
+            MatchMode::WholeLine => (nstates + 1, I::from(nstates).unwrap()),
+        };
+
         let mut trans = Vec::<I>::new();
         let mut finals = Vec::new();
 
-        for stidx in 0..nstates {
-            trans.extend_from_slice(&[I::zero(); 256]);
+        for stidx in 0..nrows {
+            trans.extend_from_slice(&[missing; 256]);
+            if stidx == nstates {
+                finals.push(false);
+                continue;
+            }
             let st = stidx as i64;
             finals.push(optfsa.final_weight(&st));
 
             for arc in optfsa.arcs(&st) {
                 let mut next = arc.nextstate();
-                let lab = arc.label() as usize;
+                let lab = arc.label().index();
                 if optfsa.final_weight(&next) {
                     next = - next;
                 }
@@ -55,35 +70,45 @@ impl<I: TableElement> TableFSARunner<I> {
 
         TableFSARunner {
             transition: trans,
+            finals: finals,
+            mode: mode,
         }
     }
 }
 
 
-impl<R: Read, I: TableElement> Runner<R> for TableFSARunner<I> {
-    fn run(&mut self, input: R) {
-        let out = stdout();
-        let mut out = BufWriter::new(out.lock());
-        let mut input = BufReader::with_capacity(8 * 1024, input);
-        let mut l = String::new();
-
-        while input.read_line(&mut l).unwrap_or(0) > 0 {
-            let mut st = I::zero();
-            let mut accepted = false;
-
-            for b in l.bytes() {
-                st = self.transition[((st.to_usize().unwrap()) << 8) | (b as usize)];
-                if st < I::zero() {
-                    accepted = true;
-                    st = I::zero() - st;
+impl<I: TableElement> Matcher for TableFSARunner<I> {
+    fn is_match(&self, line: &[u8]) -> bool {
+        let mut st = I::zero();
+        match self.mode {
+            MatchMode::Substring => {
+                // the initial state cannot be marked by the sign
+                if self.finals[0] {
+                    return true;
+                }
+                for b in line.iter() {
+                    st = self.transition[((st.to_usize().unwrap()) << 8) | (*b as usize)];
+                    if st < I::zero() {
+                        return true;
+                    }
                 }
+                false
             }
-
-            if accepted {
-                write!(out, "{}", l).expect("Write error");
+            MatchMode::WholeLine => {
+                for b in line.iter() {
+                    st = self.transition[((st.to_usize().unwrap()) << 8) | (*b as usize)];
+                    if st < I::zero() {
+                        st = I::zero() - st;
+                    }
+                }
+                self.finals[st.to_usize().unwrap()]
             }
-            l.clear();
         }
     }
+}
 
+impl<R: Read, I: TableElement> Runner<R> for TableFSARunner<I> {
+    fn run(&mut self, input: R) {
+        print_matching_lines(self, input)
+    }
 }
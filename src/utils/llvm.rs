@@ -35,7 +35,18 @@ use llvm_sys::target_machine::{
     LLVMCreateTargetMachine, LLVMCodeGenOptLevel, LLVMRelocMode,
     LLVMCodeModel,LLVMGetDefaultTargetTriple,LLVMCreateTargetDataLayout,
     LLVMTargetMachineEmitToFile,LLVMCodeGenFileType,LLVMAddAnalysisPasses,
-    LLVMGetTargetMachineTriple
+    LLVMGetTargetMachineTriple,LLVMTargetMachineEmitToMemoryBuffer
+};
+
+use llvm_sys::core::{
+    LLVMGetBufferStart,LLVMGetBufferSize,LLVMDisposeMemoryBuffer,
+    LLVMCreateMemoryBufferWithMemoryRangeCopy
+};
+
+use llvm_sys::orc::{
+    LLVMOrcJITStackRef,LLVMOrcCreateInstance,LLVMOrcDisposeInstance,
+    LLVMOrcAddObjectFile,LLVMOrcGetSymbolAddress,LLVMOrcGetErrorMsg,
+    LLVMOrcErrorCode,LLVMOrcModuleHandle
 };
 
 #[allow(unused_imports)]
@@ -439,6 +450,18 @@ impl Drop for TargetMachine {
 impl TargetMachine {
     #[allow(dead_code)]
     pub fn new(target_triple: *const i8) -> Result<Self, String> {
+        TargetMachine::new_with_reloc_mode(target_triple, LLVMRelocMode::LLVMRelocDefault)
+    }
+
+    /// Creates a machine emitting position-independent code
+    ///
+    /// This is required for objects linked into PIE executables.
+    pub fn new_pic(target_triple: *const i8) -> Result<Self, String> {
+        TargetMachine::new_with_reloc_mode(target_triple, LLVMRelocMode::LLVMRelocPIC)
+    }
+
+    fn new_with_reloc_mode(target_triple: *const i8, reloc: LLVMRelocMode)
+                           -> Result<Self, String> {
         let mut target = null_mut();
         let mut err_msg_ptr = null_mut();
 
@@ -468,7 +491,7 @@ impl TargetMachine {
                                         cpu,
                                         features,
                                         LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive,
-                                        LLVMRelocMode::LLVMRelocDefault,
+                                        reloc,
                                         LLVMCodeModel::LLVMCodeModelDefault);
             //eprintln!("TARGET = {}", CStr::from_ptr(LLVMGetTargetMachineTriple(target_machine)).to_str().expect("utf8err"));
         }
@@ -499,6 +522,89 @@ impl TargetMachine {
             }
         }
     }
+
+    /// Emits the module as an object file in memory
+    pub fn emit_object(&self, module: &Module) -> Result<Vec<u8>, String> {
+        unsafe {
+            let mut err: *mut c_char = ptr::null_mut();
+            let mut buf = ptr::null_mut();
+            let result = LLVMTargetMachineEmitToMemoryBuffer(
+                self.tm,
+                module.module,
+                LLVMCodeGenFileType::LLVMObjectFile,
+                &mut err,
+                &mut buf);
+            if result != 0 {
+                let what = CStr::from_ptr(err).to_string_lossy().into_owned();
+                LLVMDisposeMessage(err);
+                return Err(what);
+            }
+            let start = LLVMGetBufferStart(buf) as *const u8;
+            let obj = ::std::slice::from_raw_parts(start, LLVMGetBufferSize(buf)).to_vec();
+            LLVMDisposeMemoryBuffer(buf);
+            Ok(obj)
+        }
+    }
+}
+
+/// ORC JIT stack for loading object files emitted beforehand
+pub struct OrcJIT {
+    stack: LLVMOrcJITStackRef,
+}
+
+impl Drop for OrcJIT {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMOrcDisposeInstance(self.stack);
+        }
+    }
+}
+
+// Objects emitted by `CompileContext` don't refer to external symbols.
+extern "C" fn resolve_nothing(_name: *const c_char, _ctx: *mut ::std::os::raw::c_void) -> u64 {
+    0
+}
+
+impl OrcJIT {
+    /// Creates a JIT stack that takes ownership of the target machine
+    pub fn new(tm: TargetMachine) -> Self {
+        let stack = unsafe { LLVMOrcCreateInstance(tm.tm) };
+        mem::forget(tm);
+        OrcJIT {
+            stack: stack
+        }
+    }
+
+    fn error_message(&self) -> String {
+        unsafe {
+            CStr::from_ptr(LLVMOrcGetErrorMsg(self.stack)).to_string_lossy().into_owned()
+        }
+    }
+
+    pub fn add_object(&mut self, obj: &[u8]) -> Result<(), String> {
+        unsafe {
+            // The JIT stack takes ownership of the buffer.
+            let buf = LLVMCreateMemoryBufferWithMemoryRangeCopy(
+                obj.as_ptr() as *const c_char, obj.len(), c_str!("object"));
+            let mut handle: LLVMOrcModuleHandle = 0;
+            match LLVMOrcAddObjectFile(self.stack, &mut handle, buf,
+                                       Some(resolve_nothing), null_mut()) {
+                LLVMOrcErrorCode::LLVMOrcErrSuccess => Ok(()),
+                _ => Err(self.error_message())
+            }
+        }
+    }
+
+    /// Returns the address of the symbol, or 0 if not found
+    pub fn get_function_addr(&self, name: *const c_char) -> u64 {
+        let mut addr = 0;
+        unsafe {
+            match LLVMOrcGetSymbolAddress(self.stack, &mut addr, name) {
+                LLVMOrcErrorCode::LLVMOrcErrSuccess => addr,
+                _ => 0
+            }
+        }
+    }
 }
 
 /// Wraps LLVM's builder class to provide a nicer API and ensure we
@@ -1,2 +1,4 @@
+#[cfg(feature = "jit")]
 pub mod llvm;
+#[cfg_attr(not(feature = "jit"), allow(dead_code))]
 pub mod rawbuffer;